@@ -0,0 +1,185 @@
+#ifndef CONTEXT_RUNTIME_H
+#define CONTEXT_RUNTIME_H
+
+/* Generated by cbindgen from src/capi.rs; run scripts/generate-c-header.sh after changing it. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// What a function did; anything but `Ok` leaves a message in `context_last_error`.
+typedef enum ContextStatus {
+  CONTEXT_STATUS_OK = 0,
+  // A required pointer was null or a string wasn't valid UTF-8.
+  CONTEXT_STATUS_INVALID_ARGUMENT = 1,
+  CONTEXT_STATUS_DOCUMENT_NOT_FOUND = 2,
+  // The edit range is past the end, reversed or splits a character.
+  CONTEXT_STATUS_INVALID_RANGE = 3,
+  CONTEXT_STATUS_BACKEND_UNAVAILABLE = 4,
+  CONTEXT_STATUS_COMPILATION_FAILED = 5,
+  CONTEXT_STATUS_INTERNAL = 6,
+} ContextStatus;
+
+typedef enum ContextHighlightKind {
+  CONTEXT_HIGHLIGHT_KIND_KEYWORD = 0,
+  CONTEXT_HIGHLIGHT_KIND_COMMAND = 1,
+  CONTEXT_HIGHLIGHT_KIND_OPTION = 2,
+  CONTEXT_HIGHLIGHT_KIND_TEXT = 3,
+  CONTEXT_HIGHLIGHT_KIND_COMMENT = 4,
+  CONTEXT_HIGHLIGHT_KIND_ENVIRONMENT = 5,
+} ContextHighlightKind;
+
+typedef enum ContextSeverity {
+  CONTEXT_SEVERITY_ERROR = 0,
+  CONTEXT_SEVERITY_WARNING = 1,
+  CONTEXT_SEVERITY_INFO = 2,
+} ContextSeverity;
+
+// A runtime and the documents opened in it.
+typedef struct ContextHandle ContextHandle;
+
+// Settings for `context_runtime_new`; null strings keep the defaults.
+typedef struct ContextRuntimeOptions {
+  // Prefer the compile server over a local `mtxrun`.
+  bool remote;
+  const char *server_url;
+  const char *auth_token;
+  const char *local_executable;
+  const char *output_dir;
+} ContextRuntimeOptions;
+
+// A highlighted byte range of the document source.
+typedef struct ContextHighlight {
+  size_t start;
+  size_t end;
+  enum ContextHighlightKind kind;
+} ContextHighlight;
+
+typedef struct ContextHighlightList {
+  struct ContextHighlight *items;
+  size_t len;
+} ContextHighlightList;
+
+// A problem in a byte range of the document source.
+typedef struct ContextDiagnostic {
+  size_t start;
+  size_t end;
+  enum ContextSeverity severity;
+  char *message;
+} ContextDiagnostic;
+
+typedef struct ContextDiagnosticList {
+  struct ContextDiagnostic *items;
+  size_t len;
+} ContextDiagnosticList;
+
+// The outcome of `context_compile`. The PDF is a file at `pdf_path`, `pdf_len`
+// bytes at `pdf_data` or a download at `pdf_url`; the others are null.
+typedef struct ContextCompileResult {
+  bool success;
+  char *pdf_path;
+  uint8_t *pdf_data;
+  size_t pdf_len;
+  char *pdf_url;
+  char *log;
+  size_t error_count;
+  size_t warning_count;
+} ContextCompileResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last failure on this thread, or null. It stays valid until
+// the next failing call on the same thread.
+const char *context_last_error(void);
+
+// Creates a runtime, or returns null and sets `context_last_error`.
+//
+// # Safety
+// `options` is null or points to options whose strings are null or NUL-terminated.
+struct ContextHandle *context_runtime_new(const struct ContextRuntimeOptions *options);
+
+// # Safety
+// `handle` is null or was returned by `context_runtime_new` and isn't used afterwards.
+void context_runtime_free(struct ContextHandle *handle);
+
+// Opens a document, replacing it if it is already open.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` and `content` are NUL-terminated.
+enum ContextStatus context_open(const struct ContextHandle *handle,
+                                const char *uri,
+                                const char *content);
+
+// Replaces the bytes `start..end` of an open document with `text`.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` and `text` are NUL-terminated.
+enum ContextStatus context_update(const struct ContextHandle *handle,
+                                  const char *uri,
+                                  size_t start,
+                                  size_t end,
+                                  const char *text);
+
+// Closes a document; closing one that isn't open does nothing.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated.
+enum ContextStatus context_close(const struct ContextHandle *handle, const char *uri);
+
+// The source of an open document, or null. Free it with `context_string_free`.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated.
+char *context_document_source(const struct ContextHandle *handle, const char *uri);
+
+// # Safety
+// `value` is null or was returned by this library and isn't used afterwards.
+void context_string_free(char *value);
+
+// Fills `out` with the highlights of an open document; free them with
+// `context_highlight_list_free`.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+enum ContextStatus context_highlights(const struct ContextHandle *handle,
+                                      const char *uri,
+                                      struct ContextHighlightList *out);
+
+// # Safety
+// `list` is null or was filled by `context_highlights` and isn't used afterwards.
+void context_highlight_list_free(struct ContextHighlightList *list);
+
+// Fills `out` with the diagnostics of an open document; free them with
+// `context_diagnostic_list_free`.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+enum ContextStatus context_diagnostics(const struct ContextHandle *handle,
+                                       const char *uri,
+                                       struct ContextDiagnosticList *out);
+
+// # Safety
+// `list` is null or was filled by `context_diagnostics` and isn't used afterwards.
+void context_diagnostic_list_free(struct ContextDiagnosticList *list);
+
+// Compiles an open document, blocking until it is done. A document with ConTeXt
+// errors still gives `Ok`, with `success` false and the errors in the log. Free
+// the result with `context_compile_result_free`.
+//
+// # Safety
+// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+enum ContextStatus context_compile(const struct ContextHandle *handle,
+                                   const char *uri,
+                                   struct ContextCompileResult **out);
+
+// # Safety
+// `result` is null or was returned by `context_compile` and isn't used afterwards.
+void context_compile_result_free(struct ContextCompileResult *result);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CONTEXT_RUNTIME_H */
@@ -0,0 +1,59 @@
+import org.jetbrains.kotlin.gradle.tasks.KotlinCompile
+
+plugins {
+    id("com.android.library")
+    kotlin("android")
+}
+
+val cargoRoot: File by rootProject.extra
+val bindingsDir: Provider<Directory> by rootProject.extra
+val jniLibsDir = layout.buildDirectory.dir("jniLibs")
+
+// The ABIs cargo-ndk builds the library for
+val abis = listOf("arm64-v8a", "armeabi-v7a", "x86_64", "x86")
+
+android {
+    namespace = "org.contextgarden.runtime"
+    compileSdk = 35
+
+    defaultConfig {
+        minSdk = 24
+        consumerProguardFiles("consumer-rules.pro")
+    }
+
+    sourceSets["main"].apply {
+        java.srcDir(bindingsDir.get().asFile)
+        jniLibs.srcDir(jniLibsDir.get().asFile)
+    }
+
+    compileOptions {
+        sourceCompatibility = JavaVersion.VERSION_17
+        targetCompatibility = JavaVersion.VERSION_17
+    }
+}
+
+kotlin {
+    jvmToolchain(17)
+}
+
+dependencies {
+    implementation("net.java.dev.jna:jna:5.15.0@aar")
+    implementation("org.jetbrains.kotlinx:kotlinx-coroutines-android:1.9.0")
+}
+
+val buildRustLibraries by tasks.registering(Exec::class) {
+    description = "Builds libcontext_runtime.so for every Android ABI with cargo-ndk."
+    workingDir = cargoRoot
+    val targets = abis.flatMap { listOf("-t", it) }
+    commandLine(listOf("cargo", "ndk") + targets + listOf("-o", jniLibsDir.get().asFile.path, "build", "--release", "--lib"))
+    outputs.dir(jniLibsDir)
+    outputs.upToDateWhen { false }
+}
+
+tasks.named("preBuild") {
+    dependsOn(buildRustLibraries)
+}
+
+tasks.withType<KotlinCompile>().configureEach {
+    dependsOn(rootProject.tasks.named("generateBindings"))
+}
@@ -0,0 +1,5 @@
+# JNA finds the native functions by reflection on the generated bindings
+-dontwarn java.awt.*
+-keep class com.sun.jna.** { *; }
+-keep class * implements com.sun.jna.** { *; }
+-keep class org.contextgarden.runtime.** { *; }
@@ -0,0 +1,19 @@
+plugins {
+    id("com.android.library") version "8.7.3" apply false
+    kotlin("android") version "2.0.21" apply false
+    kotlin("jvm") version "2.0.21" apply false
+}
+
+val cargoRoot = rootDir.parentFile
+val bindingsDir = layout.buildDirectory.dir("generated/uniffi")
+
+val generateBindings by tasks.registering(Exec::class) {
+    description = "Builds the host library and generates the Kotlin bindings from it."
+    workingDir = cargoRoot
+    commandLine("scripts/generate-kotlin-bindings.sh", bindingsDir.get().asFile.path)
+    outputs.dir(bindingsDir)
+    outputs.upToDateWhen { false }
+}
+
+extra["cargoRoot"] = cargoRoot
+extra["bindingsDir"] = bindingsDir
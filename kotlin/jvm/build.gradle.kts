@@ -0,0 +1,32 @@
+import org.jetbrains.kotlin.gradle.tasks.KotlinCompile
+
+plugins {
+    kotlin("jvm")
+}
+
+val cargoRoot: File by rootProject.extra
+val bindingsDir: Provider<Directory> by rootProject.extra
+
+kotlin {
+    jvmToolchain(17)
+}
+
+sourceSets.main {
+    kotlin.srcDir(bindingsDir)
+}
+
+dependencies {
+    implementation("net.java.dev.jna:jna:5.15.0")
+    implementation("org.jetbrains.kotlinx:kotlinx-coroutines-core:1.9.0")
+    testImplementation(kotlin("test"))
+}
+
+tasks.withType<KotlinCompile>().configureEach {
+    dependsOn(rootProject.tasks.named("generateBindings"))
+}
+
+tasks.test {
+    useJUnitPlatform()
+    // The host build made by `generateBindings`
+    systemProperty("jna.library.path", cargoRoot.resolve("target/debug").path)
+}
@@ -0,0 +1,46 @@
+package org.contextgarden.runtime
+
+import kotlinx.coroutines.runBlocking
+import kotlin.test.Test
+import kotlin.test.assertEquals
+import kotlin.test.assertFailsWith
+import kotlin.test.assertTrue
+
+class ContextRuntimeHandleTest {
+    @Test
+    fun editsAreAppliedToTheDocument() {
+        ContextRuntimeHandle().use { handle ->
+            handle.open("edit.tex", "\\starttext Hello \\stoptext")
+            handle.update("edit.tex", 11u, 16u, "Hallo")
+
+            assertEquals("\\starttext Hallo \\stoptext", handle.getDocumentSource("edit.tex"))
+            assertTrue(handle.getHighlights("edit.tex").isNotEmpty())
+        }
+    }
+
+    @Test
+    fun errorsAreThrownAsContextErrorFfi() {
+        ContextRuntimeHandle().use { handle ->
+            val missing = assertFailsWith<ContextErrorFfi.DocumentNotFound> {
+                handle.update("missing.tex", 0u, 0u, "x")
+            }
+            assertEquals("missing.tex", missing.uri)
+
+            handle.open("ranges.tex", "\\starttext \\stoptext")
+            val range = assertFailsWith<ContextErrorFfi.InvalidRange> {
+                handle.update("ranges.tex", 5u, 100u, "x")
+            }
+            assertEquals(20u, range.length)
+        }
+    }
+
+    @Test
+    fun asyncExportsAreSuspendFunctions() = runBlocking {
+        ContextRuntimeHandle().use { handle ->
+            assertFailsWith<ContextErrorFfi.DocumentNotFound> {
+                handle.compileAsync("missing.tex")
+            }
+            assertTrue(handle.getBackendHealth().isNotEmpty())
+        }
+    }
+}
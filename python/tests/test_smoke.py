@@ -0,0 +1,41 @@
+"""Smoke tests for the generated bindings; run scripts/generate-python-bindings.sh first."""
+
+import asyncio
+import os
+import sys
+import unittest
+
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), ".."))
+
+from context_runtime import ContextErrorFfi, ContextRuntimeHandle  # noqa: E402
+
+
+class ContextRuntimeHandleTest(unittest.TestCase):
+    def setUp(self):
+        self.handle = ContextRuntimeHandle()
+
+    def test_edits_are_applied_to_the_document(self):
+        self.handle.open("edit.tex", r"\starttext Hello \stoptext")
+        self.handle.update("edit.tex", 11, 16, "Hallo")
+
+        self.assertEqual(self.handle.get_document_source("edit.tex"), r"\starttext Hallo \stoptext")
+        self.assertTrue(self.handle.get_highlights("edit.tex"))
+
+    def test_errors_are_raised_as_context_error(self):
+        with self.assertRaises(ContextErrorFfi.DocumentNotFound) as missing:
+            self.handle.update("missing.tex", 0, 0, "x")
+        self.assertEqual(missing.exception.uri, "missing.tex")
+
+        self.handle.open("ranges.tex", r"\starttext \stoptext")
+        with self.assertRaises(ContextErrorFfi.InvalidRange) as invalid:
+            self.handle.update("ranges.tex", 5, 100, "x")
+        self.assertEqual(invalid.exception.length, 20)
+
+    def test_async_exports_are_coroutines(self):
+        with self.assertRaises(ContextErrorFfi.DocumentNotFound):
+            asyncio.run(self.handle.compile_async("missing.tex"))
+        self.assertTrue(asyncio.run(self.handle.get_backend_health()))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,12 @@
+#!/bin/sh
+# Builds tests/c/smoke.c against the header and the shared library and runs it.
+set -eu
+
+cd "$(dirname "$0")/.."
+cargo build --lib
+
+out_dir=target/c-smoke
+mkdir -p "$out_dir"
+${CC:-cc} -std=c99 -Wall -Wextra -Werror -Iinclude tests/c/smoke.c \
+    -Ltarget/debug -lcontext_runtime -o "$out_dir/smoke"
+LD_LIBRARY_PATH=target/debug DYLD_LIBRARY_PATH=target/debug "$out_dir/smoke"
@@ -0,0 +1,9 @@
+#!/bin/sh
+# Regenerates include/context_runtime.h from src/capi.rs; `--verify` fails instead
+# if the header is out of date.
+#
+# Usage: scripts/generate-c-header.sh [--verify]
+set -eu
+
+cd "$(dirname "$0")/.."
+cbindgen --config cbindgen.toml --crate context_runtime --output include/context_runtime.h "$@"
@@ -0,0 +1,18 @@
+#!/bin/sh
+# Generates the Kotlin bindings from a host build of the library. The bindings are
+# the same for every platform, so Android reuses them with its own libraries.
+#
+# Usage: scripts/generate-kotlin-bindings.sh [OUT_DIR]
+set -eu
+
+cd "$(dirname "$0")/.."
+out_dir=${1:-kotlin/build/generated/uniffi}
+
+cargo build --lib
+case "$(uname -s)" in
+    Darwin) library=target/debug/libcontext_runtime.dylib ;;
+    *) library=target/debug/libcontext_runtime.so ;;
+esac
+
+cargo run --bin uniffi-bindgen -- generate --library "$library" \
+    --language kotlin --out-dir "$out_dir" --no-format
@@ -0,0 +1,22 @@
+#!/bin/sh
+# Generates the Python bindings into python/ next to a copy of the library, so
+# `import context_runtime` works with python/ on the path.
+#
+# Usage: scripts/generate-python-bindings.sh [--release]
+set -eu
+
+cd "$(dirname "$0")/.."
+profile=debug
+if [ "${1:-}" = "--release" ]; then
+    profile=release
+fi
+
+cargo build --lib ${1:-}
+case "$(uname -s)" in
+    Darwin) library=libcontext_runtime.dylib ;;
+    *) library=libcontext_runtime.so ;;
+esac
+
+cargo run --bin uniffi-bindgen -- generate --library "target/$profile/$library" \
+    --language python --out-dir python --no-format
+cp "target/$profile/$library" python/
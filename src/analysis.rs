@@ -0,0 +1,239 @@
+//! What editors show while a document is typed: diagnostics, the section outline
+//! and command completions. Pure Rust without I/O, so it also runs in a browser
+//! (see the `wasm` feature).
+
+use std::ops::Range;
+use logos::Logos;
+use serde::{Deserialize, Serialize};
+use crate::diagnostic::Diagnostic;
+use crate::highlight::{highlight, Highlight};
+use crate::lexer::Token;
+use crate::parser::parse_text;
+use crate::syntax::{SyntaxKind, SyntaxTree};
+
+/// Commands the diagnostics pass knows, and completion offers.
+pub const KNOWN_COMMANDS: &[&str] = &[
+    "setupbodyfont", "setuppapersize", "setupmargins", "setuphead",
+    "setuplist", "setupitemize", "setupenumerate", "setupdescription",
+    "definefont", "definecolor", "definelayout", "setupcolor",
+    "input", "component", "product", "environment", "project",
+    "em", "bf", "it", "tt", "rm", "sf", "sc", "sl",
+    "item", "head", "subhead", "subsubhead", "title", "subject",
+    "page", "blank", "space", "par", "break", "hfill", "vfill",
+    "starttext", "stoptext", "startdocument", "stopdocument",
+];
+
+/// Environments the diagnostics pass knows, without their `\start`/`\stop`.
+pub const KNOWN_ENVIRONMENTS: &[&str] = &[
+    "document", "text", "itemize", "enumerate", "description",
+    "table", "tabulate", "figure", "float", "framed",
+    "typing", "verbatim", "quote", "quotation", "lines",
+    "formula", "math", "alignment", "combinations", "columns",
+];
+
+// Sectioning commands and their outline level, numbered and unnumbered alike.
+const SECTIONS: &[(&str, u32)] = &[
+    ("part", 1),
+    ("chapter", 2), ("title", 2),
+    ("section", 3), ("subject", 3),
+    ("subsection", 4), ("subsubject", 4),
+    ("subsubsection", 5), ("subsubsubject", 5),
+];
+
+pub fn is_known_command(name: &str) -> bool {
+    KNOWN_COMMANDS.contains(&name) || section_level(name).is_some()
+}
+
+pub fn is_known_environment(name: &str) -> bool {
+    KNOWN_ENVIRONMENTS.contains(&name) || section_level(name).is_some()
+}
+
+fn section_level(name: &str) -> Option<u32> {
+    SECTIONS.iter().find(|(section, _)| *section == name).map(|(_, level)| *level)
+}
+
+/// A parsed document with everything an editor asks for on each change.
+#[derive(Debug)]
+pub struct Analysis {
+    tree: SyntaxTree,
+}
+
+impl Analysis {
+    pub fn new(source: &str) -> Self {
+        Self { tree: parse_text(source) }
+    }
+
+    pub fn highlights(&self) -> Vec<Highlight> {
+        highlight(&self.tree.root())
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        diagnostics(&self.tree)
+    }
+}
+
+/// Unknown commands and environments, and syntax errors.
+pub fn diagnostics(tree: &SyntaxTree) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for node in tree.root().descendants() {
+        match node.kind() {
+            SyntaxKind::Command => {
+                if let Some(name_token) = node.first_token() {
+                    let name = name_token.text().trim_start_matches('\\');
+                    if !is_known_command(name) {
+                        diagnostics.push(Diagnostic::warning(
+                            name_token.text_range().start().into(),
+                            name_token.text_range().len().into(),
+                            format!("Unknown command: \\{}", name),
+                        ));
+                    }
+                }
+            }
+            SyntaxKind::Environment => {
+                if let Some(name_token) = node.first_token() {
+                    let name = name_token.text().trim_start_matches(r"\start");
+                    if !is_known_environment(name) {
+                        diagnostics.push(Diagnostic::warning(
+                            name_token.text_range().start().into(),
+                            name_token.text_range().len().into(),
+                            format!("Unknown environment: {}", name),
+                        ));
+                    }
+                }
+            }
+            SyntaxKind::Error => {
+                if let Some(token) = node.first_token() {
+                    diagnostics.push(Diagnostic::error(
+                        token.text_range().start().into(),
+                        token.text_range().len().into(),
+                        "Syntax error".to_string(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+/// A heading, e.g. `\section{Title}` or `\startchapter[title={Title}]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutlineItem {
+    /// 1 for parts, 2 for chapters and so on.
+    pub level: u32,
+    pub title: String,
+    /// The heading command in the source, in bytes.
+    pub range: Range<usize>,
+}
+
+/// The document's headings in source order; nesting follows from their levels.
+pub fn outline(source: &str) -> Vec<OutlineItem> {
+    let tokens: Vec<(Token, Range<usize>)> = Token::lexer(source)
+        .spanned()
+        .filter_map(|(token, span)| token.ok().map(|token| (token, span)))
+        .collect();
+
+    let mut items = Vec::new();
+    for (index, (token, span)) in tokens.iter().enumerate() {
+        let (name, is_environment) = match token {
+            Token::Command => (&source[span.start + 1..span.end], false),
+            Token::StartEnv => (&source[span.start + r"\start".len()..span.end], true),
+            _ => continue,
+        };
+        let Some(level) = section_level(name) else {
+            continue;
+        };
+        let rest = &tokens[index + 1..];
+        let title = if is_environment {
+            environment_title(source, rest)
+        } else {
+            command_title(source, rest)
+        };
+        items.push(OutlineItem { level, title: title.unwrap_or_default(), range: span.clone() });
+    }
+    items
+}
+
+// `\section[reference]{Title}`: the first argument after any options.
+fn command_title(source: &str, rest: &[(Token, Range<usize>)]) -> Option<String> {
+    let mut rest = rest.iter().skip_while(|(token, _)| *token == Token::Options);
+    let (Token::BraceOpen, open) = rest.next()? else {
+        return None;
+    };
+    let mut depth = 1;
+    for (token, span) in rest {
+        match token {
+            Token::BraceOpen => depth += 1,
+            Token::BraceClose => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(source[open.end..span.start].trim().to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// `\startsection[title={Title},reference=...]`: the `title` key of the options.
+fn environment_title(source: &str, rest: &[(Token, Range<usize>)]) -> Option<String> {
+    let (Token::Options, span) = rest.first()? else {
+        return None;
+    };
+    let options = &source[span.start + 1..span.end - 1];
+    let value = options.split(',')
+        .find_map(|option| option.trim().strip_prefix("title")?.trim_start().strip_prefix('='))?
+        .trim();
+    let value = value.strip_prefix('{').and_then(|value| value.strip_suffix('}')).unwrap_or(value);
+    Some(value.trim().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompletionKind {
+    Command,
+    Environment,
+}
+
+/// Something to insert at the cursor, replacing the partial command before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completion {
+    /// What to insert, e.g. `\setuphead` or `\startitemize`.
+    pub label: String,
+    pub kind: CompletionKind,
+    /// What it replaces, from the backslash to the cursor, in bytes.
+    pub range: Range<usize>,
+}
+
+/// Commands and environments matching the partial command before `offset`, or
+/// none if the cursor isn't after a backslash and letters.
+pub fn completions(source: &str, offset: usize) -> Vec<Completion> {
+    if offset > source.len() || !source.is_char_boundary(offset) {
+        return Vec::new();
+    }
+    let before = &source[..offset];
+    let prefix_start = before.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    if !before[..prefix_start].ends_with('\\') {
+        return Vec::new();
+    }
+    let prefix = &before[prefix_start..];
+    let range = prefix_start - 1..offset;
+
+    let commands = KNOWN_COMMANDS.iter()
+        .copied()
+        .chain(SECTIONS.iter().map(|(name, _)| *name))
+        .map(|name| (name.to_string(), CompletionKind::Command));
+    let environments = KNOWN_ENVIRONMENTS.iter()
+        .chain(SECTIONS.iter().map(|(name, _)| name))
+        .flat_map(|name| [format!("start{}", name), format!("stop{}", name)])
+        .map(|name| (name, CompletionKind::Environment));
+
+    let mut completions: Vec<Completion> = commands.chain(environments)
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, kind)| Completion { label: format!("\\{}", name), kind, range: range.clone() })
+        .collect();
+    completions.sort_by(|a, b| a.label.cmp(&b.label));
+    completions.dedup_by(|a, b| a.label == b.label);
+    completions
+}
@@ -0,0 +1,154 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use regex::bytes::Regex;
+use sha2::{Digest, Sha256};
+
+/// Where the compiled PDF lives once a backend is done with it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtifactData {
+    /// The PDF itself, for callers that did not choose an output directory.
+    Bytes(Vec<u8>),
+    /// A copy owned by the caller, outside any backend-private working directory.
+    File(PathBuf),
+    /// A download location reported by a remote server.
+    Url(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilationArtifact {
+    pub data: ArtifactData,
+    pub content_hash: Option<String>,
+    pub page_count: Option<u32>,
+}
+
+impl CompilationArtifact {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            content_hash: Some(content_hash(&bytes)),
+            page_count: count_pdf_pages(&bytes),
+            data: ArtifactData::Bytes(bytes),
+        }
+    }
+
+    /// Copies the PDF into `output_dir` under a content-addressed name, so a later
+    /// compile of the same document never overwrites a file a client is still showing.
+    pub fn persist(bytes: &[u8], output_dir: &Path, stem: &str) -> std::io::Result<Self> {
+        let hash = content_hash(bytes);
+        std::fs::create_dir_all(output_dir)?;
+
+        let target = output_dir.join(format!("{}-{}.pdf", stem, &hash[..16]));
+        if !target.exists() {
+            write_atomically(&target, bytes)?;
+        }
+
+        Ok(Self {
+            data: ArtifactData::File(target),
+            content_hash: Some(hash),
+            page_count: count_pdf_pages(bytes),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            ArtifactData::File(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.data {
+            ArtifactData::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+/// Downloaded artifacts, stored as `<content hash>.pdf` so identical output is fetched once.
+#[derive(Debug, Clone)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+impl ArtifactCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `<user cache dir>/context-runtime/artifacts`, or the system temp dir when there is none.
+    pub fn default_dir() -> PathBuf {
+        dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("context-runtime")
+            .join("artifacts")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn lookup(&self, hash: &str) -> Option<CompilationArtifact> {
+        // Hashes come from servers; anything that isn't plain hex never maps to a file.
+        if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let path = self.dir.join(format!("{}.pdf", hash.to_ascii_lowercase()));
+        path.exists().then(|| CompilationArtifact {
+            page_count: std::fs::read(&path).ok().and_then(|bytes| count_pdf_pages(&bytes)),
+            content_hash: Some(hash.to_ascii_lowercase()),
+            data: ArtifactData::File(path),
+        })
+    }
+
+    pub fn store(&self, bytes: &[u8]) -> std::io::Result<CompilationArtifact> {
+        let hash = content_hash(bytes);
+        std::fs::create_dir_all(&self.dir)?;
+
+        let path = self.dir.join(format!("{}.pdf", hash));
+        if !path.exists() {
+            write_atomically(&path, bytes)?;
+        }
+
+        Ok(CompilationArtifact {
+            data: ArtifactData::File(path),
+            content_hash: Some(hash),
+            page_count: count_pdf_pages(bytes),
+        })
+    }
+}
+
+// Write to a uniquely named file next to the target and rename, so readers never see
+// a partial file and concurrent writes of the same PDF don't share one.
+fn write_atomically(target: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = target.parent().unwrap_or_else(|| Path::new("."));
+    let mut partial = tempfile::Builder::new().prefix(".partial-").tempfile_in(dir)?;
+    partial.write_all(bytes)?;
+    partial.persist(target).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Hex-encoded SHA-256 of the artifact contents.
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+static PAGE_OBJECT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/Type\s*/Page\b").unwrap());
+static PAGE_COUNT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/Count\s+(\d+)").unwrap());
+
+/// Counts pages by looking for page objects, falling back to the largest `/Count`
+/// of the page tree. Returns `None` when both are hidden in compressed object streams.
+pub fn count_pdf_pages(bytes: &[u8]) -> Option<u32> {
+    let page_objects = PAGE_OBJECT_RE.find_iter(bytes).count();
+
+    if page_objects > 0 {
+        return u32::try_from(page_objects).ok();
+    }
+
+    PAGE_COUNT_RE.captures_iter(bytes)
+        .filter_map(|caps| std::str::from_utf8(&caps[1]).ok()?.parse().ok())
+        .max()
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+use async_trait::async_trait;
+use reqwest::RequestBuilder;
+use crate::backend_traits::BackendError;
+
+/// What a request to the compile server is authenticated with.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// Sent as `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// Sent as `<header>: <key>`, e.g. `X-Api-Key`.
+    ApiKey { header: String, key: String },
+}
+
+impl Credentials {
+    pub fn apply(&self, builder: RequestBuilder) -> RequestBuilder {
+        match self {
+            Self::Bearer(token) => builder.bearer_auth(token),
+            Self::ApiKey { header, key } => builder.header(header.as_str(), key.as_str()),
+        }
+    }
+}
+
+// Secrets stay out of logs.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bearer(_) => f.write_str("Bearer(..)"),
+            Self::ApiKey { header, .. } => write!(f, "ApiKey({}: ..)", header),
+        }
+    }
+}
+
+/// A PEM certificate (chain) and its PKCS#8 PEM private key, presented to servers
+/// that require mutual TLS.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    pub certificate_pem: Vec<u8>,
+    pub private_key_pem: Vec<u8>,
+}
+
+impl fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientCertificate").finish_non_exhaustive()
+    }
+}
+
+/// Supplies credentials for the compile server, so they can change without
+/// rebuilding the client, e.g. when a login expires.
+///
+/// `credentials` is asked before every request, so implementations should cache.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    /// Credentials for the next request, or `None` to send it unauthenticated.
+    async fn credentials(&self) -> Result<Option<Credentials>, BackendError>;
+
+    /// Called once when the server answered `401` to `rejected`. Returning new
+    /// credentials repeats the request with them; `None` or the same credentials
+    /// give up, failing it with `BackendError::Unauthorized`.
+    async fn refresh(&self, rejected: Option<&Credentials>) -> Result<Option<Credentials>, BackendError>;
+
+    /// A certificate for mutual TLS, read once when the client is set up.
+    fn client_certificate(&self) -> Option<ClientCertificate> {
+        None
+    }
+}
+
+/// Credentials fixed at construction, such as `RuntimeConfig::auth_token`.
+#[derive(Debug, Clone, Default)]
+pub struct StaticCredentials {
+    credentials: Option<Credentials>,
+    certificate: Option<ClientCertificate>,
+}
+
+impl StaticCredentials {
+    pub fn new(credentials: Option<Credentials>) -> Self {
+        Self { credentials, certificate: None }
+    }
+
+    pub fn bearer(token: Option<String>) -> Self {
+        Self::new(token.map(Credentials::Bearer))
+    }
+
+    pub fn with_client_certificate(mut self, certificate: Option<ClientCertificate>) -> Self {
+        self.certificate = certificate;
+        self
+    }
+}
+
+#[async_trait]
+impl AuthProvider for StaticCredentials {
+    async fn credentials(&self) -> Result<Option<Credentials>, BackendError> {
+        Ok(self.credentials.clone())
+    }
+
+    async fn refresh(&self, _rejected: Option<&Credentials>) -> Result<Option<Credentials>, BackendError> {
+        Ok(None)
+    }
+
+    fn client_certificate(&self) -> Option<ClientCertificate> {
+        self.certificate.clone()
+    }
+}
@@ -0,0 +1,173 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use async_trait::async_trait;
+use crate::backend_traits::{
+    BackendError, BackendHealth, CompilationBackend, CompilationRequest, CompilationResult,
+};
+
+/// How long a backend that failed to respond is skipped before it is tried again.
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Tries an ordered list of backends, falling back to the next one when a backend
+/// is unavailable.
+///
+/// Only availability problems (`Unavailable`, `Network` and `Setup` errors) cause a
+/// fallback; a document that fails to compile fails the same way everywhere, and
+/// rejected credentials must be reported rather than hidden by a local compile, so
+/// other errors are returned as they are. A backend that was unavailable is tried
+/// last for `retry_after`. Results carry the name of the backend that produced them.
+#[derive(Debug)]
+pub struct BackendManager {
+    backends: Vec<Arc<dyn CompilationBackend>>,
+    // Backends that could not be created, kept so health reports can explain why.
+    setup_errors: Vec<BackendHealth>,
+    down_since: Mutex<HashMap<usize, Instant>>,
+    retry_after: Duration,
+}
+
+impl Default for BackendManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackendManager {
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+            setup_errors: Vec::new(),
+            down_since: Mutex::new(HashMap::new()),
+            retry_after: DEFAULT_RETRY_AFTER,
+        }
+    }
+
+    /// Appends a backend with lower preference than the ones already added.
+    pub fn with_backend(self, backend: Box<dyn CompilationBackend>) -> Self {
+        self.with_shared_backend(Arc::from(backend))
+    }
+
+    /// Like `with_backend`, for a backend that is also used on its own.
+    pub fn with_shared_backend(mut self, backend: Arc<dyn CompilationBackend>) -> Self {
+        self.backends.push(backend);
+        self
+    }
+
+    /// Records a backend that could not be created, e.g. because mtxrun is missing.
+    pub fn with_setup_error(mut self, name: &str, error: BackendError) -> Self {
+        self.setup_errors.push(BackendHealth::from_check(name, Err(error)));
+        self
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn CompilationBackend> {
+        self.backends.iter().map(|backend| backend.as_ref())
+    }
+
+    fn is_down(&self, index: usize) -> bool {
+        self.down_since.lock()
+            .map(|down| down.get(&index).is_some_and(|since| since.elapsed() < self.retry_after))
+            .unwrap_or(false)
+    }
+
+    fn mark(&self, index: usize, healthy: bool) {
+        if let Ok(mut down) = self.down_since.lock() {
+            if healthy {
+                down.remove(&index);
+            } else {
+                down.insert(index, Instant::now());
+            }
+        }
+    }
+
+    // Preferred order, with backends that are known to be down moved to the end.
+    fn attempt_order(&self) -> Vec<usize> {
+        let (up, down): (Vec<usize>, Vec<usize>) = (0..self.backends.len()).partition(|&i| !self.is_down(i));
+        up.into_iter().chain(down).collect()
+    }
+
+    fn unavailable(&self, failures: Vec<String>) -> BackendError {
+        let reasons: Vec<String> = self.setup_errors.iter()
+            .map(|health| format!("{}: {}", health.name, health.error.as_deref().unwrap_or("unavailable")))
+            .chain(failures)
+            .collect();
+
+        if reasons.is_empty() {
+            BackendError::Unavailable("No compilation backend configured".into())
+        } else {
+            BackendError::Unavailable(format!("No compilation backend available ({})", reasons.join("; ")))
+        }
+    }
+}
+
+#[async_trait]
+impl CompilationBackend for BackendManager {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        let mut failures = Vec::new();
+
+        for index in self.attempt_order() {
+            let backend = &self.backends[index];
+            match backend.compile(request.clone()).await {
+                Err(error) if error.is_unavailable() => {
+                    log::warn!("Backend {} unavailable, trying the next one: {}", backend.name(), error);
+                    self.mark(index, false);
+                    failures.push(format!("{}: {}", backend.name(), error));
+                }
+                result => {
+                    self.mark(index, true);
+                    return result;
+                }
+            }
+        }
+
+        Err(self.unavailable(failures))
+    }
+
+    fn fingerprint(&self) -> String {
+        let fingerprints: Vec<String> = self.backends.iter().map(|backend| backend.fingerprint()).collect();
+        format!("manager:[{}]", fingerprints.join(","))
+    }
+
+    fn name(&self) -> &str {
+        "manager"
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        let mut failures = Vec::new();
+        for (index, backend) in self.backends.iter().enumerate() {
+            let check = backend.health_check().await;
+            self.mark(index, check.is_ok());
+            match check {
+                Ok(()) => return Ok(()),
+                Err(error) => failures.push(format!("{}: {}", backend.name(), error)),
+            }
+        }
+        Err(self.unavailable(failures))
+    }
+
+    async fn health_report(&self) -> Vec<BackendHealth> {
+        let mut report = Vec::new();
+        for (index, backend) in self.backends.iter().enumerate() {
+            let check = backend.health_check().await;
+            self.mark(index, check.is_ok());
+            report.push(BackendHealth::from_check(backend.name(), check));
+        }
+        report.extend(self.setup_errors.iter().cloned());
+        report
+    }
+
+    async fn release(&self, job_id: &str) {
+        for backend in &self.backends {
+            backend.release(job_id).await;
+        }
+    }
+}
@@ -1,51 +1,183 @@
 use std::path::PathBuf;
 use std::any::Any;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
 use thiserror::Error;
 use tempfile::TempDir;
 use regex::Regex;
 use serde::Deserialize;
-use reqwest::Client;
-use which::which;
-
-#[derive(Debug)]
+use tokio_util::sync::CancellationToken;
+use crate::discovery;
+use crate::sandbox::{PolicyViolation, SandboxPolicy};
+use crate::process::{spawn_process_tree, ProcessLimits, ResourceLimit};
+use crate::progress::{CompileEvent, LogStream, ProgressTracker};
+use crate::synctex::SyncTex;
+use crate::artifact::CompilationArtifact;
+use crate::auth::AuthProvider;
+use crate::delta::UploadTracker;
+use crate::protocol;
+use crate::remote_client::RemoteClient;
+use crate::retry::RetryPolicy;
+
+#[derive(Debug, Clone, Default)]
 pub struct CompilationRequest {
     pub content: String,
     pub job_id: String,
+    /// Files included by the main source, e.g. `\input`ed chapters or environments.
+    pub files: Vec<ProjectFile>,
+    /// Cancelling stops the run (killing the ConTeXt process or aborting the HTTP
+    /// request) and makes `compile` return `BackendError::Cancelled`.
+    pub cancel: CancellationToken,
+    /// Receives progress and log lines while the run is going. Backends that can't
+    /// stream simply never send anything.
+    pub events: Option<tokio::sync::mpsc::UnboundedSender<CompileEvent>>,
+    pub options: CompileOptions,
 }
 
-#[derive(Debug)]
-pub struct CompilationResult {
-    pub success: bool,
-    pub pdf_path: Option<PathBuf>,
-    pub log: String,   
-    pub errors: Vec<CompilationError>, 
-    pub warnings: Vec<CompilationError>
+/// A file next to the main source, addressed by its path relative to it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, Deserialize)]
+pub struct ProjectFile {
+    pub path: String,
+    pub content: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct CompileResponse {
-    pub success: bool,
-    pub log: String,
-    pub output_url: Option<String>,
-    pub diagnostics: Vec<RemoteDiagnostic>,
+/// The engine `context` runs on. ConTeXt distributions ship both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextEngineChoice {
+    /// ConTeXt LMTX on LuaMetaTeX.
+    Lmtx,
+    /// ConTeXt MkIV on LuaTeX.
+    Mkiv,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RemoteDiagnostic {
-    pub message: String,
-    pub severity: String,
-    pub range: Option<RemoteRange>,
+/// How `context` is run. The default matches a plain `context file.tex`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CompileOptions {
+    /// `None` uses the installation's default engine.
+    pub engine: Option<ContextEngineChoice>,
+    /// A single pass with the `draft` mode enabled, for quick previews. References
+    /// and the table of contents may be out of date.
+    pub draft: bool,
+    /// Modes enabled with `--mode`, tested in the source with `\doifmode`.
+    pub modes: Vec<String>,
+    /// Variables passed with `--arguments`, read in the source with `\getdocumentargument`.
+    pub arguments: std::collections::BTreeMap<String, String>,
+    /// Name of the output file without extension, `--result`.
+    pub result: Option<String>,
+    /// Maximum number of passes, `--runs`.
+    pub runs: Option<u32>,
+    /// Environment files loaded before the document, `--environment`.
+    pub environments: Vec<String>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct RemoteRange {
-    pub start: u32,
-    pub end: u32,
+impl CompileOptions {
+    /// The options as `mtx-context` flags, e.g. `--mode=draft`.
+    pub fn context_arguments(&self) -> Result<Vec<String>, BackendError> {
+        self.validate()?;
+
+        let mut arguments = Vec::new();
+        match self.engine {
+            Some(ContextEngineChoice::Lmtx) => arguments.push("--luametatex".to_string()),
+            Some(ContextEngineChoice::Mkiv) => arguments.push("--luatex".to_string()),
+            None => {}
+        }
+
+        let mut modes = self.modes.clone();
+        if self.draft {
+            arguments.push("--once".to_string());
+            modes.push("draft".to_string());
+        }
+        if !modes.is_empty() {
+            arguments.push(format!("--mode={}", modes.join(",")));
+        }
+        if !self.arguments.is_empty() {
+            let pairs: Vec<String> = self.arguments.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+            arguments.push(format!("--arguments={}", pairs.join(",")));
+        }
+        if let Some(runs) = self.runs {
+            arguments.push(format!("--runs={}", runs));
+        }
+        if !self.environments.is_empty() {
+            arguments.push(format!("--environment={}", self.environments.join(",")));
+        }
+        if let Some(result) = &self.result {
+            arguments.push(format!("--result={}", result));
+        }
+        Ok(arguments)
+    }
+
+    /// Values end up in comma-separated flags, so separators and control characters
+    /// are rejected.
+    pub fn validate(&self) -> Result<(), BackendError> {
+        let invalid = |what: &str, value: &str| {
+            BackendError::InvalidOptions(format!("{} {:?} contains a separator or control character", what, value))
+        };
+        let has_separator = |value: &str| value.is_empty() || value.chars().any(|c| c == ',' || c.is_control());
+
+        for mode in &self.modes {
+            if has_separator(mode) {
+                return Err(invalid("Mode", mode));
+            }
+        }
+        for (key, value) in &self.arguments {
+            if has_separator(key) || key.contains('=') || value.chars().any(|c| c == ',' || c.is_control()) {
+                return Err(invalid("Argument", key));
+            }
+        }
+        for environment in &self.environments {
+            if has_separator(environment) {
+                return Err(invalid("Environment", environment));
+            }
+        }
+        if let Some(result) = &self.result
+            && (has_separator(result) || result.contains(['/', '\\']) || result.starts_with('.'))
+        {
+            return Err(BackendError::InvalidOptions(format!("Result name must be a plain file name: {:?}", result)));
+        }
+        if self.runs == Some(0) {
+            return Err(BackendError::InvalidOptions("Runs must be at least 1".into()));
+        }
+        Ok(())
+    }
+
+    /// Also checks that environment files are inside the job's directory, like
+    /// project files, for documents from clients that aren't trusted.
+    pub fn validate_untrusted(&self) -> Result<(), BackendError> {
+        self.validate()?;
+        match self.environments.iter().find(|environment| leaves_job_dir(Path::new(environment))) {
+            Some(environment) => Err(BackendError::InvalidOptions(format!(
+                "Environment must be relative to the main source: {:?}",
+                environment
+            ))),
+            None => Ok(()),
+        }
+    }
 }
 
-#[derive(Debug)]
+// Whether `path`, taken relative to a job's directory, can point outside of it.
+fn leaves_job_dir(path: &Path) -> bool {
+    path.components().any(|c| !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+}
+
+#[derive(Debug, Clone)]
+pub struct CompilationResult {
+    pub success: bool,
+    pub artifact: Option<CompilationArtifact>,
+    pub log: String,   
+    pub errors: Vec<CompilationError>, 
+    pub warnings: Vec<CompilationError>,
+    pub synctex: Option<SyncTex>,
+    /// Name of the backend that produced the result, see `CompilationBackend::name`.
+    pub backend: String,
+}
+
+pub use crate::protocol::{CompileResponse, RemoteDiagnostic, RemoteRange};
+
+#[derive(Debug, Clone)]
 pub struct CompilationError {
     pub line: u32,
     pub column: u32,
@@ -62,20 +194,95 @@ pub enum BackendError {
     Unavailable(String),
     #[error("Something went wrong with the setup: {0}")]
     Setup(String),
+    /// The server rejected the credentials, even after asking the `AuthProvider` to refresh them.
+    #[error("Authentication failed: {0}")]
+    Unauthorized(String),
     #[error("IO Error: {0}")]
     IO(String),
+    #[error("Compilation cancelled")]
+    Cancelled,
+    #[error("Invalid compile options: {0}")]
+    InvalidOptions(String),
+    /// The run was killed after the wall-clock limit; `log` holds its output so far.
+    #[error("Compilation timed out after {}s", .timeout.as_secs())]
+    Timeout { timeout: Duration, log: String },
+    #[error("Compilation exceeded its {resource} limit")]
+    ResourceExceeded { resource: ResourceLimit, log: String },
+    /// The document did something the backend's `SandboxPolicy` doesn't allow.
+    #[error("Compilation blocked by the sandbox policy: {violation}")]
+    PolicyViolation { violation: PolicyViolation, log: String },
+}
+
+impl BackendError {
+    /// Whether the same request may work later: the network or server failed, not
+    /// the document or the configuration.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Network(_) | Self::Unavailable(_))
+    }
+
+    /// Whether another backend should be tried: this one couldn't be reached or set
+    /// up. Errors about the document or the request would be the same everywhere.
+    pub fn is_unavailable(&self) -> bool {
+        matches!(self, Self::Network(_) | Self::Unavailable(_) | Self::Setup(_))
+    }
 }
 
 #[async_trait]
 pub trait CompilationBackend: Send + Sync + std::fmt::Debug + Any {
     fn as_any(&self) -> &dyn Any;
     async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError>;
+
+    /// Describes the settings that affect output, so cached results from a
+    /// differently configured backend are never reused.
+    fn fingerprint(&self) -> String {
+        String::new()
+    }
+
+    /// Short name reported with results and health checks, e.g. "local" or "remote".
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Checks, without compiling anything, that the backend can take jobs right now.
+    async fn health_check(&self) -> Result<(), BackendError> {
+        Ok(())
+    }
+
+    /// Health of this backend, or of every backend it delegates to.
+    async fn health_report(&self) -> Vec<BackendHealth> {
+        vec![BackendHealth::from_check(self.name(), self.health_check().await)]
+    }
+
+    /// Drops what the backend keeps between compiles of `job_id`, such as its
+    /// working files. A later compile of the job starts from scratch.
+    async fn release(&self, _job_id: &str) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendHealth {
+    pub name: String,
+    pub healthy: bool,
+    /// Why the backend is unhealthy.
+    pub error: Option<String>,
+}
+
+impl BackendHealth {
+    pub fn from_check(name: &str, check: Result<(), BackendError>) -> Self {
+        Self {
+            name: name.to_string(),
+            healthy: check.is_ok(),
+            error: check.err().map(|e| e.to_string()),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct LocalBackend {
     mtxrun_path: PathBuf,
     working_dir: TempDir,
+    output_dir: Option<PathBuf>,
+    limits: ProcessLimits,
+    sandbox: SandboxPolicy,
 }
 
 impl LocalBackend {
@@ -88,8 +295,8 @@ impl LocalBackend {
             }
             path
         } else {
-            which("mtxrun").map_err(|e| BackendError::Unavailable(
-                format!("mtxrun executable not found in system PATH. Error: {}", e)
+            discovery::find_mtxrun(None).map(|(path, _)| path).ok_or_else(|| BackendError::Unavailable(
+                "mtxrun executable not found in system PATH or any known ConTeXt installation".into()
             ))?
         };
 
@@ -99,32 +306,100 @@ impl LocalBackend {
         Ok(Self {
             mtxrun_path: actual_mtxrun_path,
             working_dir,
+            output_dir: None,
+            limits: ProcessLimits::default(),
+            sandbox: SandboxPolicy::default(),
         })
     }
 
-    async fn create_temp_file(&self, job_id: &str, content: &str) -> Result<PathBuf, BackendError> {
-        let file_path = self.working_dir.path().join(format!("{}.tex", job_id));
-        
-        tokio::fs::write(&file_path, content)
+    /// Copy each PDF into `output_dir` instead of returning its bytes.
+    pub fn with_output_dir(mut self, output_dir: Option<PathBuf>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ProcessLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Restricts what documents can do, for compiling untrusted sources.
+    pub fn with_sandbox(mut self, sandbox: SandboxPolicy) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub fn mtxrun_path(&self) -> &Path {
+        &self.mtxrun_path
+    }
+
+    pub(crate) fn working_dir(&self) -> &Path {
+        self.working_dir.path()
+    }
+
+    pub(crate) fn limits(&self) -> &ProcessLimits {
+        &self.limits
+    }
+
+    pub(crate) fn sandbox(&self) -> &SandboxPolicy {
+        &self.sandbox
+    }
+
+    // mtxrun in `dir`, wrapped and restricted as the sandbox policy says.
+    pub(crate) async fn mtxrun_command(&self, dir: &Path) -> Result<tokio::process::Command, BackendError> {
+        self.sandbox.command(&self.mtxrun_path, dir).await
+            .map_err(|e| BackendError::Unavailable(e.to_string()))
+    }
+
+    // Writes the request's source and project files into the job's own directory and
+    // returns the source's path. Each job id gets a directory, so jobs running side by
+    // side never share files; it is kept between compiles of the same job, which lets
+    // ConTeXt start from the previous run's data.
+    pub(crate) async fn prepare_job(&self, request: &CompilationRequest) -> Result<PathBuf, BackendError> {
+        let stem = file_stem_for(&request.job_id);
+        let dir = self.working_dir.path().join(&stem);
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| BackendError::IO(e.to_string()))?;
+
+        for file in &request.files {
+            let relative = Path::new(&file.path);
+            if leaves_job_dir(relative) {
+                return Err(BackendError::IO(format!("Project file must be relative to the main source: {}", file.path)));
+            }
+
+            let target = dir.join(relative);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| BackendError::IO(e.to_string()))?;
+            }
+
+            tokio::fs::write(&target, &file.content)
+                .await
+                .map_err(|e| BackendError::IO(e.to_string()))?;
+        }
+
+        let source_file = dir.join(format!("{}.tex", stem));
+        tokio::fs::write(&source_file, &request.content)
             .await
             .map_err(|e| BackendError::IO(e.to_string()))?;
-            
-        Ok(file_path)
+
+        Ok(source_file)
     }
 
-    async fn process_output(
+    pub(crate) async fn process_output(
         &self,
-        output: std::process::Output,
+        exited_ok: bool,
+        full_log: String,
         source_file: &Path,
+        options: &CompileOptions,
     ) -> Result<CompilationResult, BackendError> {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let full_log = format!("{}\n\nSTDERR:\n{}", stdout, stderr);
-        
+        let synctex = self.load_synctex(source_file);
+
         // Check for PDF output
-        let pdf_path = if output.status.success() {
-            let pdf_path = source_file.with_extension("pdf");
-            pdf_path.exists().then_some(pdf_path)
+        let artifact = if exited_ok {
+            self.collect_artifact(source_file, options.result.as_deref(), synctex.as_ref()).await?
         } else {
             None
         };
@@ -133,14 +408,62 @@ impl LocalBackend {
         let result = self.parse_compiler_output(&full_log);
 
         Ok(CompilationResult {
-            success: output.status.success(),
-            pdf_path,
+            success: exited_ok,
+            artifact,
             log: full_log,
             errors: result.errors,
             warnings: result.warnings,
+            synctex,
+            backend: "local".to_string(),
         })
     }
 
+    // The job directory is private and reused per job id, so the PDF is either
+    // read into memory or copied to the caller's output directory. `--result` renames
+    // the PDF but not the SyncTeX file.
+    async fn collect_artifact(
+        &self,
+        source_file: &Path,
+        result_name: Option<&str>,
+        synctex: Option<&SyncTex>,
+    ) -> Result<Option<CompilationArtifact>, BackendError> {
+        let pdf_path = match result_name {
+            Some(name) => source_file.with_file_name(format!("{}.pdf", name)),
+            None => source_file.with_extension("pdf"),
+        };
+        let bytes = match tokio::fs::read(&pdf_path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(BackendError::IO(e.to_string())),
+        };
+
+        let mut artifact = match &self.output_dir {
+            Some(output_dir) => {
+                let stem = result_name
+                    .or_else(|| source_file.file_stem().and_then(|s| s.to_str()))
+                    .unwrap_or("output");
+                CompilationArtifact::persist(&bytes, output_dir, stem)
+                    .map_err(|e| BackendError::IO(e.to_string()))?
+            }
+            None => CompilationArtifact::from_bytes(bytes),
+        };
+
+        if artifact.page_count.is_none() {
+            artifact.page_count = synctex.map(SyncTex::page_count).filter(|&pages| pages > 0);
+        }
+
+        Ok(Some(artifact))
+    }
+
+    fn load_synctex(&self, source_file: &Path) -> Option<SyncTex> {
+        // ConTeXt writes `<job>.synctex.gz` by default, or plain `<job>.synctex` when compression is off.
+        ["synctex.gz", "synctex"].iter()
+            .map(|ext| source_file.with_extension(ext))
+            .find(|path| path.exists())
+            .and_then(|path| SyncTex::load(&path).ok())
+            .map(|synctex| synctex.with_main_input(source_file))
+    }
+
 
     pub fn parse_compiler_output(&self, output: &str) -> CompilationResult {
         let mut errors = Vec::new();
@@ -158,10 +481,12 @@ impl LocalBackend {
         
         CompilationResult {
             success: errors.is_empty(),
-            pdf_path: None,
+            artifact: None,
             log: output.to_string(),
             errors,
             warnings,
+            synctex: None,
+            backend: "local".to_string(),
         }
     }
 
@@ -186,6 +511,75 @@ impl LocalBackend {
     }
 }
 
+// Collects a pipe line by line, forwarding each line (and progress on stdout) as it arrives.
+async fn read_pipe<R: tokio::io::AsyncRead + Unpin>(
+    pipe: Option<R>,
+    stream: LogStream,
+    events: Option<tokio::sync::mpsc::UnboundedSender<CompileEvent>>,
+) -> Vec<u8> {
+    use tokio::io::AsyncBufReadExt;
+
+    let mut buffer = Vec::new();
+    let Some(pipe) = pipe else { return buffer };
+    let mut reader = tokio::io::BufReader::new(pipe);
+    let mut tracker = ProgressTracker::new();
+
+    loop {
+        let start = buffer.len();
+        // A read error just ends the log early; whatever arrived is still useful.
+        match reader.read_until(b'\n', &mut buffer).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let Some(events) = &events else { continue };
+        let line = String::from_utf8_lossy(&buffer[start..]).into_owned();
+        if stream == LogStream::Stdout
+            && let Some(progress) = tracker.feed_line(&line)
+        {
+            let _ = events.send(CompileEvent::Progress(progress));
+        }
+        let _ = events.send(CompileEvent::Log { stream, chunk: line });
+    }
+    buffer
+}
+
+// Waits for the pipe readers, which finish once every process holding the pipes has exited.
+async fn collect_log(
+    stdout: tokio::task::JoinHandle<Vec<u8>>,
+    stderr: tokio::task::JoinHandle<Vec<u8>>,
+) -> String {
+    let stdout = stdout.await.unwrap_or_default();
+    let stderr = stderr.await.unwrap_or_default();
+    format!("{}\n\nSTDERR:\n{}", String::from_utf8_lossy(&stdout), String::from_utf8_lossy(&stderr))
+}
+
+pub(crate) async fn sleep_or_forever(timeout: Option<Duration>) {
+    sleep_until_or_forever(timeout.map(|timeout| tokio::time::Instant::now() + timeout)).await
+}
+
+async fn sleep_until_or_forever(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+// Job ids are usually document URIs, which aren't valid file names. Replacing the
+// other characters can map two ids to one name, e.g. `a/b.tex` and `a_b.tex`, so a
+// short hash of the whole id is appended.
+fn file_stem_for(job_id: &str) -> String {
+    let stem: String = job_id.trim_start_matches("file://")
+        .trim_end_matches(".tex")
+        .trim_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    let stem = if stem.is_empty() { "document" } else { stem.as_str() };
+
+    format!("{}-{}", stem, &crate::artifact::content_hash(job_id.as_bytes())[..8])
+}
+
 #[async_trait]
 impl CompilationBackend for LocalBackend {
     fn as_any(&self) -> &dyn Any {
@@ -193,39 +587,146 @@ impl CompilationBackend for LocalBackend {
     }
 
     async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
-        use tokio::process::Command;
+        if request.cancel.is_cancelled() {
+            return Err(BackendError::Cancelled);
+        }
 
-        let temp_file = self.create_temp_file(&request.job_id, &request.content).await?;
-        let temp_file_name = temp_file.file_name()
-                                    .and_then(|s| s.to_str())
-                                    .ok_or_else(|| BackendError::IO("Failed to get temp file name".into()))?;
+        let options = request.options.context_arguments()?;
+        let temp_file = self.prepare_job(&request).await?;
+        let (Some(job_dir), Some(temp_file_name)) = (temp_file.parent(), temp_file.file_name().and_then(|s| s.to_str())) else {
+            return Err(BackendError::IO("Failed to get temp file name".into()));
+        };
 
-        let output = Command::new(&self.mtxrun_path) 
+        // Building the command can run mtxrun to find the TeX tree, so it is bounded by
+        // the same deadline and cancellation as the run itself.
+        let deadline = self.limits.timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+        let mut command = tokio::select! {
+            command = self.mtxrun_command(job_dir) => command?,
+            _ = request.cancel.cancelled() => return Err(BackendError::Cancelled),
+            _ = sleep_until_or_forever(deadline) => {
+                return Err(BackendError::Timeout {
+                    timeout: self.limits.timeout.unwrap_or_default(),
+                    log: "Timed out while preparing mtxrun".to_string(),
+                });
+            }
+        };
+        command
             .arg("--script")                          
             .arg("context")                          
             .arg("--batchmode")
             .arg("--nonstopmode")
             .arg("--purgeall")
+            .arg("--synctex")
+            .args(self.sandbox.context_arguments())
+            .args(&options)
             .arg(temp_file_name)
-            .current_dir(&self.working_dir)
-            .output()
-            .await
-            .map_err(|e| BackendError::Compilation(format!("Failed to execute mtxrun: {}", e)))?;
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        // The guard also fires when this future is dropped, e.g. a superseded live-preview compile.
+        let (mut child, mut process_tree) = spawn_process_tree(&mut command, &self.limits)
+            .map_err(|e| BackendError::Unavailable(format!("Failed to execute mtxrun: {}", e)))?;
+
+        // Read the pipes in the background so the output survives a kill.
+        let stdout = tokio::spawn(read_pipe(child.stdout.take(), LogStream::Stdout, request.events.clone()));
+        let stderr = tokio::spawn(read_pipe(child.stderr.take(), LogStream::Stderr, request.events.clone()));
+
+        let status = tokio::select! {
+            status = child.wait() => {
+                status.map_err(|e| BackendError::Compilation(format!("Failed to execute mtxrun: {}", e)))?
+            }
+            _ = request.cancel.cancelled() => {
+                process_tree.kill();
+                return Err(BackendError::Cancelled);
+            }
+            _ = sleep_until_or_forever(deadline) => {
+                process_tree.kill();
+                return Err(BackendError::Timeout {
+                    timeout: self.limits.timeout.unwrap_or_default(),
+                    log: collect_log(stdout, stderr).await,
+                });
+            }
+        };
+        // Anything the run left in the background still holds the pipes open, so it is
+        // stopped before the log is read to the end.
+        process_tree.kill();
+
+        let log = collect_log(stdout, stderr).await;
+        if let Some(resource) = self.limits.exceeded_by(&status, &log) {
+            return Err(BackendError::ResourceExceeded { resource, log });
+        }
+        if let Some(violation) = self.sandbox.find_violation(&log) {
+            return Err(BackendError::PolicyViolation { violation, log });
+        }
 
-        self.process_output(output, &temp_file).await
+        self.process_output(status.success(), log, &temp_file, &request.options).await
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("local:{}:{:?}:{:?}", self.mtxrun_path.display(), self.output_dir, self.sandbox)
+    }
+
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        if !self.mtxrun_path.is_file() {
+            return Err(BackendError::Unavailable(format!("mtxrun executable not found at {:?}", self.mtxrun_path)));
+        }
+        self.sandbox.check().map_err(|e| BackendError::Unavailable(e.to_string()))
+    }
+
+    async fn release(&self, job_id: &str) {
+        let dir = self.working_dir.path().join(file_stem_for(job_id));
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove working files of {}: {}", job_id, e),
+        }
     }
 }
+/// Compiles on a server speaking the [`protocol`](crate::protocol), through a `RemoteClient`.
 #[derive(Debug)]
 pub struct RemoteBackend {
-    endpoint: String,
-    client: Client,
-    auth_token: Option<String>,
+    client: RemoteClient,
 }
 
 impl RemoteBackend {
     pub fn new(endpoint: String, auth_token: Option<String>) -> Self {
-        let client = Client::new();
-        Self { endpoint, auth_token, client }
+        Self { client: RemoteClient::new(endpoint, auth_token) }
+    }
+
+    /// See `RemoteClient::with_auth`.
+    pub fn with_auth(mut self, auth: Arc<dyn AuthProvider>) -> Result<Self, BackendError> {
+        self.client = self.client.with_auth(auth)?;
+        Ok(self)
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.client = self.client.with_cache_dir(cache_dir);
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.client = self.client.with_retry(retry);
+        self
+    }
+
+    /// See `RemoteClient::with_uploads`.
+    pub fn with_uploads(mut self, uploads: Arc<UploadTracker>) -> Self {
+        self.client = self.client.with_uploads(uploads);
+        self
+    }
+
+    /// See `RemoteClient::with_timeout`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    pub fn client(&self) -> &RemoteClient {
+        &self.client
     }
 }
 
@@ -236,58 +737,28 @@ impl CompilationBackend for RemoteBackend {
     }
 
     async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
-        let mut req = self.client
-            .post(&format!("{}/compile", self.endpoint))
-            .json(&serde_json::json!({
-                "uri": request.job_id,    
-                "content": request.content,
-                "format": "pdf",         
-            }));
-
-        // Add auth header if token present
-        if let Some(token) = &self.auth_token {
-            req = req.bearer_auth(token);
-        }
-
-        let response = req.send().await
-            .map_err(|e| BackendError::Network(e.to_string()))?;
+        request.options.validate()?;
+
+        let body = protocol::CompileRequest {
+            uri: request.job_id,
+            content: request.content,
+            files: request.files,
+            options: request.options,
+            ..Default::default()
+        };
+        let remote = self.client.compile(&body, &request.cancel, request.events.as_ref()).await?;
+        Ok(remote.response.into_result(remote.artifact, self.name()))
+    }
 
-        if !response.status().is_success() {
-            return Err(BackendError::Compilation(format!(
-                "Server returned {}",
-                response.status()
-            )));
-        }
+    fn fingerprint(&self) -> String {
+        format!("remote:{}", self.client.endpoint())
+    }
 
-        let remote_result: CompileResponse = response.json().await
-            .map_err(|e| BackendError::Network(e.to_string()))?;
+    fn name(&self) -> &str {
+        "remote"
+    }
 
-        Ok(CompilationResult {
-            success: remote_result.success,
-            pdf_path: remote_result.output_url.map(PathBuf::from),
-            log: remote_result.log,
-            errors: remote_result.diagnostics.iter().filter_map(|d| {
-                if d.severity == "error" {
-                    Some(CompilationError {
-                        line: d.range.as_ref().map_or(0, |r| r.start),
-                        column: d.range.as_ref().map_or(0, |r| r.end),
-                        message: d.message.clone(),
-                    })
-                } else {
-                    None
-                }
-            }).collect(),
-            warnings: remote_result.diagnostics.iter().filter_map(|d| {
-                if d.severity == "warning" {
-                    Some(CompilationError {
-                        line: d.range.as_ref().map_or(0, |r| r.start),
-                        column: d.range.as_ref().map_or(0, |r| r.end),
-                        message: d.message.clone(),
-                    })
-                } else {
-                    None
-                }
-            }).collect(),
-        })
+    async fn health_check(&self) -> Result<(), BackendError> {
+        self.client.health().await
     }
 }
@@ -0,0 +1,124 @@
+//! Compiles ConTeXt documents for remote clients over HTTP, using a local `mtxrun`.
+//!
+//! ```text
+//! context-server [--bind 127.0.0.1:8080] [--token TOKEN] [--mtxrun PATH]
+//!                [--artifacts DIR] [--jobs N] [--timeout SECONDS]
+//!                [--trusted]
+//! ```
+//!
+//! The token can also be set with `CONTEXT_SERVER_TOKEN`, which keeps it out of the
+//! process list.
+//!
+//! Documents come from clients, so they are compiled with the hardened sandbox
+//! policy. `--trusted` lifts it, e.g. for a server only its owner uses.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use context_runtime::backend_traits::{ContextEngineChoice, LocalBackend};
+use context_runtime::discovery::{self, ContextEngine, InstallationSource};
+use context_runtime::process::ProcessLimits;
+use context_runtime::sandbox::SandboxPolicy;
+use context_runtime::server::{CompileServer, ServerConfig};
+
+const USAGE: &str = "usage: context-server [--bind ADDR] [--token TOKEN] [--mtxrun PATH] [--artifacts DIR] \
+                     [--jobs N] [--timeout SECONDS] [--trusted]";
+
+struct Options {
+    bind: SocketAddr,
+    mtxrun: Option<PathBuf>,
+    limits: ProcessLimits,
+    trusted: bool,
+    server: ServerConfig,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        bind: SocketAddr::from(([127, 0, 0, 1], 8080)),
+        mtxrun: None,
+        limits: ProcessLimits::default(),
+        trusted: false,
+        server: ServerConfig {
+            auth_token: std::env::var("CONTEXT_SERVER_TOKEN").ok().filter(|token| !token.is_empty()),
+            ..Default::default()
+        },
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--bind" => options.bind = value()?.parse().map_err(|e| format!("Invalid --bind: {}", e))?,
+            "--token" => options.server.auth_token = Some(value()?),
+            "--mtxrun" => options.mtxrun = Some(PathBuf::from(value()?)),
+            "--artifacts" => options.server.artifact_dir = PathBuf::from(value()?),
+            "--jobs" => options.server.max_concurrent = value()?.parse().map_err(|e| format!("Invalid --jobs: {}", e))?,
+            "--timeout" => {
+                let seconds: u64 = value()?.parse().map_err(|e| format!("Invalid --timeout: {}", e))?;
+                options.limits.timeout = (seconds > 0).then(|| Duration::from_secs(seconds));
+            }
+            "--trusted" => options.trusted = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown argument: {}\n{}", other, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+#[tokio::main]
+async fn main() {
+    // The library logs each request; show it unless RUST_LOG says otherwise.
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let mut options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let sandbox = if options.trusted {
+        log::warn!("Compiling trusted documents: they can run programs and read any file");
+        SandboxPolicy::trusted()
+    } else {
+        SandboxPolicy::hardened()
+    };
+    if let Err(e) = sandbox.check() {
+        log::error!("Cannot sandbox compilations: {} (pass --trusted to compile without the sandbox)", e);
+        std::process::exit(1);
+    }
+    let local = match LocalBackend::new(options.mtxrun) {
+        Ok(local) => local.with_limits(options.limits).with_sandbox(sandbox),
+        Err(e) => {
+            log::error!("Cannot start: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let installation = discovery::probe(local.mtxrun_path(), InstallationSource::Configured).await;
+    log::info!("Using mtxrun at {} ({})", local.mtxrun_path().display(), installation.engine);
+    options.server.engines = match installation.engine {
+        ContextEngine::LuaMetaTeX => vec![ContextEngineChoice::Lmtx],
+        ContextEngine::LuaTeX => vec![ContextEngineChoice::Mkiv],
+        ContextEngine::Unknown => Vec::new(),
+    };
+
+    if options.server.auth_token.is_none() {
+        log::warn!("No token set, every client can compile");
+    }
+
+    let listener = match tokio::net::TcpListener::bind(options.bind).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Cannot listen on {}: {}", options.bind, e);
+            std::process::exit(1);
+        }
+    };
+    log::info!("Listening on http://{}", options.bind);
+
+    let server = CompileServer::new(Box::new(local), options.server);
+    if let Err(e) = server.serve(listener).await {
+        log::error!("Server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,7 @@
+//! uniffi's binding generator, built against the same uniffi version as the library.
+//!
+//! `scripts/generate-kotlin-bindings.sh` runs it for the Kotlin bindings.
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,516 @@
+//! A C API over [`ContextRuntime`] for desktop tools that can't use the uniffi
+//! bindings. `include/context_runtime.h` is generated from this module with cbindgen,
+//! see `scripts/generate-c-header.sh`.
+//!
+//! Strings are UTF-8 and NUL-terminated. Everything the library returns is owned by
+//! the caller and freed with the matching `*_free` function. When a function fails,
+//! `context_last_error` describes why.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Arc;
+
+use crate::artifact::ArtifactData;
+use crate::backend_traits::CompilationResult;
+use crate::diagnostic::{Diagnostic, DiagnosticSeverity};
+use crate::highlight::{Highlight, HighlightKind};
+use crate::runtime::{ContextRuntime, RuntimeConfig, RuntimeError};
+
+/// A runtime and the documents opened in it.
+pub struct ContextHandle {
+    runtime: Arc<ContextRuntime>,
+    tokio_runtime: tokio::runtime::Runtime,
+}
+
+/// What a function did; anything but `Ok` leaves a message in `context_last_error`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextStatus {
+    Ok = 0,
+    /// A required pointer was null or a string wasn't valid UTF-8.
+    InvalidArgument = 1,
+    DocumentNotFound = 2,
+    /// The edit range is past the end, reversed or splits a character.
+    InvalidRange = 3,
+    BackendUnavailable = 4,
+    CompilationFailed = 5,
+    Internal = 6,
+}
+
+/// Settings for `context_runtime_new`; null strings keep the defaults.
+#[repr(C)]
+pub struct ContextRuntimeOptions {
+    /// Prefer the compile server over a local `mtxrun`.
+    pub remote: bool,
+    pub server_url: *const c_char,
+    pub auth_token: *const c_char,
+    pub local_executable: *const c_char,
+    pub output_dir: *const c_char,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextHighlightKind {
+    Keyword = 0,
+    Command = 1,
+    Option = 2,
+    Text = 3,
+    Comment = 4,
+    Environment = 5,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextSeverity {
+    Error = 0,
+    Warning = 1,
+    Info = 2,
+}
+
+/// A highlighted byte range of the document source.
+#[repr(C)]
+pub struct ContextHighlight {
+    pub start: usize,
+    pub end: usize,
+    pub kind: ContextHighlightKind,
+}
+
+#[repr(C)]
+pub struct ContextHighlightList {
+    pub items: *mut ContextHighlight,
+    pub len: usize,
+}
+
+/// A problem in a byte range of the document source.
+#[repr(C)]
+pub struct ContextDiagnostic {
+    pub start: usize,
+    pub end: usize,
+    pub severity: ContextSeverity,
+    pub message: *mut c_char,
+}
+
+#[repr(C)]
+pub struct ContextDiagnosticList {
+    pub items: *mut ContextDiagnostic,
+    pub len: usize,
+}
+
+/// The outcome of `context_compile`. The PDF is a file at `pdf_path`, `pdf_len`
+/// bytes at `pdf_data` or a download at `pdf_url`; the others are null.
+#[repr(C)]
+pub struct ContextCompileResult {
+    pub success: bool,
+    pub pdf_path: *mut c_char,
+    pub pdf_data: *mut u8,
+    pub pdf_len: usize,
+    pub pdf_url: *mut c_char,
+    pub log: *mut c_char,
+    pub error_count: usize,
+    pub warning_count: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn fail(status: ContextStatus, message: impl Into<String>) -> ContextStatus {
+    set_last_error(message);
+    status
+}
+
+// Unwinding into C is undefined behaviour, so panics become `Internal`.
+fn guard(f: impl FnOnce() -> ContextStatus) -> ContextStatus {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| fail(ContextStatus::Internal, "The runtime panicked"))
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str, ContextStatus> {
+    if value.is_null() {
+        return Err(fail(ContextStatus::InvalidArgument, format!("`{}` is null", name)));
+    }
+    unsafe { CStr::from_ptr(value) }.to_str()
+        .map_err(|_| fail(ContextStatus::InvalidArgument, format!("`{}` is not valid UTF-8", name)))
+}
+
+unsafe fn optional_str_arg(value: *const c_char) -> Option<String> {
+    if value.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(value) }.to_str().ok().map(str::to_string)
+}
+
+unsafe fn handle_arg<'a>(handle: *const ContextHandle) -> Result<&'a ContextHandle, ContextStatus> {
+    unsafe { handle.as_ref() }.ok_or_else(|| fail(ContextStatus::InvalidArgument, "`handle` is null"))
+}
+
+fn into_c_string(value: String) -> *mut c_char {
+    CString::new(value.replace('\0', " ")).unwrap_or_default().into_raw()
+}
+
+unsafe fn free_c_string(value: *mut c_char) {
+    if !value.is_null() {
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+fn into_raw_slice<T>(items: Vec<T>) -> (*mut T, usize) {
+    if items.is_empty() {
+        return (ptr::null_mut(), 0);
+    }
+    let items = Box::into_raw(items.into_boxed_slice());
+    (items.cast(), items.len())
+}
+
+unsafe fn from_raw_slice<T>(items: *mut T, len: usize) -> Box<[T]> {
+    if items.is_null() {
+        return Box::default();
+    }
+    unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(items, len)) }
+}
+
+fn runtime_status(error: RuntimeError) -> ContextStatus {
+    let status = match &error {
+        RuntimeError::DocumentNotFound(_) => ContextStatus::DocumentNotFound,
+        RuntimeError::Unavailable(_) | RuntimeError::AuthenticationFailed { .. } => ContextStatus::BackendUnavailable,
+        RuntimeError::LockPoisoned => ContextStatus::Internal,
+        _ => ContextStatus::CompilationFailed,
+    };
+    fail(status, error.to_string())
+}
+
+impl From<HighlightKind> for ContextHighlightKind {
+    fn from(kind: HighlightKind) -> Self {
+        match kind {
+            HighlightKind::Keyword => Self::Keyword,
+            HighlightKind::Command => Self::Command,
+            HighlightKind::Option => Self::Option,
+            HighlightKind::Text => Self::Text,
+            HighlightKind::Comment => Self::Comment,
+            HighlightKind::Environment => Self::Environment,
+        }
+    }
+}
+
+impl From<DiagnosticSeverity> for ContextSeverity {
+    fn from(severity: DiagnosticSeverity) -> Self {
+        match severity {
+            DiagnosticSeverity::Error => Self::Error,
+            DiagnosticSeverity::Warning => Self::Warning,
+            DiagnosticSeverity::Info => Self::Info,
+        }
+    }
+}
+
+impl From<Highlight> for ContextHighlight {
+    fn from(highlight: Highlight) -> Self {
+        Self { start: highlight.range.start, end: highlight.range.end, kind: highlight.kind.into() }
+    }
+}
+
+impl From<Diagnostic> for ContextDiagnostic {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self {
+            start: diagnostic.range.start,
+            end: diagnostic.range.end,
+            severity: diagnostic.severity.into(),
+            message: into_c_string(diagnostic.message),
+        }
+    }
+}
+
+impl From<CompilationResult> for ContextCompileResult {
+    fn from(result: CompilationResult) -> Self {
+        let (mut pdf_path, mut pdf_data, mut pdf_len, mut pdf_url) = (ptr::null_mut(), ptr::null_mut(), 0, ptr::null_mut());
+        match result.artifact.map(|artifact| artifact.data) {
+            Some(ArtifactData::File(path)) => pdf_path = into_c_string(path.to_string_lossy().into_owned()),
+            Some(ArtifactData::Bytes(bytes)) => (pdf_data, pdf_len) = into_raw_slice(bytes),
+            Some(ArtifactData::Url(url)) => pdf_url = into_c_string(url),
+            None => {}
+        }
+        Self {
+            success: result.success,
+            pdf_path,
+            pdf_data,
+            pdf_len,
+            pdf_url,
+            log: into_c_string(result.log),
+            error_count: result.errors.len(),
+            warning_count: result.warnings.len(),
+        }
+    }
+}
+
+/// The message of the last failure on this thread, or null. It stays valid until
+/// the next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn context_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Creates a runtime, or returns null and sets `context_last_error`.
+///
+/// # Safety
+/// `options` is null or points to options whose strings are null or NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_runtime_new(options: *const ContextRuntimeOptions) -> *mut ContextHandle {
+    let mut config = RuntimeConfig::default();
+    if let Some(options) = unsafe { options.as_ref() } {
+        config.remote = options.remote;
+        config.server_url = unsafe { optional_str_arg(options.server_url) };
+        config.auth_token = unsafe { optional_str_arg(options.auth_token) };
+        config.local_executable = unsafe { optional_str_arg(options.local_executable) }.map(PathBuf::from);
+        config.output_dir = unsafe { optional_str_arg(options.output_dir) }.map(PathBuf::from);
+    }
+
+    let mut handle = ptr::null_mut();
+    guard(|| {
+        let tokio_runtime = match tokio::runtime::Runtime::new() {
+            Ok(tokio_runtime) => tokio_runtime,
+            Err(e) => return fail(ContextStatus::Internal, format!("Failed to start the runtime: {}", e)),
+        };
+        let runtime = ContextRuntime::new(config);
+        handle = Box::into_raw(Box::new(ContextHandle { runtime, tokio_runtime }));
+        ContextStatus::Ok
+    });
+    handle
+}
+
+/// # Safety
+/// `handle` is null or was returned by `context_runtime_new` and isn't used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_runtime_free(handle: *mut ContextHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Opens a document, replacing it if it is already open.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` and `content` are NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_open(handle: *const ContextHandle, uri: *const c_char, content: *const c_char) -> ContextStatus {
+    guard(|| {
+        let (handle, uri, content) = match unsafe { (handle_arg(handle), str_arg(uri, "uri"), str_arg(content, "content")) } {
+            (Ok(handle), Ok(uri), Ok(content)) => (handle, uri, content),
+            (Err(status), _, _) | (_, Err(status), _) | (_, _, Err(status)) => return status,
+        };
+        match handle.runtime.open_document(uri.to_string(), content.to_string()) {
+            Ok(()) => ContextStatus::Ok,
+            Err(e) => runtime_status(e),
+        }
+    })
+}
+
+/// Replaces the bytes `start..end` of an open document with `text`.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` and `text` are NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_update(
+    handle: *const ContextHandle,
+    uri: *const c_char,
+    start: usize,
+    end: usize,
+    text: *const c_char,
+) -> ContextStatus {
+    guard(|| {
+        let (handle, uri, text) = match unsafe { (handle_arg(handle), str_arg(uri, "uri"), str_arg(text, "text")) } {
+            (Ok(handle), Ok(uri), Ok(text)) => (handle, uri, text),
+            (Err(status), _, _) | (_, Err(status), _) | (_, _, Err(status)) => return status,
+        };
+        let Some(source) = handle.runtime.get_document_source(uri) else {
+            return fail(ContextStatus::DocumentNotFound, format!("Document not found: {}", uri));
+        };
+        if start > end || end > source.len() || !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+            return fail(
+                ContextStatus::InvalidRange,
+                format!("Invalid range {}..{} in {} ({} bytes)", start, end, uri, source.len()),
+            );
+        }
+        match handle.runtime.update_document(uri, start..end, text) {
+            Ok(()) => ContextStatus::Ok,
+            Err(e) => runtime_status(e),
+        }
+    })
+}
+
+/// Closes a document; closing one that isn't open does nothing.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_close(handle: *const ContextHandle, uri: *const c_char) -> ContextStatus {
+    guard(|| {
+        let (handle, uri) = match unsafe { (handle_arg(handle), str_arg(uri, "uri")) } {
+            (Ok(handle), Ok(uri)) => (handle, uri),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        handle.runtime.close_document(uri);
+        ContextStatus::Ok
+    })
+}
+
+/// The source of an open document, or null. Free it with `context_string_free`.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_document_source(handle: *const ContextHandle, uri: *const c_char) -> *mut c_char {
+    let mut source = ptr::null_mut();
+    guard(|| {
+        let (handle, uri) = match unsafe { (handle_arg(handle), str_arg(uri, "uri")) } {
+            (Ok(handle), Ok(uri)) => (handle, uri),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        match handle.runtime.get_document_source(uri) {
+            Some(content) => {
+                source = into_c_string(content);
+                ContextStatus::Ok
+            }
+            None => fail(ContextStatus::DocumentNotFound, format!("Document not found: {}", uri)),
+        }
+    });
+    source
+}
+
+/// # Safety
+/// `value` is null or was returned by this library and isn't used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_string_free(value: *mut c_char) {
+    unsafe { free_c_string(value) }
+}
+
+/// Fills `out` with the highlights of an open document; free them with
+/// `context_highlight_list_free`.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_highlights(
+    handle: *const ContextHandle,
+    uri: *const c_char,
+    out: *mut ContextHighlightList,
+) -> ContextStatus {
+    guard(|| {
+        let (handle, uri) = match unsafe { (handle_arg(handle), str_arg(uri, "uri")) } {
+            (Ok(handle), Ok(uri)) => (handle, uri),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        let Some(out) = (unsafe { out.as_mut() }) else {
+            return fail(ContextStatus::InvalidArgument, "`out` is null");
+        };
+        if handle.runtime.get_document_source(uri).is_none() {
+            return fail(ContextStatus::DocumentNotFound, format!("Document not found: {}", uri));
+        }
+        let highlights = handle.runtime.get_highlights(uri).into_iter().map(Into::into).collect();
+        (out.items, out.len) = into_raw_slice(highlights);
+        ContextStatus::Ok
+    })
+}
+
+/// # Safety
+/// `list` is null or was filled by `context_highlights` and isn't used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_highlight_list_free(list: *mut ContextHighlightList) {
+    if let Some(list) = unsafe { list.as_mut() } {
+        drop(unsafe { from_raw_slice(list.items, list.len) });
+        (list.items, list.len) = (ptr::null_mut(), 0);
+    }
+}
+
+/// Fills `out` with the diagnostics of an open document; free them with
+/// `context_diagnostic_list_free`.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_diagnostics(
+    handle: *const ContextHandle,
+    uri: *const c_char,
+    out: *mut ContextDiagnosticList,
+) -> ContextStatus {
+    guard(|| {
+        let (handle, uri) = match unsafe { (handle_arg(handle), str_arg(uri, "uri")) } {
+            (Ok(handle), Ok(uri)) => (handle, uri),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        let Some(out) = (unsafe { out.as_mut() }) else {
+            return fail(ContextStatus::InvalidArgument, "`out` is null");
+        };
+        if handle.runtime.get_document_source(uri).is_none() {
+            return fail(ContextStatus::DocumentNotFound, format!("Document not found: {}", uri));
+        }
+        let diagnostics = handle.runtime.get_diagnostics(uri).into_iter().map(Into::into).collect();
+        (out.items, out.len) = into_raw_slice(diagnostics);
+        ContextStatus::Ok
+    })
+}
+
+/// # Safety
+/// `list` is null or was filled by `context_diagnostics` and isn't used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_diagnostic_list_free(list: *mut ContextDiagnosticList) {
+    if let Some(list) = unsafe { list.as_mut() } {
+        for diagnostic in unsafe { from_raw_slice(list.items, list.len) } {
+            unsafe { free_c_string(diagnostic.message) };
+        }
+        (list.items, list.len) = (ptr::null_mut(), 0);
+    }
+}
+
+/// Compiles an open document, blocking until it is done. A document with ConTeXt
+/// errors still gives `Ok`, with `success` false and the errors in the log. Free
+/// the result with `context_compile_result_free`.
+///
+/// # Safety
+/// `handle` comes from `context_runtime_new`; `uri` is NUL-terminated and `out` is writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_compile(
+    handle: *const ContextHandle,
+    uri: *const c_char,
+    out: *mut *mut ContextCompileResult,
+) -> ContextStatus {
+    guard(|| {
+        let (handle, uri) = match unsafe { (handle_arg(handle), str_arg(uri, "uri")) } {
+            (Ok(handle), Ok(uri)) => (handle, uri),
+            (Err(status), _) | (_, Err(status)) => return status,
+        };
+        let Some(out) = (unsafe { out.as_mut() }) else {
+            return fail(ContextStatus::InvalidArgument, "`out` is null");
+        };
+        *out = ptr::null_mut();
+        match handle.tokio_runtime.block_on(handle.runtime.compile_document(uri)) {
+            Ok(result) => {
+                *out = Box::into_raw(Box::new(result.into()));
+                ContextStatus::Ok
+            }
+            Err(e) => runtime_status(e),
+        }
+    })
+}
+
+/// # Safety
+/// `result` is null or was returned by `context_compile` and isn't used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn context_compile_result_free(result: *mut ContextCompileResult) {
+    if result.is_null() {
+        return;
+    }
+    let result = unsafe { Box::from_raw(result) };
+    unsafe {
+        free_c_string(result.pdf_path);
+        free_c_string(result.pdf_url);
+        free_c_string(result.log);
+        drop(from_raw_slice(result.pdf_data, result.pdf_len));
+    }
+}
@@ -0,0 +1,242 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::artifact::ArtifactData;
+use crate::backend_traits::{BackendError, BackendHealth, CompilationBackend, CompilationRequest, CompilationResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompileCacheLimits {
+    pub max_entries: usize,
+    /// Upper bound on in-memory PDF bytes and logs held by the cache.
+    pub max_bytes: u64,
+}
+
+impl Default for CompileCacheLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 32,
+            max_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompileCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    result: CompilationResult,
+    size: u64,
+    last_used: u64,
+}
+
+/// Least-recently-used store of compilation results, keyed by [`cache_key`].
+#[derive(Debug)]
+pub struct CompileCache {
+    limits: CompileCacheLimits,
+    entries: HashMap<String, CacheEntry>,
+    clock: u64,
+    bytes: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl CompileCache {
+    pub fn new(limits: CompileCacheLimits) -> Self {
+        Self {
+            limits,
+            entries: HashMap::new(),
+            clock: 0,
+            bytes: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<CompilationResult> {
+        self.clock += 1;
+
+        // A persisted or downloaded PDF may have been deleted since it was cached.
+        let stale = self.entries.get(key).is_some_and(|entry| {
+            matches!(
+                entry.result.artifact.as_ref().map(|a| &a.data),
+                Some(ArtifactData::File(path)) if !path.exists()
+            )
+        });
+        if stale {
+            self.remove(key);
+        }
+
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                Some(entry.result.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: String, result: CompilationResult) {
+        let size = result_size(&result);
+        if self.limits.max_entries == 0 || size > self.limits.max_bytes {
+            return;
+        }
+
+        self.remove(&key);
+        self.clock += 1;
+        self.bytes += size;
+        self.entries.insert(key, CacheEntry { result, size, last_used: self.clock });
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+
+    pub fn stats(&self) -> CompileCacheStats {
+        CompileCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+            bytes: self.bytes,
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.size;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.limits.max_entries || self.bytes > self.limits.max_bytes {
+            let Some(oldest) = self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+fn result_size(result: &CompilationResult) -> u64 {
+    let artifact = match result.artifact.as_ref().map(|a| &a.data) {
+        Some(ArtifactData::Bytes(bytes)) => bytes.len(),
+        _ => 0,
+    };
+    (artifact + result.log.len()) as u64
+}
+
+/// Hashes everything that can change the output: the backend's settings, the job id
+/// (it names the output files), the compile options and the main source plus every
+/// included file.
+pub fn cache_key(request: &CompilationRequest, backend_fingerprint: &str) -> String {
+    let mut hasher = Sha256::new();
+    let mut field = |value: &str| {
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value.as_bytes());
+    };
+
+    field(backend_fingerprint);
+    field(&request.job_id);
+    field(&request.content);
+    field(&serde_json::to_string(&request.options).unwrap_or_default());
+
+    let mut files: Vec<_> = request.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    for file in files {
+        field(&file.path);
+        field(&file.content);
+    }
+
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Serves repeated compilations of identical input from a [`CompileCache`]
+/// instead of running the wrapped backend again.
+#[derive(Debug)]
+pub struct CachingBackend {
+    inner: Arc<dyn CompilationBackend>,
+    cache: Mutex<CompileCache>,
+}
+
+impl CachingBackend {
+    pub fn new(inner: Box<dyn CompilationBackend>, limits: CompileCacheLimits) -> Self {
+        Self {
+            inner: Arc::from(inner),
+            cache: Mutex::new(CompileCache::new(limits)),
+        }
+    }
+
+    pub fn inner(&self) -> &dyn CompilationBackend {
+        self.inner.as_ref()
+    }
+
+    pub fn stats(&self) -> CompileCacheStats {
+        self.cache.lock().map(|cache| cache.stats()).unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.clear();
+        }
+    }
+}
+
+#[async_trait]
+impl CompilationBackend for CachingBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        let key = cache_key(&request, &self.inner.fingerprint());
+
+        if let Some(cached) = self.cache.lock().ok().and_then(|mut cache| cache.get(&key)) {
+            return Ok(cached);
+        }
+
+        // Only completed runs are cached; backend errors (network, IO) may be transient.
+        let result = self.inner.compile(request).await?;
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(key, result.clone());
+        }
+
+        Ok(result)
+    }
+
+    fn fingerprint(&self) -> String {
+        self.inner.fingerprint()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        self.inner.health_check().await
+    }
+
+    async fn health_report(&self) -> Vec<BackendHealth> {
+        self.inner.health_report().await
+    }
+
+    async fn release(&self, job_id: &str) {
+        self.inner.release(job_id).await
+    }
+}
@@ -0,0 +1,177 @@
+//! Incremental uploads for the remote [`protocol`](crate::protocol): what changed
+//! since the version a server last accepted, and how to rebuild a document from it.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use sha2::{Digest, Sha256};
+use crate::backend_traits::ProjectFile;
+use crate::protocol::{CompileRequest, DocumentVersion, TextEdit};
+
+/// SHA-256 over the main content and every file, in path order, so both sides can
+/// check they hold the same document.
+pub fn document_hash(content: &str, files: &[ProjectFile]) -> String {
+    let mut sorted: Vec<&ProjectFile> = files.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut hasher = Sha256::new();
+    hasher.update(content.len().to_le_bytes());
+    hasher.update(content);
+    for file in sorted {
+        hasher.update(file.path.len().to_le_bytes());
+        hasher.update(&file.path);
+        hasher.update(file.content.len().to_le_bytes());
+        hasher.update(&file.content);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The edits turning `old` into `new`: none if they are equal, otherwise a single
+/// replacement of everything between their common prefix and suffix. Editors change
+/// one place at a time, so this is usually as small as a full diff.
+pub fn diff(old: &str, new: &str) -> Vec<TextEdit> {
+    if old == new {
+        return Vec::new();
+    }
+
+    let mut prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(prefix) || !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old.bytes().rev().zip(new.bytes().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    vec![TextEdit {
+        start: prefix,
+        end: old.len() - suffix,
+        text: new[prefix..new.len() - suffix].to_string(),
+    }]
+}
+
+/// Applies `edits` to `base`, or `None` if they overlap, are out of order or don't
+/// fall on character boundaries.
+pub fn apply(base: &str, edits: &[TextEdit]) -> Option<String> {
+    let mut result = String::with_capacity(base.len());
+    let mut copied = 0;
+    for edit in edits {
+        if edit.start < copied || edit.end < edit.start || !base.is_char_boundary(edit.start) || !base.is_char_boundary(edit.end) {
+            return None;
+        }
+        result.push_str(&base[copied..edit.start]);
+        result.push_str(&edit.text);
+        copied = edit.end;
+    }
+    result.push_str(&base[copied..]);
+    Some(result)
+}
+
+/// Remembers what a server accepted for each document, so later compiles can send
+/// only what changed.
+///
+/// Servers keep versions per client, so the tracker also holds the client id sent
+/// with every request, and clients sharing a tracker share the id.
+#[derive(Debug)]
+pub struct UploadTracker {
+    client_id: String,
+    acknowledged: Mutex<HashMap<String, Upload>>,
+}
+
+impl Default for UploadTracker {
+    fn default() -> Self {
+        Self {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            acknowledged: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// A document version as it was sent.
+#[derive(Debug, Clone)]
+pub struct Upload {
+    pub version: DocumentVersion,
+    content: String,
+    file_hashes: HashMap<String, String>,
+}
+
+/// The request to send, and what to record once the server accepted it.
+#[derive(Debug, Clone)]
+pub struct PreparedUpload {
+    pub request: CompileRequest,
+    pub upload: Upload,
+}
+
+impl PreparedUpload {
+    pub fn is_delta(&self) -> bool {
+        self.request.base.is_some()
+    }
+}
+
+impl UploadTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /// `request` as a delta against the document's acknowledged version, or in full
+    /// with a version number when there is none or the delta wouldn't be smaller.
+    pub fn prepare(&self, request: &CompileRequest) -> PreparedUpload {
+        let base = self.acknowledged.lock().ok().and_then(|acknowledged| acknowledged.get(&request.uri).cloned());
+        let upload = Upload {
+            version: DocumentVersion {
+                version: base.as_ref().map_or(1, |base| base.version.version + 1),
+                hash: document_hash(&request.content, &request.files),
+            },
+            content: request.content.clone(),
+            file_hashes: request.files.iter()
+                .map(|file| (file.path.clone(), crate::artifact::content_hash(file.content.as_bytes())))
+                .collect(),
+        };
+
+        let Some(base) = base else {
+            return PreparedUpload { request: Self::full(request, &upload), upload };
+        };
+        let delta = diff(&base.content, &request.content);
+        let (unchanged, changed): (Vec<&ProjectFile>, Vec<&ProjectFile>) = request.files.iter()
+            .partition(|file| base.file_hashes.get(&file.path) == upload.file_hashes.get(&file.path));
+
+        let delta_bytes: usize = delta.iter().map(|edit| edit.text.len()).sum::<usize>()
+            + changed.iter().map(|file| file.content.len()).sum::<usize>();
+        let full_bytes = request.content.len() + request.files.iter().map(|file| file.content.len()).sum::<usize>();
+        if delta_bytes >= full_bytes {
+            return PreparedUpload { request: Self::full(request, &upload), upload };
+        }
+
+        let request = CompileRequest {
+            content: String::new(),
+            files: changed.into_iter().cloned().collect(),
+            version: Some(upload.version.clone()),
+            base: Some(base.version),
+            delta,
+            unchanged_files: unchanged.into_iter().map(|file| file.path.clone()).collect(),
+            ..request.clone()
+        };
+        PreparedUpload { request, upload }
+    }
+
+    /// `request` in full, still numbered so the server can base later deltas on it.
+    pub fn full(request: &CompileRequest, upload: &Upload) -> CompileRequest {
+        CompileRequest { version: Some(upload.version.clone()), ..request.clone() }
+    }
+
+    pub fn acknowledge(&self, uri: &str, upload: Upload) {
+        if let Ok(mut acknowledged) = self.acknowledged.lock() {
+            acknowledged.insert(uri.to_string(), upload);
+        }
+    }
+
+    pub fn forget(&self, uri: &str) {
+        if let Ok(mut acknowledged) = self.acknowledged.lock() {
+            acknowledged.remove(uri);
+        }
+    }
+}
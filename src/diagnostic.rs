@@ -6,6 +6,16 @@ pub struct Diagnostic {
     pub range: Range<usize>,
     pub severity: DiagnosticSeverity,
     pub message: String,
+    #[serde(default)]
+    pub source: DiagnosticSource,
+}
+
+/// What found a diagnostic: the parser, or a compilation's log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosticSource {
+    #[default]
+    Syntax,
+    Compilation,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -31,6 +41,7 @@ impl Diagnostic {
             range: start..(start + length),
             severity: DiagnosticSeverity::Error, 
             message,
+            source: DiagnosticSource::Syntax,
         }
     }
 
@@ -39,6 +50,12 @@ impl Diagnostic {
             range: start..(start + length),
             severity: DiagnosticSeverity::Warning, 
             message,
+            source: DiagnosticSource::Syntax,
         }
     }
+
+    pub fn with_source(mut self, source: DiagnosticSource) -> Self {
+        self.source = source;
+        self
+    }
 }
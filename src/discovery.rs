@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::Duration;
+use regex::Regex;
+use tokio::process::Command;
+use which::which;
+
+/// Oldest ConTeXt release we test against. Versions are dates, so they compare as strings.
+pub const MINIMUM_CONTEXT_VERSION: &str = "2021.01.01";
+
+/// Environment variables that may point at the root of a ConTeXt installation,
+/// the directory that contains `tex/texmf-<platform>/bin`.
+pub const CONTEXT_ROOT_VARS: &[&str] = &["CONTEXTROOT", "CONTEXT_ROOT", "CONTEXT_HOME"];
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Where an `mtxrun` executable was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallationSource {
+    /// The path set in the runtime configuration.
+    Configured,
+    /// One of `CONTEXT_ROOT_VARS`.
+    Environment,
+    /// `mtxrun` or `context` on `PATH`.
+    Path,
+    /// A ConTeXt LMTX standalone install such as `~/context`.
+    Standalone,
+    /// A TeX Live installation directory.
+    TexLive,
+}
+
+/// The TeX engine the installation's `context` runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextEngine {
+    /// LuaMetaTeX, ConTeXt LMTX (`.mkxl` files).
+    LuaMetaTeX,
+    /// LuaTeX, ConTeXt MkIV (`.mkiv` files).
+    LuaTeX,
+    Unknown,
+}
+
+impl std::fmt::Display for ContextEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContextEngine::LuaMetaTeX => write!(f, "LuaMetaTeX"),
+            ContextEngine::LuaTeX => write!(f, "LuaTeX"),
+            ContextEngine::Unknown => write!(f, "unknown engine"),
+        }
+    }
+}
+
+/// A ConTeXt installation and what `context --version` reported about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextInstallation {
+    pub mtxrun_path: PathBuf,
+    pub source: InstallationSource,
+    pub engine: ContextEngine,
+    /// The ConTeXt version, e.g. `2024.04.01 09:33`.
+    pub version: Option<String>,
+    /// Why the installation may not work, e.g. it is too old or could not be queried.
+    pub warnings: Vec<String>,
+}
+
+impl ContextInstallation {
+    pub fn is_supported(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+/// Finds `mtxrun` without running it: the configured path, then the first hit from
+/// `candidates`. Cheap enough to call when a backend is created.
+pub fn find_mtxrun(configured: Option<&Path>) -> Option<(PathBuf, InstallationSource)> {
+    match configured {
+        Some(path) => path.is_file().then(|| (path.to_path_buf(), InstallationSource::Configured)),
+        None => candidates().into_iter().next(),
+    }
+}
+
+/// Every `mtxrun` on this machine, in the order a backend would pick them:
+/// `CONTEXT_ROOT_VARS`, `PATH`, standalone installs, then TeX Live (newest first).
+pub fn candidates() -> Vec<(PathBuf, InstallationSource)> {
+    let mut found: Vec<(PathBuf, InstallationSource)> = Vec::new();
+    let mut add = |path: PathBuf, source| {
+        if path.is_file() && !found.iter().any(|(p, _)| same_file(p, &path)) {
+            found.push((path, source));
+        }
+    };
+
+    for var in CONTEXT_ROOT_VARS {
+        if let Some(root) = std::env::var_os(var).filter(|v| !v.is_empty()) {
+            for path in mtxrun_under_root(Path::new(&root)) {
+                add(path, InstallationSource::Environment);
+            }
+        }
+    }
+
+    if let Ok(path) = which("mtxrun") {
+        add(path, InstallationSource::Path);
+    }
+    // TeX Live puts `context` and `mtxrun` side by side.
+    if let Ok(context) = which("context")
+        && let Some(dir) = context.parent()
+    {
+        add(dir.join(mtxrun_file_name()), InstallationSource::Path);
+    }
+
+    for root in standalone_roots() {
+        for path in mtxrun_under_root(&root) {
+            add(path, InstallationSource::Standalone);
+        }
+    }
+
+    for path in texlive_mtxruns() {
+        add(path, InstallationSource::TexLive);
+    }
+
+    found
+}
+
+/// Runs `context --version` through `mtxrun` and reports the engine and version.
+pub async fn probe(mtxrun_path: &Path, source: InstallationSource) -> ContextInstallation {
+    let mut installation = ContextInstallation {
+        mtxrun_path: mtxrun_path.to_path_buf(),
+        source,
+        engine: ContextEngine::Unknown,
+        version: None,
+        warnings: Vec::new(),
+    };
+
+    let output = Command::new(mtxrun_path)
+        .args(["--script", "context", "--version"])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    let output = match tokio::time::timeout(PROBE_TIMEOUT, output).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            installation.warnings.push(format!("Failed to run mtxrun: {}", e));
+            return installation;
+        }
+        Err(_) => {
+            installation.warnings.push("mtxrun did not report its version in time".into());
+            return installation;
+        }
+    };
+
+    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let (engine, version) = parse_version_output(&text);
+    installation.engine = engine;
+    installation.version = version;
+    installation.warnings = version_warnings(engine, installation.version.as_deref());
+    installation
+}
+
+/// Probes every installation `candidates` finds.
+pub async fn discover() -> Vec<ContextInstallation> {
+    let mut installations = Vec::new();
+    for (path, source) in candidates() {
+        installations.push(probe(&path, source).await);
+    }
+    installations
+}
+
+// mtx-context     | current version: 2024.04.01 09:33
+static VERSION_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"current version:\s*(\d{4}\.\d{2}\.\d{2}(?: \d{2}:\d{2})?)").unwrap());
+// mtx-context     | main context file: /opt/context/tex/texmf-context/tex/context/base/mkxl/context.mkxl
+static MAIN_FILE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"main context file:\s*\S*\.(mkxl|mkiv)").unwrap());
+
+/// Reads the engine and version from the output of `context --version`.
+pub fn parse_version_output(output: &str) -> (ContextEngine, Option<String>) {
+    let version = VERSION_RE.captures(output).map(|caps| caps[1].to_string());
+    let engine = match MAIN_FILE_RE.captures(output) {
+        Some(caps) if &caps[1] == "mkxl" => ContextEngine::LuaMetaTeX,
+        Some(_) => ContextEngine::LuaTeX,
+        None if output.to_lowercase().contains("luametatex") => ContextEngine::LuaMetaTeX,
+        None => ContextEngine::Unknown,
+    };
+    (engine, version)
+}
+
+fn version_warnings(engine: ContextEngine, version: Option<&str>) -> Vec<String> {
+    let mut warnings = Vec::new();
+    match version {
+        Some(version) if version < MINIMUM_CONTEXT_VERSION => warnings.push(format!(
+            "ConTeXt {} is older than the oldest supported version ({})",
+            version, MINIMUM_CONTEXT_VERSION
+        )),
+        Some(_) => {}
+        None => warnings.push("Could not determine the ConTeXt version".into()),
+    }
+    match engine {
+        ContextEngine::LuaTeX => warnings.push("ConTeXt MkIV on LuaTeX is frozen; ConTeXt LMTX is recommended".into()),
+        ContextEngine::Unknown => warnings.push("Could not determine the TeX engine".into()),
+        ContextEngine::LuaMetaTeX => {}
+    }
+    warnings
+}
+
+fn mtxrun_file_name() -> String {
+    format!("mtxrun{}", std::env::consts::EXE_SUFFIX)
+}
+
+// `<root>/tex/texmf-<platform>/bin/mtxrun`, the layout of the LMTX standalone distribution.
+fn mtxrun_under_root(root: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![root.join("bin").join(mtxrun_file_name())];
+    if let Ok(entries) = std::fs::read_dir(root.join("tex")) {
+        let mut platforms: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("texmf-") && name != "texmf-context")
+            })
+            .collect();
+        platforms.sort();
+        paths.extend(platforms.into_iter().map(|dir| dir.join("bin").join(mtxrun_file_name())));
+    }
+    paths
+}
+
+fn standalone_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        roots.push(home.join("context"));
+        roots.push(home.join("context-lmtx"));
+    }
+    if cfg!(windows) {
+        roots.push(PathBuf::from(r"C:\context"));
+    } else {
+        roots.push(PathBuf::from("/opt/context"));
+        roots.push(PathBuf::from("/usr/local/context"));
+    }
+    roots
+}
+
+// `<texlive>/<year>/bin/<platform>/mtxrun`, newest year first.
+fn texlive_mtxruns() -> Vec<PathBuf> {
+    let roots = if cfg!(windows) {
+        vec![PathBuf::from(r"C:\texlive")]
+    } else {
+        vec![PathBuf::from("/usr/local/texlive"), PathBuf::from("/opt/texlive")]
+    };
+
+    let mut paths = Vec::new();
+    for root in roots {
+        let Ok(entries) = std::fs::read_dir(&root) else { continue };
+        let mut years: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.chars().all(|c| c.is_ascii_digit()))
+            })
+            .collect();
+        years.sort_by(|a, b| b.cmp(a));
+
+        for year in years {
+            let Ok(platforms) = std::fs::read_dir(year.join("bin")) else { continue };
+            paths.extend(platforms.filter_map(|entry| entry.ok().map(|entry| entry.path().join(mtxrun_file_name()))));
+        }
+    }
+    paths
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -1,8 +1,21 @@
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock, Mutex};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock, Mutex, OnceLock};
+use std::time::Duration;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::backend_traits::{BackendError, CompilationBackend, CompilationRequest, CompileOptions, RemoteBackend};
+use crate::auth::{AuthProvider, ClientCertificate, Credentials, StaticCredentials};
+use crate::delta::UploadTracker;
+use crate::offline_queue::OfflineQueue;
+use crate::analysis::Analysis;
 use crate::runtime::ContextRuntime;
+use crate::scheduler::{CompileScheduler, DEFAULT_COMPILE_DELAY};
+use crate::job_queue::{JobPriority, JobQueue, DEFAULT_MAX_CONCURRENT_JOBS};
+use crate::progress::CompileEvent;
+use crate::discovery;
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio_util::sync::CancellationToken;
 use crate::ffi_bridge::*; // This import is crucial for your FFI types like HighlightFfi, DiagnosticFfi, CompileResultFfi, etc.
 
 use uniffi::{self};
@@ -22,26 +35,48 @@ pub trait LiveUpdateCallback: Send + Sync {
     fn on_diagnostics_updated(&self, uri: String, diagnostics: Vec<DiagnosticFfi>);
     fn on_compilation_completed(&self, uri: String, result: CompileResultFfi);
     fn on_error(&self, error: RuntimeErrorFfi);
+    // Sent while a local compilation runs, before `on_compilation_completed`
+    fn on_compilation_progress(&self, uri: String, progress: CompileProgressFfi);
+    fn on_log_chunk(&self, uri: String, chunk: String);
 }
 
-// Job tracking for async operations
-#[derive(Debug, Clone)]
-struct CompilationJob {
-    uri: String,
-    content: String,
-    config: RuntimeConfigFfi,
+// Supplies credentials for the compile server, so the app's login flow owns them.
+// Called from a background thread; implementations may block, e.g. to refresh a token.
+#[uniffi::export(callback_interface)]
+pub trait AuthProviderCallback: Send + Sync {
+    // Credentials for the next request, None sending it unauthenticated
+    fn get_credentials(&self) -> Option<CredentialsFfi>;
+    // Called when the server answered 401 to `rejected`; None gives up
+    fn refresh_credentials(&self, rejected: Option<CredentialsFfi>) -> Option<CredentialsFfi>;
+    // A certificate for servers requiring mutual TLS, None for plain TLS
+    fn get_client_certificate(&self) -> Option<ClientCertificateFfi>;
 }
 
 #[derive(uniffi::Object)]
 pub struct ContextRuntimeHandle {
     config: RuntimeConfigFfi,
-    documents: RwLock<HashMap<String, DocumentState>>,
+    documents: Arc<RwLock<HashMap<String, DocumentState>>>,
     // FIX 2: Correct type for the callback storage
     live_callback: Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
-    active_jobs: Arc<Mutex<HashMap<String, CompilationJob>>>,
+    // Every compilation goes through the queue, which bounds how many run at once
+    jobs: Arc<JobQueue>,
     tokio_runtime: Arc<tokio::runtime::Runtime>,
+    // Shared runtime for compilations, so results like SyncTeX data outlive a single job
+    compile_runtime: Arc<OnceLock<Arc<ContextRuntime>>>,
+    // Live preview: edits schedule a debounced compile when enabled
+    auto_compile: Arc<AtomicBool>,
+    scheduler: CompileScheduler,
+    // Credentials and documents waiting for the compile server, shared by remote compilations
+    remote: Arc<RemoteConnection>,
+    network_available: Arc<Notify>,
 }
 
+// Live-preview jobs are named this followed by the document's URI
+const AUTO_COMPILE_JOB_PREFIX: &str = "auto_";
+
+// Server probes while documents wait offline start at the first delay and double up to the second
+const RECONNECT_BACKOFF: (Duration, Duration) = (Duration::from_secs(2), Duration::from_secs(60));
+
 #[uniffi::export]
 impl ContextRuntimeHandle {
     #[uniffi::constructor]
@@ -54,14 +89,37 @@ impl ContextRuntimeHandle {
         let tokio_runtime = Arc::new(tokio::runtime::Runtime::new()
             .expect("Failed to create tokio runtime"));
 
-        Arc::new(Self {
+        let scheduler = CompileScheduler::new(tokio_runtime.handle().clone(), DEFAULT_COMPILE_DELAY);
+        let max_concurrent = match config.max_concurrent_compilations {
+            0 => DEFAULT_MAX_CONCURRENT_JOBS,
+            count => count as usize,
+        };
+        let jobs = Arc::new(JobQueue::new(tokio_runtime.handle().clone(), max_concurrent));
+
+        let handle = Arc::new(Self {
             config,
-            documents: RwLock::new(HashMap::new()),
+            documents: Arc::new(RwLock::new(HashMap::new())),
             // FIX 2 (continued): Initialize with the new type
             live_callback: Arc::new(RwLock::new(None)),
-            active_jobs: Arc::new(Mutex::new(HashMap::new())),
+            jobs,
             tokio_runtime,
-        })
+            compile_runtime: Arc::new(OnceLock::new()),
+            auto_compile: Arc::new(AtomicBool::new(false)),
+            scheduler,
+            remote: Arc::new(RemoteConnection::default()),
+            network_available: Arc::new(Notify::new()),
+        });
+
+        if let Some(server_url) = handle.config.server_url.clone().filter(|url| handle.config.remote && !url.is_empty()) {
+            let watcher = resubmit_when_reachable(
+                handle.compiler(),
+                Arc::clone(&handle.documents),
+                Arc::clone(&handle.network_available),
+                server_url,
+            );
+            handle.tokio_runtime.spawn(watcher);
+        }
+        handle
     }
 
     pub fn set_live_callback(&self, callback: Option<Box<dyn LiveUpdateCallback>>) {
@@ -70,102 +128,93 @@ impl ContextRuntimeHandle {
         }
     }
 
-    pub fn open(&self, uri: String, content: String) -> bool {
-        let runtime = ContextRuntime::new(self.config.clone().into());
-
-        match runtime.open_document(uri.clone(), content.clone()) {
-            Ok(_) => {
-                let highlights: Vec<HighlightFfi> = runtime.get_highlights(&uri)
-                    .into_iter()
-                    .map(Into::into)
-                    .collect();
-
-                let diagnostics: Vec<DiagnosticFfi> = runtime.get_diagnostics(&uri)
-                    .into_iter()
-                    .map(Into::into)
-                    .collect();
-
-                let doc_state = DocumentState {
-                    uri: uri.clone(),
-                    content,
-                    highlights: highlights.clone(),
-                    diagnostics: diagnostics.clone(),
-                };
+    /// Lets the app supply and refresh the compile server's credentials, replacing
+    /// `RuntimeConfigFfi.auth_token`. Takes effect from the next compilation.
+    pub fn set_auth_provider(&self, provider: Option<Box<dyn AuthProviderCallback>>) {
+        let provider = provider.map(|callback| Arc::new(CallbackAuthProvider::new(callback)) as Arc<dyn AuthProvider>);
+        self.remote.set_auth(provider);
+    }
 
-                if let Ok(mut docs) = self.documents.write() {
-                    docs.insert(uri.clone(), doc_state);
+    /// Turns live-preview compilation on or off. While on, every successful `open`/`update`
+    /// compiles the document after `delay_ms` of inactivity, and only the newest result
+    /// is delivered through `on_compilation_completed`. Turning it off stops pending,
+    /// queued and running live-preview compilations, which deliver nothing.
+    pub fn set_auto_compile(&self, enabled: bool, delay_ms: u32) {
+        self.scheduler.set_delay(std::time::Duration::from_millis(delay_ms.into()));
+        self.auto_compile.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.scheduler.cancel_all();
+            for job in self.jobs.snapshot() {
+                if job.id.starts_with(AUTO_COMPILE_JOB_PREFIX) {
+                    self.jobs.cancel(&job.id);
                 }
-
-                self.notify_highlights_updated(&uri, highlights);
-                self.notify_diagnostics_updated(&uri, diagnostics);
-                true
-            }
-            Err(e) => {
-                self.notify_error(e.into());
-                false
             }
         }
     }
 
-    pub fn update(&self, uri: String, start: u32, end: u32, new_text: String) -> bool {
-        let mut updated_content = None;
+    pub fn is_auto_compile_enabled(&self) -> bool {
+        self.auto_compile.load(Ordering::Relaxed)
+    }
 
-        if let Ok(docs) = self.documents.read() {
-            if let Some(doc) = docs.get(&uri) {
-                let mut content = doc.content.clone();
-                let range = (start as usize)..(end as usize);
+    pub fn open(&self, uri: String, content: String) -> Result<(), ContextErrorFfi> {
+        let (highlights, diagnostics) = Self::analyze(&content);
 
-                // Ensure range is valid
-                if range.end <= content.len() && range.start <= range.end {
-                    content.replace_range(range, &new_text);
-                    updated_content = Some(content);
-                }
-            }
-        }
+        let doc_state = DocumentState {
+            uri: uri.clone(),
+            content,
+            highlights: highlights.clone(),
+            diagnostics: diagnostics.clone(),
+        };
+        self.documents.write()
+            .map_err(|_| ContextErrorFfi::Internal { details: "Document store lock poisoned".into() })?
+            .insert(uri.clone(), doc_state);
+
+        self.notify_highlights_updated(&uri, highlights);
+        self.notify_diagnostics_updated(&uri, diagnostics);
+        self.schedule_auto_compile(&uri);
+        Ok(())
+    }
 
-        if let Some(content) = updated_content {
-            let runtime = ContextRuntime::new(self.config.clone().into());
-
-            match runtime.open_document(uri.clone(), content.clone()) {
-                Ok(_) => {
-                    let highlights: Vec<HighlightFfi> = runtime.get_highlights(&uri)
-                        .into_iter()
-                        .map(Into::into)
-                        .collect();
-
-                    let diagnostics: Vec<DiagnosticFfi> = runtime.get_diagnostics(&uri)
-                        .into_iter()
-                        .map(Into::into)
-                        .collect();
-
-                    if let Ok(mut docs) = self.documents.write() {
-                        if let Some(doc) = docs.get_mut(&uri) {
-                            doc.content = content;
-                            doc.highlights = highlights.clone();
-                            doc.diagnostics = diagnostics.clone();
-                        }
-                    }
+    /// Replaces the bytes `start..end` of the document with `new_text`.
+    pub fn update(&self, uri: String, start: u32, end: u32, new_text: String) -> Result<(), ContextErrorFfi> {
+        let mut content = self.get_document_source(uri.clone())
+            .ok_or_else(|| ContextErrorFfi::DocumentNotFound { uri: uri.clone() })?;
 
-                    self.notify_highlights_updated(&uri, highlights);
-                    self.notify_diagnostics_updated(&uri, diagnostics);
-                    true
-                }
-                Err(e) => {
-                    self.notify_error(e.into());
-                    false
-                }
-            }
-        } else {
-            // FIX 3: Correct constructor for RuntimeErrorFfi::DocumentNotFound
-            self.notify_error(RuntimeErrorFfi::DocumentNotFound { uri });
-            false
+        let range = (start as usize)..(end as usize);
+        if range.start > range.end || range.end > content.len()
+            || !content.is_char_boundary(range.start) || !content.is_char_boundary(range.end)
+        {
+            return Err(ContextErrorFfi::InvalidRange { uri, start, end, length: content.len() as u32 });
         }
+        content.replace_range(range, &new_text);
+
+        let (highlights, diagnostics) = Self::analyze(&content);
+        if let Ok(mut docs) = self.documents.write()
+            && let Some(doc) = docs.get_mut(&uri)
+        {
+            doc.content = content;
+            doc.highlights = highlights.clone();
+            doc.diagnostics = diagnostics.clone();
+        }
+
+        self.notify_highlights_updated(&uri, highlights);
+        self.notify_diagnostics_updated(&uri, diagnostics);
+        self.schedule_auto_compile(&uri);
+        Ok(())
     }
 
     pub fn close(&self, uri: String) {
+        self.scheduler.cancel(&uri);
+        self.jobs.cancel_document(&uri);
+        self.remote.offline.remove(&uri);
+        self.remote.uploads.forget(&uri);
         if let Ok(mut docs) = self.documents.write() {
             docs.remove(&uri);
         }
+        // Drops the document's SyncTeX data and options along with it.
+        if let Some(runtime) = self.compile_runtime.get() {
+            runtime.close_document(&uri);
+        }
     }
 
     pub fn get_document_source(&self, uri: String) -> Option<String> {
@@ -188,112 +237,138 @@ impl ContextRuntimeHandle {
             .unwrap_or_default()
     }
 
-    pub fn compile(&self, uri: String) -> String {
-        // Create job_id first and clone it for the async block
+    /// Queues a compilation of the document and returns its job id. The result is
+    /// delivered through `on_compilation_completed`.
+    pub fn compile(&self, uri: String) -> Result<String, ContextErrorFfi> {
+        let content = self.get_document_source(uri.clone())
+            .ok_or_else(|| ContextErrorFfi::DocumentNotFound { uri: uri.clone() })?;
+
         let job_id = format!("compile_{}", uuid::Uuid::new_v4());
-        let job_id_for_async = job_id.clone();
+        self.submit_compile(job_id.clone(), uri, content);
+        Ok(job_id)
+    }
 
-        let content = match self.get_document_source(uri.clone()) {
-            Some(content) => content,
-            None => {
-                // FIX 3: Correct constructor for RuntimeErrorFfi::DocumentNotFound
-                self.notify_error(RuntimeErrorFfi::DocumentNotFound { uri });
-                return job_id;
-            }
-        };
+    /// Tells the runtime the device is back online, so documents waiting for the
+    /// compile server are resubmitted without waiting for the next probe.
+    pub fn notify_network_available(&self) {
+        self.network_available.notify_one();
+    }
 
-        let job = CompilationJob {
-            uri: uri.clone(),
-            content: content.clone(),
-            config: self.config.clone(),
-        };
+    /// Documents whose remote compilation waits for the server to become reachable.
+    pub fn get_offline_documents(&self) -> Vec<String> {
+        self.remote.offline.uris()
+    }
 
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.insert(job_id.clone(), job.clone());
-        }
+    /// Stops a queued or running compilation, killing the ConTeXt process or aborting
+    /// the remote request. The job reports `RuntimeErrorFfi::Cancelled` through `on_error`.
+    pub fn cancel_compilation(&self, job_id: String) -> bool {
+        self.jobs.cancel(&job_id)
+    }
 
-        // Clone all necessary Arc references
-        let active_jobs = Arc::clone(&self.active_jobs);
-        // FIX 2 (continued): Clone the correct type of live_callback
-        let live_callback = Arc::clone(&self.live_callback);
-        let config = self.config.clone();
-        let cancelled = Arc::new(AtomicBool::new(false));
+    pub fn get_active_jobs(&self) -> Vec<String> {
+        self.jobs.snapshot()
+            .into_iter()
+            .map(|job| job.id)
+            .collect()
+    }
 
-        // Spawn the async task using the cloned job_id_for_async
-        self.tokio_runtime.spawn(async move {
-            if cancelled.load(Ordering::Relaxed) {
-                return;
-            }
+    /// Running jobs first, then queued jobs in the order they will start.
+    pub fn get_job_queue(&self) -> Vec<CompileJobFfi> {
+        self.jobs.snapshot()
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
 
-            println!("Starting async compilation for job: {}", job_id_for_async);
+    /// Compilations of the foreground document jump ahead of other queued work.
+    pub fn set_foreground_document(&self, uri: Option<String>) {
+        self.jobs.set_foreground(uri);
+    }
 
-            let ffi_result = if config.remote {
-                match perform_remote_compilation(&config, &job.uri, &job.content).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        println!("Remote compilation failed: {}", e);
-                        CompileResultFfi::error(e)
-                    }
-                }
-            } else {
-                match perform_local_compilation(&job).await {
-                    Ok(result) => result,
-                    Err(e) => {
-                        println!("Local compilation failed: {}", e);
-                        CompileResultFfi::error(e)
-                    }
-                }
-            };
+    pub fn set_max_concurrent_compilations(&self, count: u32) {
+        self.jobs.set_max_concurrent(count as usize);
+    }
 
-            println!("Compilation completed for job {}: success={}", job_id_for_async, ffi_result.success);
+    /// Sets the engine, modes and other options for one document's compilations.
+    /// `None` goes back to the options in the runtime config.
+    pub fn set_compile_options(&self, uri: String, options: Option<CompileOptionsFfi>) -> Result<(), ContextErrorFfi> {
+        let options: Option<CompileOptions> = options.map(Into::into);
+        if let Some(options) = &options {
+            options.validate().map_err(|e| ContextErrorFfi::from_backend_error(&uri, e))?;
+        }
+        Ok(self.compile_runtime().set_compile_options(&uri, options)?)
+    }
 
-            // Clean up job using the cloned ID
-            if let Ok(mut jobs) = active_jobs.lock() {
-                jobs.remove(&job_id_for_async);
-            }
+    pub fn get_compile_options(&self, uri: String) -> CompileOptionsFfi {
+        self.compile_runtime().compile_options(&uri).into()
+    }
 
-            if let Ok(cb) = live_callback.read() {
-                if let Some(callback) = &*cb {
-                    callback.on_compilation_completed(job.uri.clone(), ffi_result);
-                }
-            }
+    pub fn get_document_uris(&self) -> Vec<String> {
+        self.documents.read()
+            .map(|docs| docs.keys().cloned().collect())
+            .unwrap_or_default()
+    }
 
+    /// Compiles the document and returns the result, as a Swift `async` function or a
+    /// Kotlin `suspend` function. Progress and log lines still go to the live callback,
+    /// but the result is only returned. Cancelling the calling task stops the compilation.
+    /// Throws `Offline` when the server is unreachable and there is no local ConTeXt;
+    /// the document is then compiled on the server once it is back.
+    pub async fn compile_async(&self, uri: String) -> Result<CompileResultFfi, ContextErrorFfi> {
+        let content = self.get_document_source(uri.clone())
+            .ok_or_else(|| ContextErrorFfi::DocumentNotFound { uri: uri.clone() })?;
+
+        let (sender, receiver) = oneshot::channel();
+        let runtime = self.compile_runtime();
+        let config = self.config.clone();
+        let live_callback = Arc::clone(&self.live_callback);
+        let remote = Arc::clone(&self.remote);
+        let job_uri = uri.clone();
+
+        let job_id = format!("async_{}", uuid::Uuid::new_v4());
+        let cancel = self.jobs.submit(job_id, &uri, JobPriority::Normal, move |cancel| async move {
+            let result = run_compilation(&runtime, &config, &job_uri, &content, &cancel, &live_callback, &remote).await;
+            let _ = sender.send(result);
         });
 
-        // Return the original job_id
-        job_id
+        // Dropping this future (the foreign task was cancelled) cancels the job.
+        let _cancel_on_drop = cancel.drop_guard();
+        receiver.await.unwrap_or(Err(ContextErrorFfi::Cancelled { uri }))
     }
 
-    pub fn cancel_compilation(&self, job_id: String) -> bool {
-        if let Ok(mut jobs) = self.active_jobs.lock() {
-            jobs.remove(&job_id).is_some()
-        } else {
-            false
-        }
+    /// Checks every configured backend, e.g. whether mtxrun is installed and the
+    /// server is reachable. Backends that couldn't be set up are reported unhealthy.
+    pub async fn get_backend_health(&self) -> Result<Vec<BackendHealthFfi>, ContextErrorFfi> {
+        let runtime = self.compile_runtime();
+        let health = self.run_on_runtime(async move { runtime.backend_health().await }).await?;
+        Ok(health.into_iter().map(Into::into).collect())
     }
 
-    pub fn get_active_jobs(&self) -> Vec<String> {
-        self.active_jobs.lock()
-            .map(|jobs| jobs.keys().cloned().collect())
-            .unwrap_or_default()
+    /// The ConTeXt installation local compilations use, with its engine and version,
+    /// so clients can show it and warn when it is unsupported.
+    pub async fn get_context_installation(&self) -> Result<Option<ContextInstallationFfi>, ContextErrorFfi> {
+        let runtime = self.compile_runtime();
+        let installation = self.run_on_runtime(async move { runtime.context_installation().await }).await?;
+        Ok(installation.map(Into::into))
     }
 
-    pub fn get_document_uris(&self) -> Vec<String> {
-        self.documents.read()
-            .map(|docs| docs.keys().cloned().collect())
-            .unwrap_or_default()
+    /// Every ConTeXt installation found on this machine, in the order they would be picked.
+    pub async fn discover_context_installations(&self) -> Result<Vec<ContextInstallationFfi>, ContextErrorFfi> {
+        let installations = self.run_on_runtime(discovery::discover()).await?;
+        Ok(installations.into_iter().map(Into::into).collect())
     }
 
-    pub fn compile_async(&self, uri: String) -> Option<Arc<AsyncCompilationFuture>> {
-        let content = self.get_document_source(uri.clone())?;
-        let future = AsyncCompilationFuture::new(
-            self.tokio_runtime.clone(),
-            self.config.clone(),
-            uri,
-            content,
-            Arc::clone(&self.live_callback), // This now passes the Box version
-        );
-        Some(Arc::new(future))
+    pub fn forward_search(&self, uri: String, line: u32, column: u32) -> Option<PdfLocationFfi> {
+        self.compile_runtime()
+            .forward_search(&uri, line, column)
+            .map(Into::into)
+    }
+
+    /// Maps a point on a page of `uri`'s PDF back to a source location.
+    pub fn inverse_search(&self, uri: String, page: u32, x: f64, y: f64) -> Option<SourceLocationFfi> {
+        self.compile_runtime()
+            .inverse_search(&uri, page, x, y)
+            .map(Into::into)
     }
 
     // Helper methods for notifications
@@ -312,309 +387,461 @@ impl ContextRuntimeHandle {
             }
         }
     }
+}
 
-    fn notify_error(&self, error: RuntimeErrorFfi) {
-        if let Ok(cb) = self.live_callback.read() {
-            if let Some(callback) = &*cb {
-                callback.on_error(error);
-            }
+impl ContextRuntimeHandle {
+    // Parses `content` for its highlights and diagnostics.
+    fn analyze(content: &str) -> (Vec<HighlightFfi>, Vec<DiagnosticFfi>) {
+        let analysis = Analysis::new(content);
+        let highlights = analysis.highlights().into_iter().map(Into::into).collect();
+        let diagnostics = analysis.diagnostics().into_iter().map(Into::into).collect();
+        (highlights, diagnostics)
+    }
+
+    fn submit_compile(&self, job_id: String, uri: String, content: String) {
+        self.compiler().submit(job_id, uri, content);
+    }
+
+    fn compiler(&self) -> Compiler {
+        Compiler {
+            config: self.config.clone(),
+            runtime: Arc::clone(&self.compile_runtime),
+            live_callback: Arc::clone(&self.live_callback),
+            remote: Arc::clone(&self.remote),
+            jobs: Arc::clone(&self.jobs),
         }
     }
+
+    // Runs `future` on the handle's runtime, whichever executor awaits the result;
+    // dropping the returned future aborts it.
+    async fn run_on_runtime<T: Send + 'static>(
+        &self,
+        future: impl std::future::Future<Output = T> + Send + 'static,
+    ) -> Result<T, ContextErrorFfi> {
+        let task = self.tokio_runtime.spawn(future);
+        let _abort_on_drop = AbortOnDrop(task.abort_handle());
+        task.await.map_err(|e| ContextErrorFfi::Internal { details: e.to_string() })
+    }
+
+    fn compile_runtime(&self) -> Arc<ContextRuntime> {
+        Arc::clone(self.compile_runtime.get_or_init(|| ContextRuntime::new(self.config.clone().into())))
+    }
+
+    fn schedule_auto_compile(&self, uri: &str) {
+        if !self.is_auto_compile_enabled() {
+            return;
+        }
+        let Some(content) = self.get_document_source(uri.to_string()) else { return };
+
+        let runtime = self.compile_runtime();
+        let config = self.config.clone();
+        let live_callback = Arc::clone(&self.live_callback);
+        let remote = Arc::clone(&self.remote);
+        let jobs = Arc::clone(&self.jobs);
+        let auto_compile = Arc::clone(&self.auto_compile);
+        let job_uri = uri.to_string();
+
+        // One live-preview job per document: an edit stops the previous run right
+        // away, so its stale result is never delivered, and only the next is delayed.
+        let job_id = format!("{}{}", AUTO_COMPILE_JOB_PREFIX, job_uri);
+        self.jobs.cancel(&job_id);
+
+        self.scheduler.schedule(uri, move || async move {
+            let uri = job_uri.clone();
+            jobs.submit_replacing(job_id, &uri, JobPriority::Background, move |cancel| async move {
+                let ffi_result = match run_compilation(&runtime, &config, &job_uri, &content, &cancel, &live_callback, &remote).await {
+                    Ok(ffi_result) => ffi_result,
+                    Err(ContextErrorFfi::Cancelled { .. } | ContextErrorFfi::Offline { .. }) => return,
+                    Err(e) => CompileResultFfi::from_error(e),
+                };
+                // Auto-compile may have been turned off while this job was being submitted.
+                if !auto_compile.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                if let Ok(cb) = live_callback.read()
+                    && let Some(callback) = &*cb
+                {
+                    callback.on_compilation_completed(job_uri, ffi_result);
+                }
+            });
+        });
+    }
+}
+
+// What compile jobs need from the handle. Tasks on the handle's tokio runtime
+// take this instead of the handle: dropping the last handle inside one of them
+// would drop the runtime from its own worker thread, which panics.
+#[derive(Clone)]
+struct Compiler {
+    config: RuntimeConfigFfi,
+    runtime: Arc<OnceLock<Arc<ContextRuntime>>>,
+    live_callback: Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
+    remote: Arc<RemoteConnection>,
+    jobs: Arc<JobQueue>,
 }
 
+impl Compiler {
+    fn runtime(&self) -> Arc<ContextRuntime> {
+        Arc::clone(self.runtime.get_or_init(|| ContextRuntime::new(self.config.clone().into())))
+    }
 
-async fn perform_remote_compilation(
+    fn submit(&self, job_id: String, uri: String, content: String) {
+        let live_callback = Arc::clone(&self.live_callback);
+        let config = self.config.clone();
+        let runtime = self.runtime();
+        let remote = Arc::clone(&self.remote);
+        let job_id_for_async = job_id.clone();
+        let job_uri = uri.clone();
+
+        self.jobs.submit(job_id, &uri, JobPriority::Normal, move |cancel| async move {
+            log::debug!("Starting async compilation for job: {}", job_id_for_async);
+
+            let ffi_result = run_compilation(&runtime, &config, &job_uri, &content, &cancel, &live_callback, &remote).await;
+
+            if let Ok(cb) = live_callback.read()
+                && let Some(callback) = &*cb
+            {
+                match ffi_result {
+                    Ok(ffi_result) => {
+                        log::debug!("Compilation completed for job {}: success={}", job_id_for_async, ffi_result.success);
+                        callback.on_compilation_completed(job_uri, ffi_result);
+                    }
+                    Err(ContextErrorFfi::Cancelled { .. }) => {
+                        log::info!("Compilation cancelled for job {}", job_id_for_async);
+                        callback.on_error(RuntimeErrorFfi::Cancelled { uri: job_uri });
+                    }
+                    // Reported through `on_error`; the result follows once the server is back.
+                    Err(ContextErrorFfi::Offline { .. }) => {}
+                    Err(e) => callback.on_compilation_completed(job_uri, CompileResultFfi::from_error(e)),
+                }
+            }
+        });
+    }
+}
+
+// Fails with `Cancelled` when the compilation was cancelled.
+async fn run_compilation(
+    runtime: &ContextRuntime,
     config: &RuntimeConfigFfi,
     uri: &str,
     content: &str,
-) -> Result<CompileResultFfi, String> {
-    let server_url = config.server_url.as_ref().ok_or("No server URL configured")?;
-    let request_body = CompileRequestFfi {
-        uri: uri.to_string(),
-        content: content.to_string(),
-        format: Some("pdf".to_string()),
+    cancel: &CancellationToken,
+    live_callback: &Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
+    remote: &RemoteConnection,
+) -> Result<CompileResultFfi, ContextErrorFfi> {
+    let cancelled = || ContextErrorFfi::Cancelled { uri: uri.to_string() };
+    if cancel.is_cancelled() {
+        return Err(cancelled());
+    }
+
+    let attempt = remote.offline.start();
+    let remote_result = match config.server_url.as_deref().filter(|url| config.remote && !url.is_empty()) {
+        Some(server_url) => {
+            let (events, forwarder) = forward_compile_events(live_callback, uri);
+            let options = runtime.compile_options(uri);
+            let result = match remote.backend(config, server_url) {
+                Ok(backend) => perform_remote_compilation(backend.as_ref(), server_url, uri, content, options, cancel.clone(), events).await,
+                Err(e) => Err(e),
+            };
+            if cancel.is_cancelled() {
+                return Err(cancelled());
+            }
+            let _ = forwarder.await;
+            Some(result)
+        }
+        None => None,
     };
 
-    println!("Sending async request to: {}/compile", server_url);
-    println!("Request body: uri={}, content_length={}", request_body.uri, request_body.content.len());
-
-    let client = reqwest::Client::new();
-    let mut request = client.post(&format!("{}/compile", server_url))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .timeout(std::time::Duration::from_secs(30));
-
-    if let Some(token) = &config.auth_token {
-        request = request.bearer_auth(token);
-        println!("Using authentication token for async request");
-    }
-
-    let response = request.send().await.map_err(|e| format!("Failed to send request: {}", e))?;
-    let status = response.status();
-
-    if !status.is_success() {
-        let error_details = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("Server error: {} - {}", status, error_details));
-    }
-
-    let mut result = response.json::<CompileResultFfi>().await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    if let Some(pdf_path) = result.pdf_path.take() {
-        println!("Original PDF path from server: {}", pdf_path);
-        
-    let final_url = if pdf_path.starts_with("http://") || pdf_path.starts_with("https://") {
-            println!("Server returned complete URL");
-            pdf_path
-        } else {
-            let server_url = config.server_url.as_deref().unwrap_or("").trim_end_matches('/');
-            let pdf_path_trimmed = pdf_path.trim_start_matches('/');
-            let constructed_url = format!("{}/{}", server_url, pdf_path_trimmed);
-            println!("Constructed URL from relative path: {}", constructed_url);
-            constructed_url
-        };
-        
-        println!("Final PDF URL: {}", final_url);
-        result.pdf_path = Some(final_url);
-    }
-
-    // Ensure all diagnostics have valid ranges
-    result.diagnostics = result.diagnostics.into_iter().map(|d| {
-        DiagnosticFfi {
-            // FIX 1: Use `start`, `end`, and `severity`
-            start: d.start.or(Some(0)), // Use .or(Some(0)) as they are Option<u32>
-            end: d.end.or(Some(0)),     // Use .or(Some(0)) as they are Option<u32>
-            severity: d.severity,
-            message: d.message,
+    let result = match remote_result {
+        Some(Ok(result)) => {
+            remote.offline.compiled(uri, attempt);
+            Ok(result)
+        }
+        // Only an unreachable server falls back, straight to local ConTeXt: the
+        // runtime's backends would try the same server again first.
+        Some(Err(e)) if e.is_unavailable() && runtime.has_local_backend() => {
+            log::warn!("Remote compilation failed, compiling locally: {}", e);
+            compile_locally(runtime, uri, content, cancel, live_callback, true).await
         }
-    }).collect();
+        // Without local ConTeXt the document waits for the server instead, so either
+        // way it is compiled once.
+        Some(Err(e)) => {
+            log::warn!("Remote compilation failed: {}", e);
+            let (error_uri, details) = (uri.to_string(), e.to_string());
+            let (notice, error) = match e {
+                e if e.is_transient() => {
+                    remote.offline.push(uri, attempt);
+                    let error = ContextErrorFfi::Offline { uri: error_uri.clone(), details: details.clone() };
+                    (RuntimeErrorFfi::Offline { uri: error_uri, details }, error)
+                }
+                e @ BackendError::Unauthorized(_) => {
+                    (RuntimeErrorFfi::AuthenticationFailed { uri: error_uri, details }, ContextErrorFfi::from_backend_error(uri, e))
+                }
+                e => (RuntimeErrorFfi::RemoteRejected { uri: error_uri, details }, ContextErrorFfi::from_backend_error(uri, e)),
+            };
+            notify_error(live_callback, notice);
+            Err(error)
+        }
+        None => compile_locally(runtime, uri, content, cancel, live_callback, false).await,
+    };
 
-    Ok(result)
+    if cancel.is_cancelled() {
+        return Err(cancelled());
+    }
+    result
 }
 
-async fn perform_local_compilation(job: &CompilationJob) -> Result<CompileResultFfi, String> {
-    println!("Performing local compilation");
-    let runtime = ContextRuntime::new(job.config.clone().into());
+// Compiles with the runtime's backends, or with local ConTeXt only when `local_only`.
+async fn compile_locally(
+    runtime: &ContextRuntime,
+    uri: &str,
+    content: &str,
+    cancel: &CancellationToken,
+    live_callback: &Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
+    local_only: bool,
+) -> Result<CompileResultFfi, ContextErrorFfi> {
+    let (events, forwarder) = forward_compile_events(live_callback, uri);
+    let result = perform_local_compilation(runtime, uri, content, cancel.clone(), events, local_only).await;
+    // Deliver the remaining progress before the result, unless the run was killed.
+    if !cancel.is_cancelled() {
+        let _ = forwarder.await;
+    }
+    result.map_err(|e| {
+        log::warn!("Local compilation failed: {}", e);
+        e
+    })
+}
 
-    runtime.open_document(job.uri.clone(), job.content.clone())
-        .map_err(|e| format!("Failed to open document: {}", e))?;
+struct AbortOnDrop(tokio::task::AbortHandle);
 
-    let rt_inner = tokio::runtime::Runtime::new()
-        .map_err(|e| format!("Failed to create runtime: {}", e))?;
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
 
-    let result = rt_inner.block_on(runtime.compile_document(&job.uri))
-        .map_err(|e| format!("Compilation failed: {}", e))?;
+async fn perform_remote_compilation(
+    backend: &dyn CompilationBackend,
+    server_url: &str,
+    uri: &str,
+    content: &str,
+    options: CompileOptions,
+    cancel: CancellationToken,
+    events: mpsc::UnboundedSender<CompileEvent>,
+) -> Result<CompileResultFfi, BackendError> {
+    log::info!("Compiling {} on {} ({} bytes)", uri, server_url, content.len());
 
-    println!("Local compilation successful");
-    Ok(result.into())
-}
+    let request = CompilationRequest {
+        content: content.to_string(),
+        job_id: uri.to_string(),
+        options,
+        cancel,
+        events: Some(events),
+        ..Default::default()
+    };
 
-// Async compilation future
-#[derive(uniffi::Object)]
-pub struct AsyncCompilationFuture {
-    result: Arc<Mutex<Option<CompileResultFfi>>>,
-    ready: Arc<AtomicBool>,
-    cancelled: Arc<AtomicBool>,
-    // Change this to Box as well
-    live_callback: Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
+    Ok(backend.compile(request).await?.into())
 }
 
-impl AsyncCompilationFuture {
-    fn new(
-        tokio_runtime: Arc<tokio::runtime::Runtime>,
-        config: RuntimeConfigFfi,
-        uri: String,
-        content: String,
-        // Change parameter type
-        live_callback: Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
-    ) -> Self {
-        let result = Arc::new(Mutex::new(None));
-        let ready = Arc::new(AtomicBool::new(false));
-        let cancelled = Arc::new(AtomicBool::new(false));
-
-        let result_clone = Arc::clone(&result);
-        let ready_clone = Arc::clone(&ready);
-        let cancelled_clone = Arc::clone(&cancelled);
-        // FIX 2 (continued): Clone for the async move block
-        let live_callback_clone = Arc::clone(&live_callback);
-
-        let uri_for_callback = uri.clone(); // Keep original URI for callback
-
-        tokio_runtime.spawn(async move {
-            if cancelled_clone.load(Ordering::Relaxed) {
-                return;
-            }
+// What remote compilations share: the app's credentials, the versions the server
+// has for deltas and the documents waiting for it to become reachable again.
+#[derive(Default)]
+struct RemoteConnection {
+    auth: RwLock<Option<Arc<dyn AuthProvider>>>,
+    // Kept between compilations so they share HTTP connections, the server's
+    // capabilities and the compile cache; rebuilt when the credentials change.
+    backend: Mutex<Option<Arc<dyn CompilationBackend>>>,
+    uploads: Arc<UploadTracker>,
+    offline: OfflineQueue,
+}
 
-            println!("Starting async compilation for URI: {}", uri);
-
-            let ffi_result = if config.remote {
-                println!("Performing remote async compilation");
-                // It's generally better to call the shared `perform_remote_compilation` here
-                // but adapting the existing duplicated logic as per your request for minimal changes
-                let server_url = config.server_url.clone().unwrap_or_default();
-                let auth_token = config.auth_token.clone();
-                let request_body = CompileRequestFfi {
-                    uri: uri.clone(),
-                    content: content.clone(),
-                    format: Some("pdf".to_string()),
-                };
+impl RemoteConnection {
+    fn backend(&self, config: &RuntimeConfigFfi, server_url: &str) -> Result<Arc<dyn CompilationBackend>, BackendError> {
+        let Ok(mut cached) = self.backend.lock() else {
+            return self.build_backend(config, server_url);
+        };
+        if let Some(backend) = &*cached {
+            return Ok(Arc::clone(backend));
+        }
+        let backend = self.build_backend(config, server_url)?;
+        *cached = Some(Arc::clone(&backend));
+        Ok(backend)
+    }
 
-                println!("Sending async request to: {}/compile", server_url);
-                println!("Request body: uri={}, content_length={}", request_body.uri, request_body.content.len());
+    // The timeout only bounds short requests, so compiles take as long as they need.
+    fn build_backend(&self, config: &RuntimeConfigFfi, server_url: &str) -> Result<Arc<dyn CompilationBackend>, BackendError> {
+        let auth = self.auth.read().ok().and_then(|auth| auth.clone())
+            .unwrap_or_else(|| Arc::new(StaticCredentials::bearer(config.auth_token.clone())));
+        let backend = RemoteBackend::new(server_url.to_string(), None)
+            .with_cache_dir(config.cache_dir.clone().map(PathBuf::from))
+            .with_retry(config.remote_retry.clone().map(Into::into).unwrap_or_default())
+            .with_uploads(Arc::clone(&self.uploads))
+            .with_timeout(Some(std::time::Duration::from_secs(30)))
+            .with_auth(auth)?;
+        Ok(ContextRuntime::with_cache(&config.clone().into(), Box::new(backend)))
+    }
 
-                let client = reqwest::Client::new();
-                let mut request = client.post(&format!("{}/compile", server_url))
-                    .header("Content-Type", "application/json")
-                    .json(&request_body)
-                    .timeout(std::time::Duration::from_secs(30));
+    fn set_auth(&self, provider: Option<Arc<dyn AuthProvider>>) {
+        if let Ok(mut auth) = self.auth.write() {
+            *auth = provider;
+        }
+        if let Ok(mut backend) = self.backend.lock() {
+            *backend = None;
+        }
+    }
+}
 
-                if let Some(token) = auth_token {
-                    request = request.bearer_auth(token);
-                    println!("Using authentication token for async request");
-                }
+// Adapts the app's blocking callback, caching its credentials between requests.
+// The client certificate is asked for once, when the provider is set.
+struct CallbackAuthProvider {
+    callback: Arc<dyn AuthProviderCallback>,
+    cached: Mutex<Option<Credentials>>,
+    certificate: Option<ClientCertificate>,
+}
 
-                match request.send().await {
-                    Ok(response) => {
-                        let status = response.status();
-                        println!("Async compilation response status: {}", status);
-
-                        if status.is_success() {
-                            match response.json::<CompileResultFfi>().await {
-                                Ok(mut result) => { // Make result mutable to potentially modify pdf_path
-                                    if let Some(pdf_path) = result.pdf_path.take() {
-                                        let server_url = config.server_url.as_deref().unwrap_or("").trim_end_matches('/');
-                                        let pdf_path_trimmed = pdf_path.trim_start_matches('/');
-                                        let full_url = if server_url.is_empty() {
-                                            pdf_path_trimmed.to_string()
-                                        } else {
-                                            format!("{}/{}", server_url, pdf_path_trimmed)
-                                        };
-                                        result.pdf_path = Some(full_url);
-                                    }
-
-                                    result.diagnostics = result.diagnostics.into_iter().map(|d| {
-                                        DiagnosticFfi {
-                                            // FIX 1: Use `start`, `end`, and `severity`
-                                            start: d.start.or(Some(0)),
-                                            end: d.end.or(Some(0)),
-                                            severity: d.severity,
-                                            message: d.message,
-                                        }
-                                    }).collect();
-                                    println!("Successfully parsed async compilation result: success={}", result.success);
-                                    result
-                                },
-                                Err(e) => {
-                                    let error_msg = format!("Failed to parse remote async compilation response: {}", e);
-                                    println!("{}", error_msg);
-                                    CompileResultFfi::error(error_msg)
-                                },
-                            }
-                        } else {
-                            let error_details = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-                            let error_msg = format!("Remote async compilation failed with status: {} - {}", status, error_details);
-                            println!("{}", error_msg);
-                            CompileResultFfi::error(error_msg)
-                        }
-                    },
-                    Err(e) => {
-                        let error_msg = format!("Failed to send remote async compilation request: {}", e);
-                        println!("{}", error_msg);
-                        CompileResultFfi::error(error_msg)
-                    },
-                }
-            } else {
-                println!("Performing local async compilation");
-                let compilation_result = tokio::task::spawn_blocking(move || {
-                    if cancelled_clone.load(Ordering::Relaxed) {
-                        // FIX: Return a proper CompileResultFfi for cancellation, perhaps with a specific message
-                        return Ok(CompileResultFfi::error("Compilation cancelled".to_string()));
-                    }
+impl CallbackAuthProvider {
+    fn new(callback: Box<dyn AuthProviderCallback>) -> Self {
+        let certificate = callback.get_client_certificate().map(Into::into);
+        Self { callback: Arc::from(callback), cached: Mutex::new(None), certificate }
+    }
 
-                    let runtime = ContextRuntime::new(config.into());
-                    runtime.open_document(uri.clone(), content)
-                        .and_then(|_| {
-                            let rt_inner = tokio::runtime::Runtime::new()
-                                .expect("Failed to create tokio runtime for local async compilation");
-                            rt_inner.block_on(runtime.compile_document(&uri))
-                        })
-                        .map(|res| res.into()) // Convert CompilationResult to CompileResultFfi
-                        .map_err(|e| format!("{}", e)) // Convert RuntimeErrors to String
-                }).await;
-
-                match compilation_result {
-                    Ok(Ok(compile_result)) => {
-                        println!("Local async compilation successful");
-                        compile_result
-                    },
-                    Ok(Err(error_msg)) => {
-                        let error_msg = format!("Local async compilation failed: {}", error_msg);
-                        println!("{}", error_msg);
-                        CompileResultFfi {
-                            success: false,
-                            pdf_path: None,
-                            log: error_msg.clone(),
-                            diagnostics: vec![DiagnosticFfi {
-                                start: Some(0),
-                                end: Some(0),
-                                severity: "error".to_string(),
-                                message: error_msg,
-                            }],
-                        }
-                    },
-                    Err(join_err) => {
-                        let error_msg = format!("Async compilation task failed: {}", join_err);
-                        println!("{}", error_msg);
-                        CompileResultFfi {
-                            success: false,
-                            pdf_path: None,
-                            log: error_msg.clone(),
-                            diagnostics: vec![DiagnosticFfi {
-                                start: Some(0),
-                                end: Some(0),
-                                severity: "error".to_string(),
-                                message: error_msg,
-                            }],
-                        }
-                    }
-                }
-            };
+    async fn ask<T, F>(&self, call: F) -> Result<T, BackendError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn AuthProviderCallback) -> T + Send + 'static,
+    {
+        let callback = Arc::clone(&self.callback);
+        tokio::task::spawn_blocking(move || call(callback.as_ref())).await
+            .map_err(|e| BackendError::Unauthorized(format!("Auth provider failed: {}", e)))
+    }
 
-            println!("Async compilation completed: success={}", ffi_result.success);
+    fn store(&self, credentials: Option<Credentials>) -> Option<Credentials> {
+        if let Ok(mut cached) = self.cached.lock() {
+            cached.clone_from(&credentials);
+        }
+        credentials
+    }
+}
 
-            if let Ok(mut result_guard) = result_clone.lock() {
-                *result_guard = Some(ffi_result.clone()); // Clone for storage
-            }
+#[async_trait::async_trait]
+impl AuthProvider for CallbackAuthProvider {
+    async fn credentials(&self) -> Result<Option<Credentials>, BackendError> {
+        if let Some(cached) = self.cached.lock().ok().and_then(|cached| cached.clone()) {
+            return Ok(Some(cached));
+        }
+        let credentials = self.ask(|callback| callback.get_credentials()).await?;
+        Ok(self.store(credentials.map(Into::into)))
+    }
 
-            // Notify the live callback
-            if let Ok(cb) = live_callback_clone.read() {
-                if let Some(callback) = &*cb {
-                    callback.on_compilation_completed(uri_for_callback, ffi_result); // Use uri_for_callback
-                }
-            }
+    async fn refresh(&self, rejected: Option<&Credentials>) -> Result<Option<Credentials>, BackendError> {
+        let rejected = rejected.cloned().map(CredentialsFfi::from);
+        let credentials = self.ask(move |callback| callback.refresh_credentials(rejected)).await?;
+        Ok(self.store(credentials.map(Into::into)))
+    }
 
-            ready_clone.store(true, Ordering::Relaxed);
-        });
+    fn client_certificate(&self) -> Option<ClientCertificate> {
+        self.certificate.clone()
+    }
+}
 
-        Self { result, ready, cancelled, live_callback } // Initialize live_callback
+fn notify_error(live_callback: &RwLock<Option<Box<dyn LiveUpdateCallback>>>, error: RuntimeErrorFfi) {
+    if let Ok(cb) = live_callback.read()
+        && let Some(callback) = &*cb
+    {
+        callback.on_error(error);
     }
 }
 
-#[uniffi::export]
-impl AsyncCompilationFuture {
-    pub fn poll_result(&self) -> Option<CompileResultFfi> {
-        if self.is_ready() {
-            self.result.lock().ok().and_then(|r| r.clone())
-        } else {
-            None
+// Waits for documents to queue up offline, probes the server with growing delays (or
+// as soon as the app reports the network is back) and resubmits them once it answers.
+async fn resubmit_when_reachable(
+    compiler: Compiler,
+    documents: Arc<RwLock<HashMap<String, DocumentState>>>,
+    network_available: Arc<Notify>,
+    server_url: String,
+) {
+    let (first_delay, max_delay) = RECONNECT_BACKOFF;
+    let remote = &compiler.remote;
+    loop {
+        remote.offline.wait_for_documents().await;
+
+        let mut delay = first_delay;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = network_available.notified() => {}
+            }
+            if let Ok(server) = remote.backend(&compiler.config, &server_url)
+                && server.health_check().await.is_ok()
+            {
+                break;
+            }
+            delay = (delay * 2).min(max_delay);
         }
-    }
 
-    pub fn is_ready(&self) -> bool {
-        self.ready.load(Ordering::Relaxed)
+        // Documents are compiled as they are now; closed ones are dropped.
+        for uri in remote.offline.drain() {
+            let content = documents.read().ok().and_then(|docs| docs.get(&uri).map(|doc| doc.content.clone()));
+            let Some(content) = content else { continue };
+            log::info!("Compile server reachable again, resubmitting {}", uri);
+            compiler.submit(format!("compile_{}", uuid::Uuid::new_v4()), uri, content);
+        }
     }
+}
 
-    pub fn cancel(&self) -> bool {
-        self.cancelled.store(true, Ordering::Relaxed);
-        true
-    }
+// Relays backend events to the live callback until the sender side is dropped.
+fn forward_compile_events(
+    live_callback: &Arc<RwLock<Option<Box<dyn LiveUpdateCallback>>>>,
+    uri: &str,
+) -> (mpsc::UnboundedSender<CompileEvent>, tokio::task::JoinHandle<()>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let live_callback = Arc::clone(live_callback);
+    let uri = uri.to_string();
+
+    let forwarder = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            if let Ok(cb) = live_callback.read()
+                && let Some(callback) = &*cb
+            {
+                match event {
+                    CompileEvent::Progress(progress) => callback.on_compilation_progress(uri.clone(), progress.into()),
+                    CompileEvent::Log { chunk, .. } => callback.on_log_chunk(uri.clone(), chunk),
+                    CompileEvent::Retrying { attempt, delay, reason } => callback.on_error(RuntimeErrorFfi::Retrying {
+                        uri: uri.clone(),
+                        attempt,
+                        delay_ms: delay.as_millis() as u64,
+                        details: reason,
+                    }),
+                }
+            }
+        }
+    });
+
+    (sender, forwarder)
+}
+
+async fn perform_local_compilation(
+    runtime: &ContextRuntime,
+    uri: &str,
+    content: &str,
+    cancel: CancellationToken,
+    events: mpsc::UnboundedSender<CompileEvent>,
+    local_only: bool,
+) -> Result<CompileResultFfi, ContextErrorFfi> {
+    log::debug!("Performing local compilation");
+
+    runtime.open_document(uri.to_string(), content.to_string())?;
+
+    let compilation = if local_only {
+        runtime.compile_document_locally(uri, cancel, Some(events)).await
+    } else {
+        runtime.compile_document_streaming(uri, cancel, Some(events)).await
+    };
+    // Timeouts, limits and sandbox violations are thrown with the run's partial log,
+    // so the user can see where it got stuck.
+    let result = compilation?;
+
+    log::debug!("Local compilation successful");
+    Ok(result.into())
 }
@@ -1,9 +1,21 @@
-use crate::backend_traits::CompilationResult;
-use crate::runtime::{RuntimeError, RuntimeConfig};
+use crate::artifact::ArtifactData;
+use crate::auth::{ClientCertificate, Credentials};
+use crate::backend_traits::{BackendError, BackendHealth, CompilationResult, CompileOptions, ContextEngineChoice};
+use crate::compile_cache::CompileCacheLimits;
+use crate::retry::RetryPolicy;
+use crate::sandbox::{SandboxPolicy, ViolationKind};
+use crate::discovery::{ContextEngine, ContextInstallation, InstallationSource};
+use crate::job_queue::{JobInfo, JobPriority, JobState, DEFAULT_MAX_CONCURRENT_JOBS};
+use crate::process::{ProcessLimits, DEFAULT_COMPILE_TIMEOUT};
+use crate::progress::CompileProgress;
+use crate::runtime::{RuntimeError, RuntimeConfig, SourceLocation};
+use crate::synctex::PdfRect;
 use crate::diagnostic::Diagnostic;
 use crate::highlight::Highlight;
 use rowan::TextRange;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 use uniffi;
 
 // ============================================================================
@@ -29,6 +41,16 @@ pub struct CompileResultFfi {
     pub pdf_path: Option<String>,
     pub log: String,
     pub diagnostics: Vec<DiagnosticFfi>,
+    // Set when the PDF is returned in memory rather than as `pdf_path`
+    #[serde(default, skip_serializing)]
+    pub pdf_bytes: Option<Vec<u8>>,
+    #[serde(default, alias = "output_hash")]
+    pub content_hash: Option<String>,
+    #[serde(default)]
+    pub page_count: Option<u32>,
+    // Which backend produced the result, e.g. "local" or "remote"
+    #[serde(default)]
+    pub backend: Option<String>,
 }
 
 #[derive(Debug, Clone, uniffi::Enum)]
@@ -36,9 +58,65 @@ pub enum RuntimeErrorFfi {
     DocumentNotFound { uri: String },
     LockPoisoned,
     CompilationError { details: String },
-    // === ADD THIS VARIANT ===
     Unavailable { details: String },
-    // ========================
+    Cancelled { uri: String },
+    // The run took longer than its timeout; `log` is its output up to then
+    Timeout { details: String, log: String },
+    // A resource limit killed the run; `log` is its output up to then
+    LimitExceeded { details: String, log: String },
+    // The document did something the sandbox policy blocks
+    PolicyViolation { kind: PolicyViolationKindFfi, details: String, log: String },
+    // A remote request failed for a transient reason and is sent again after `delay_ms`
+    Retrying { uri: String, attempt: u32, delay_ms: u64, details: String },
+    // The server stayed unreachable and there is no local ConTeXt to fall back to; the
+    // document is compiled on the server once it's back
+    Offline { uri: String, details: String },
+    // The server refused the compilation, e.g. invalid options; retrying won't help
+    RemoteRejected { uri: String, details: String },
+    // The server rejected the credentials, also after `refresh_credentials`
+    AuthenticationFailed { uri: String, details: String },
+}
+
+// Thrown by the handle's methods, so Swift and Kotlin callers get native errors
+#[derive(Debug, Clone, PartialEq, thiserror::Error, uniffi::Error)]
+pub enum ContextErrorFfi {
+    #[error("Document not found: {uri}")]
+    DocumentNotFound { uri: String },
+    // `start..end` lies outside the document or splits a character
+    #[error("Invalid range {start}..{end} in {uri} ({length} bytes)")]
+    InvalidRange { uri: String, start: u32, end: u32, length: u32 },
+    #[error("Invalid compile options: {details}")]
+    InvalidOptions { details: String },
+    // Neither the server nor a local ConTeXt can compile right now
+    #[error("Backend unavailable: {details}")]
+    BackendUnavailable { details: String },
+    #[error("Network error: {details}")]
+    Network { details: String },
+    // See `RuntimeErrorFfi::Offline`; the result arrives through `on_compilation_completed`
+    #[error("Compile server unreachable, {uri} is compiled there once it is back: {details}")]
+    Offline { uri: String, details: String },
+    #[error("Authentication failed: {details}")]
+    AuthenticationFailed { details: String },
+    // `log` is the run's output up to the limit
+    #[error("{details}")]
+    Timeout { details: String, log: String },
+    #[error("{details}")]
+    LimitExceeded { details: String, log: String },
+    #[error("Compilation blocked by the sandbox policy: {details}")]
+    PolicyViolation { kind: PolicyViolationKindFfi, details: String, log: String },
+    #[error("Compilation cancelled: {uri}")]
+    Cancelled { uri: String },
+    #[error("Compilation failed: {details}")]
+    Compilation { details: String },
+    #[error("Internal error: {details}")]
+    Internal { details: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum PolicyViolationKindFfi {
+    ShellEscape,
+    FileAccess,
+    Blocked,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, uniffi::Record)]
@@ -63,14 +141,149 @@ pub struct RuntimeConfigFfi {
     pub server_url: Option<String>,
     pub auth_token: Option<String>,
     pub local_executable: Option<String>,
+    pub output_dir: Option<String>,
+    pub cache_dir: Option<String>,
+    // A limit of 0 entries turns the compile cache off
+    pub compile_cache_entries: u32,
+    pub compile_cache_bytes: u64,
+    // Limits for local runs, 0 meaning unlimited
+    pub compile_timeout_ms: u64,
+    pub max_memory_bytes: u64,
+    pub max_cpu_seconds: u64,
+    // How many compilations may run at once, 0 meaning the default
+    pub max_concurrent_compilations: u32,
+    // Options for documents without their own, None meaning plain `context`
+    pub compile_options: Option<CompileOptionsFfi>,
+    // Restrictions for local runs, None trusting every document
+    pub sandbox: Option<SandboxPolicyFfi>,
+    // Retries of failed remote requests, None using the defaults
+    pub remote_retry: Option<RetryPolicyFfi>,
+    // Keep a warm ConTeXt engine between local compilations
+    pub persistent_server: bool,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, uniffi::Record)]
-pub struct CompileRequestFfi {
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct RetryPolicyFfi {
+    // Attempts in total, 1 meaning no retries
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // Up to this percentage of each delay is randomly taken off
+    pub jitter_percent: u32,
+}
+
+// How requests to the compile server are authenticated
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum CredentialsFfi {
+    Bearer { token: String },
+    // Sent as `header: key`, e.g. `X-Api-Key`
+    ApiKey { header: String, key: String },
+}
+
+// A PEM certificate and its PKCS#8 PEM private key, for servers requiring mutual TLS
+#[derive(Clone, PartialEq, Eq, uniffi::Record)]
+pub struct ClientCertificateFfi {
+    pub certificate_pem: String,
+    pub private_key_pem: String,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct SandboxPolicyFfi {
+    pub allow_shell_escape: bool,
+    pub restrict_file_access: bool,
+    // Linux only, needs bubblewrap (bwrap)
+    pub isolate: bool,
+    pub writable_paths: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextEngineChoiceFfi {
+    Lmtx,
+    Mkiv,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, uniffi::Record)]
+#[serde(default)]
+pub struct CompileOptionsFfi {
+    pub engine: Option<ContextEngineChoiceFfi>,
+    pub draft: bool,
+    pub modes: Vec<String>,
+    pub arguments: HashMap<String, String>,
+    pub result: Option<String>,
+    pub runs: Option<u32>,
+    pub environments: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct CompileProgressFfi {
+    pub pass: u32,
+    pub pages: u32,
+    pub current_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum JobPriorityFfi {
+    Background,
+    Normal,
+    Foreground,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct CompileJobFfi {
+    pub id: String,
     pub uri: String,
-    pub content: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub format: Option<String>,
+    pub priority: JobPriorityFfi,
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct BackendHealthFfi {
+    pub name: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum InstallationSourceFfi {
+    Configured,
+    Environment,
+    Path,
+    Standalone,
+    TexLive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ContextEngineFfi {
+    LuaMetaTeX,
+    LuaTeX,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct ContextInstallationFfi {
+    pub mtxrun_path: String,
+    pub source: InstallationSourceFfi,
+    pub engine: ContextEngineFfi,
+    pub version: Option<String>,
+    pub supported: bool,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct PdfLocationFfi {
+    pub page: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct SourceLocationFfi {
+    pub uri: String,
+    pub line: u32,
+    pub column: u32,
 }
 
 // ============================================================================
@@ -103,9 +316,69 @@ impl From<RuntimeError> for RuntimeErrorFfi {
             RuntimeError::CompilationError { message, .. } => Self::CompilationError {
                 details: message
             },
-            // === ADD THIS MATCH ARM ===
             RuntimeError::Unavailable(details) => Self::Unavailable { details },
-            // ==========================
+            RuntimeError::Cancelled(uri) => Self::Cancelled { uri },
+            RuntimeError::AuthenticationFailed { uri, details } => Self::AuthenticationFailed { uri, details },
+            RuntimeError::Timeout { message, log } => Self::Timeout { details: message, log },
+            RuntimeError::LimitExceeded { message, log } => Self::LimitExceeded { details: message, log },
+            RuntimeError::PolicyViolation { violation, log } => Self::PolicyViolation {
+                kind: violation.kind.into(),
+                details: violation.to_string(),
+                log,
+            },
+        }
+    }
+}
+
+impl From<RuntimeError> for ContextErrorFfi {
+    fn from(err: RuntimeError) -> Self {
+        match err {
+            RuntimeError::DocumentNotFound(uri) => Self::DocumentNotFound { uri },
+            RuntimeError::LockPoisoned => Self::Internal { details: err.to_string() },
+            RuntimeError::CompilationError { message, .. } => Self::Compilation { details: message },
+            RuntimeError::Unavailable(details) => Self::BackendUnavailable { details },
+            RuntimeError::Cancelled(uri) => Self::Cancelled { uri },
+            RuntimeError::AuthenticationFailed { details, .. } => Self::AuthenticationFailed { details },
+            RuntimeError::Timeout { message, log } => Self::Timeout { details: message, log },
+            RuntimeError::LimitExceeded { message, log } => Self::LimitExceeded { details: message, log },
+            RuntimeError::PolicyViolation { violation, log } => Self::PolicyViolation {
+                kind: violation.kind.into(),
+                details: violation.to_string(),
+                log,
+            },
+        }
+    }
+}
+
+impl ContextErrorFfi {
+    /// `err` from compiling `uri`, which it doesn't name itself.
+    pub fn from_backend_error(uri: &str, err: BackendError) -> Self {
+        let details = err.to_string();
+        match err {
+            BackendError::Network(details) => Self::Network { details },
+            BackendError::Compilation(details) => Self::Compilation { details },
+            BackendError::Unavailable(details) | BackendError::Setup(details) => Self::BackendUnavailable { details },
+            BackendError::Unauthorized(details) => Self::AuthenticationFailed { details },
+            BackendError::IO(_) => Self::Internal { details },
+            BackendError::Cancelled => Self::Cancelled { uri: uri.to_string() },
+            BackendError::InvalidOptions(details) => Self::InvalidOptions { details },
+            BackendError::Timeout { log, .. } => Self::Timeout { details, log },
+            BackendError::ResourceExceeded { log, .. } => Self::LimitExceeded { details, log },
+            BackendError::PolicyViolation { violation, log } => Self::PolicyViolation {
+                kind: violation.kind.into(),
+                details: violation.to_string(),
+                log,
+            },
+        }
+    }
+}
+
+impl From<ViolationKind> for PolicyViolationKindFfi {
+    fn from(kind: ViolationKind) -> Self {
+        match kind {
+            ViolationKind::ShellEscape => Self::ShellEscape,
+            ViolationKind::FileAccess => Self::FileAccess,
+            ViolationKind::Blocked => Self::Blocked,
         }
     }
 }
@@ -176,11 +449,26 @@ impl From<CompilationResult> for CompileResultFfi {
             });
         }
 
+        let (content_hash, page_count) = result.artifact.as_ref()
+            .map(|a| (a.content_hash.clone(), a.page_count))
+            .unwrap_or_default();
+
+        let (pdf_path, pdf_bytes) = match result.artifact.map(|a| a.data) {
+            Some(ArtifactData::Bytes(bytes)) => (None, Some(bytes)),
+            Some(ArtifactData::File(path)) => (path.to_str().map(|s| s.to_string()), None),
+            Some(ArtifactData::Url(url)) => (Some(url), None),
+            None => (None, None),
+        };
+
         CompileResultFfi {
             success: result.success,
-            pdf_path: result.pdf_path.and_then(|p| p.to_str().map(|s| s.to_string())),
+            pdf_path,
             log: result.log,
             diagnostics,
+            pdf_bytes,
+            content_hash,
+            page_count,
+            backend: Some(result.backend).filter(|name| !name.is_empty()),
         }
     }
 }
@@ -199,11 +487,109 @@ impl From<Result<CompilationResult, RuntimeError>> for CompileResultFfi {
                     severity: "error".to_string(),
                     message: format!("{:?}", error),
                 }],
+                ..Default::default()
             }
         }
     }
 }
 
+impl From<PdfRect> for PdfLocationFfi {
+    fn from(rect: PdfRect) -> Self {
+        Self {
+            page: rect.page,
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+impl From<SourceLocation> for SourceLocationFfi {
+    fn from(location: SourceLocation) -> Self {
+        Self {
+            uri: location.uri,
+            line: location.line,
+            column: location.column,
+        }
+    }
+}
+
+impl From<CompileProgress> for CompileProgressFfi {
+    fn from(progress: CompileProgress) -> Self {
+        Self {
+            pass: progress.pass,
+            pages: progress.pages,
+            current_file: progress.current_file,
+        }
+    }
+}
+
+impl From<BackendHealth> for BackendHealthFfi {
+    fn from(health: BackendHealth) -> Self {
+        Self {
+            name: health.name,
+            healthy: health.healthy,
+            error: health.error,
+        }
+    }
+}
+
+impl From<JobInfo> for CompileJobFfi {
+    fn from(job: JobInfo) -> Self {
+        Self {
+            id: job.id,
+            uri: job.uri,
+            priority: match job.priority {
+                JobPriority::Background => JobPriorityFfi::Background,
+                JobPriority::Normal => JobPriorityFfi::Normal,
+                JobPriority::Foreground => JobPriorityFfi::Foreground,
+            },
+            running: job.state == JobState::Running,
+        }
+    }
+}
+
+impl From<CompileOptions> for CompileOptionsFfi {
+    fn from(options: CompileOptions) -> Self {
+        Self {
+            engine: options.engine.map(|engine| match engine {
+                ContextEngineChoice::Lmtx => ContextEngineChoiceFfi::Lmtx,
+                ContextEngineChoice::Mkiv => ContextEngineChoiceFfi::Mkiv,
+            }),
+            draft: options.draft,
+            modes: options.modes,
+            arguments: options.arguments.into_iter().collect(),
+            result: options.result,
+            runs: options.runs,
+            environments: options.environments,
+        }
+    }
+}
+
+impl From<ContextInstallation> for ContextInstallationFfi {
+    fn from(installation: ContextInstallation) -> Self {
+        Self {
+            mtxrun_path: installation.mtxrun_path.to_string_lossy().into_owned(),
+            source: match installation.source {
+                InstallationSource::Configured => InstallationSourceFfi::Configured,
+                InstallationSource::Environment => InstallationSourceFfi::Environment,
+                InstallationSource::Path => InstallationSourceFfi::Path,
+                InstallationSource::Standalone => InstallationSourceFfi::Standalone,
+                InstallationSource::TexLive => InstallationSourceFfi::TexLive,
+            },
+            engine: match installation.engine {
+                ContextEngine::LuaMetaTeX => ContextEngineFfi::LuaMetaTeX,
+                ContextEngine::LuaTeX => ContextEngineFfi::LuaTeX,
+                ContextEngine::Unknown => ContextEngineFfi::Unknown,
+            },
+            version: installation.version.clone(),
+            supported: installation.is_supported(),
+            warnings: installation.warnings,
+        }
+    }
+}
+
 impl From<RuntimeConfigFfi> for RuntimeConfig {
     fn from(config: RuntimeConfigFfi) -> Self {
         Self {
@@ -211,6 +597,21 @@ impl From<RuntimeConfigFfi> for RuntimeConfig {
             server_url: config.server_url,
             auth_token: config.auth_token,
             local_executable: config.local_executable.map(PathBuf::from),
+            output_dir: config.output_dir.map(PathBuf::from),
+            cache_dir: config.cache_dir.map(PathBuf::from),
+            compile_cache: (config.compile_cache_entries > 0).then_some(CompileCacheLimits {
+                max_entries: config.compile_cache_entries as usize,
+                max_bytes: config.compile_cache_bytes,
+            }),
+            process_limits: ProcessLimits {
+                timeout: (config.compile_timeout_ms > 0).then(|| Duration::from_millis(config.compile_timeout_ms)),
+                max_memory_bytes: (config.max_memory_bytes > 0).then_some(config.max_memory_bytes),
+                max_cpu_seconds: (config.max_cpu_seconds > 0).then_some(config.max_cpu_seconds),
+            },
+            compile_options: config.compile_options.map(Into::into).unwrap_or_default(),
+            sandbox: config.sandbox.map(Into::into).unwrap_or_default(),
+            remote_retry: config.remote_retry.map(Into::into).unwrap_or_default(),
+            persistent_server: config.persistent_server,
         }
     }
 }
@@ -219,6 +620,72 @@ impl From<RuntimeConfigFfi> for RuntimeConfig {
 // Conversions: From FFI Types to Rust Types
 // ============================================================================
 
+impl From<CompileOptionsFfi> for CompileOptions {
+    fn from(options: CompileOptionsFfi) -> Self {
+        Self {
+            engine: options.engine.map(|engine| match engine {
+                ContextEngineChoiceFfi::Lmtx => ContextEngineChoice::Lmtx,
+                ContextEngineChoiceFfi::Mkiv => ContextEngineChoice::Mkiv,
+            }),
+            draft: options.draft,
+            modes: options.modes,
+            arguments: options.arguments.into_iter().collect(),
+            result: options.result,
+            runs: options.runs,
+            environments: options.environments,
+        }
+    }
+}
+
+impl From<RetryPolicyFfi> for RetryPolicy {
+    fn from(policy: RetryPolicyFfi) -> Self {
+        Self {
+            max_attempts: policy.max_attempts.max(1),
+            initial_backoff: Duration::from_millis(policy.initial_backoff_ms),
+            max_backoff: Duration::from_millis(policy.max_backoff_ms),
+            jitter_percent: policy.jitter_percent,
+        }
+    }
+}
+
+impl From<CredentialsFfi> for Credentials {
+    fn from(credentials: CredentialsFfi) -> Self {
+        match credentials {
+            CredentialsFfi::Bearer { token } => Self::Bearer(token),
+            CredentialsFfi::ApiKey { header, key } => Self::ApiKey { header, key },
+        }
+    }
+}
+
+impl From<Credentials> for CredentialsFfi {
+    fn from(credentials: Credentials) -> Self {
+        match credentials {
+            Credentials::Bearer(token) => Self::Bearer { token },
+            Credentials::ApiKey { header, key } => Self::ApiKey { header, key },
+        }
+    }
+}
+
+impl From<ClientCertificateFfi> for ClientCertificate {
+    fn from(certificate: ClientCertificateFfi) -> Self {
+        Self {
+            certificate_pem: certificate.certificate_pem.into_bytes(),
+            private_key_pem: certificate.private_key_pem.into_bytes(),
+        }
+    }
+}
+
+impl From<SandboxPolicyFfi> for SandboxPolicy {
+    fn from(policy: SandboxPolicyFfi) -> Self {
+        Self {
+            allow_shell_escape: policy.allow_shell_escape,
+            restrict_file_access: policy.restrict_file_access,
+            isolate: policy.isolate,
+            writable_paths: policy.writable_paths.into_iter().map(PathBuf::from).collect(),
+        }
+    }
+}
+
 impl From<TextRangeFfi> for std::ops::Range<usize> {
     fn from(range: TextRangeFfi) -> Self {
         range.start as usize..range.end as usize
@@ -238,6 +705,18 @@ impl Default for RuntimeConfigFfi {
             server_url: None,
             auth_token: None,
             local_executable: None,
+            output_dir: None,
+            cache_dir: None,
+            compile_cache_entries: CompileCacheLimits::default().max_entries as u32,
+            compile_cache_bytes: CompileCacheLimits::default().max_bytes,
+            compile_timeout_ms: DEFAULT_COMPILE_TIMEOUT.as_millis() as u64,
+            max_memory_bytes: 0,
+            max_cpu_seconds: 0,
+            max_concurrent_compilations: DEFAULT_MAX_CONCURRENT_JOBS as u32,
+            compile_options: None,
+            sandbox: None,
+            remote_retry: None,
+            persistent_server: false,
         }
     }
 }
@@ -269,6 +748,22 @@ impl CompileResultFfi {
                 severity: "error".to_string(),
                 message,
             }],
+            ..Default::default()
+        }
+    }
+
+    /// A failed result for callbacks, keeping the log of runs that were stopped.
+    pub fn from_error(error: ContextErrorFfi) -> Self {
+        let log = match &error {
+            ContextErrorFfi::Timeout { log, .. }
+            | ContextErrorFfi::LimitExceeded { log, .. }
+            | ContextErrorFfi::PolicyViolation { log, .. } => Some(format!("{}\n\n{}", error, log)),
+            _ => None,
+        };
+        let result = Self::error(error.to_string());
+        match log {
+            Some(log) => Self { log, ..result },
+            None => result,
         }
     }
 
@@ -278,6 +773,7 @@ impl CompileResultFfi {
             pdf_path,
             log,
             diagnostics: vec![],
+            ..Default::default()
         }
     }
 
@@ -0,0 +1,325 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+use tokio_util::sync::CancellationToken;
+
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobPriority {
+    Background,
+    Normal,
+    /// Used for every job of the document the user is looking at.
+    Foreground,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+}
+
+/// A point-in-time view of one job, for display and debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobInfo {
+    pub id: String,
+    pub uri: String,
+    pub priority: JobPriority,
+    pub state: JobState,
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type JobFn = Box<dyn FnOnce(CancellationToken) -> JobFuture + Send>;
+
+struct PendingJob {
+    id: String,
+    uri: String,
+    priority: JobPriority,
+    sequence: u64,
+    // Submitted with `submit_replacing`, so a newer such job may take its place.
+    replaceable: bool,
+    cancel: CancellationToken,
+    run: JobFn,
+}
+
+struct RunningJob {
+    uri: String,
+    priority: JobPriority,
+    cancel: CancellationToken,
+}
+
+struct QueueState {
+    pending: Vec<PendingJob>,
+    running: HashMap<String, RunningJob>,
+    max_concurrent: usize,
+    foreground: Option<String>,
+    next_sequence: u64,
+}
+
+struct Shared {
+    runtime: Handle,
+    state: Mutex<QueueState>,
+}
+
+/// Runs compilations with a bounded number of workers.
+///
+/// At most `max_concurrent` jobs run at once and never two for the same document;
+/// a job for a busy document waits until the running one finishes. Among waiting
+/// jobs the foreground document goes first, then higher priority, then older jobs.
+/// Jobs submitted with `submit_replacing`, where only the newest result matters,
+/// replace a queued job of the same document and priority submitted the same way.
+/// Jobs from `submit` are never replaced, so a caller waiting for one always gets
+/// its result.
+///
+/// Every job function is called exactly once. Jobs that are cancelled or replaced
+/// before they start are called right away with an already-cancelled token, so they
+/// can report the cancellation the same way a running job would.
+pub struct JobQueue {
+    shared: Arc<Shared>,
+}
+
+impl std::fmt::Debug for JobQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JobQueue").field("jobs", &self.snapshot()).finish()
+    }
+}
+
+impl JobQueue {
+    pub fn new(runtime: Handle, max_concurrent: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                runtime,
+                state: Mutex::new(QueueState {
+                    pending: Vec::new(),
+                    running: HashMap::new(),
+                    max_concurrent: max_concurrent.max(1),
+                    foreground: None,
+                    next_sequence: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Queues `job` under `id` and returns its cancellation token, which is also
+    /// passed to `job`. Cancelling the token has the same effect as `cancel(id)`.
+    ///
+    /// `job` is called with the queue locked and should only build its future.
+    pub fn submit<F, Fut>(&self, id: impl Into<String>, uri: &str, priority: JobPriority, job: F) -> CancellationToken
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.push(id.into(), uri, priority, false, job)
+    }
+
+    /// Like `submit`, but first replaces a queued job for `uri` of the same priority
+    /// that was also submitted with this method. The replaced job is called with a
+    /// cancelled token.
+    pub fn submit_replacing<F, Fut>(&self, id: impl Into<String>, uri: &str, priority: JobPriority, job: F) -> CancellationToken
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.push(id.into(), uri, priority, true, job)
+    }
+
+    fn push<F, Fut>(&self, id: String, uri: &str, priority: JobPriority, replaceable: bool, job: F) -> CancellationToken
+    where
+        F: FnOnce(CancellationToken) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cancel = CancellationToken::new();
+        let Ok(mut state) = self.shared.state.lock() else {
+            cancel.cancel();
+            return cancel;
+        };
+
+        let replaced = state.pending.iter()
+            .position(|job| replaceable && job.replaceable && job.uri == uri && job.priority == priority)
+            .map(|index| state.pending.remove(index));
+
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.pending.push(PendingJob {
+            id,
+            uri: uri.to_string(),
+            priority,
+            sequence,
+            replaceable,
+            cancel: cancel.clone(),
+            run: Box::new(move |cancel| Box::pin(job(cancel))),
+        });
+
+        if let Some(job) = replaced {
+            self.shared.discard(job);
+        }
+
+        self.shared.dispatch(&mut state);
+        cancel
+    }
+
+    /// Cancels a queued or running job. Returns whether the job was found.
+    pub fn cancel(&self, id: &str) -> bool {
+        let Ok(mut state) = self.shared.state.lock() else { return false };
+
+        if let Some(index) = state.pending.iter().position(|job| job.id == id) {
+            let job = state.pending.remove(index);
+            self.shared.discard(job);
+            return true;
+        }
+
+        match state.running.get(id) {
+            Some(job) => {
+                job.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every queued and running job for `uri`. Returns how many there were.
+    pub fn cancel_document(&self, uri: &str) -> usize {
+        let ids: Vec<String> = self.snapshot().into_iter()
+            .filter(|job| job.uri == uri)
+            .map(|job| job.id)
+            .collect();
+        ids.iter().filter(|id| self.cancel(id)).count()
+    }
+
+    pub fn cancel_all(&self) {
+        let Ok(mut state) = self.shared.state.lock() else { return };
+
+        for job in std::mem::take(&mut state.pending) {
+            self.shared.discard(job);
+        }
+        for job in state.running.values() {
+            job.cancel.cancel();
+        }
+    }
+
+    /// Gives every job for `uri` precedence over other queued work.
+    pub fn set_foreground(&self, uri: Option<String>) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.foreground = uri;
+        }
+    }
+
+    pub fn set_max_concurrent(&self, max_concurrent: usize) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.max_concurrent = max_concurrent.max(1);
+            self.shared.dispatch(&mut state);
+        }
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.shared.state.lock()
+            .map(|state| state.max_concurrent)
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_JOBS)
+    }
+
+    /// Running jobs first, then queued jobs in the order they will start.
+    pub fn snapshot(&self) -> Vec<JobInfo> {
+        let Ok(state) = self.shared.state.lock() else { return Vec::new() };
+
+        let mut running: Vec<JobInfo> = state.running.iter()
+            .map(|(id, job)| JobInfo {
+                id: id.clone(),
+                uri: job.uri.clone(),
+                priority: job.priority,
+                state: JobState::Running,
+            })
+            .collect();
+        running.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut pending: Vec<&PendingJob> = state.pending.iter().collect();
+        pending.sort_by_key(|job| Reverse(state.rank(job)));
+
+        running.into_iter()
+            .chain(pending.into_iter().map(|job| JobInfo {
+                id: job.id.clone(),
+                uri: job.uri.clone(),
+                priority: job.priority,
+                state: JobState::Queued,
+            }))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shared.state.lock()
+            .map(|state| state.pending.is_empty() && state.running.is_empty())
+            .unwrap_or(true)
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+impl QueueState {
+    fn rank(&self, job: &PendingJob) -> (bool, JobPriority, Reverse<u64>) {
+        let foreground = self.foreground.as_deref() == Some(job.uri.as_str());
+        (foreground, job.priority, Reverse(job.sequence))
+    }
+}
+
+// Frees the worker when its job ends, even if the job panics.
+struct WorkerSlot {
+    shared: Arc<Shared>,
+    id: String,
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.shared.state.lock() {
+            state.running.remove(&self.id);
+            self.shared.dispatch(&mut state);
+        }
+    }
+}
+
+impl Shared {
+    // Starts as many queued jobs as the limit allows. Called with the lock held.
+    fn dispatch(self: &Arc<Self>, state: &mut QueueState) {
+        while state.running.len() < state.max_concurrent {
+            let busy: HashSet<&str> = state.running.values().map(|job| job.uri.as_str()).collect();
+            let next = state.pending.iter()
+                .enumerate()
+                .filter(|(_, job)| !busy.contains(job.uri.as_str()))
+                .max_by_key(|(_, job)| state.rank(job))
+                .map(|(index, _)| index);
+
+            let Some(index) = next else { break };
+            let job = state.pending.remove(index);
+            if job.cancel.is_cancelled() {
+                self.discard(job);
+                continue;
+            }
+            state.running.insert(job.id.clone(), RunningJob {
+                uri: job.uri.clone(),
+                priority: job.priority,
+                cancel: job.cancel.clone(),
+            });
+
+            // The slot is made inside the task: a runtime that is shutting down drops
+            // the task right here, and freeing the slot takes the lock we're holding.
+            let (shared, id) = (Arc::clone(self), job.id);
+            let future = (job.run)(job.cancel);
+            self.runtime.spawn(async move {
+                let slot = WorkerSlot { shared, id };
+                future.await;
+                drop(slot);
+            });
+        }
+    }
+
+    // Runs a job that will never get a worker, so it can observe its cancellation.
+    fn discard(&self, job: PendingJob) {
+        job.cancel.cancel();
+        self.runtime.spawn((job.run)(job.cancel));
+    }
+}
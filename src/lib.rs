@@ -1,15 +1,64 @@
+// The analysis core, which also builds for WebAssembly.
 pub mod parser;
 pub mod highlight;
-pub mod runtime;
-pub mod ffi;
 pub mod diagnostic;
 pub mod lexer;
 pub mod syntax;
+pub mod analysis;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// Compiling documents, behind the default `runtime` feature.
+#[cfg(feature = "runtime")]
+pub mod runtime;
+#[cfg(feature = "runtime")]
+pub mod ffi;
+#[cfg(feature = "runtime")]
 pub mod ffi_bridge;
+#[cfg(feature = "runtime")]
 pub mod backend_traits;
+#[cfg(feature = "runtime")]
+pub mod backend_manager;
+#[cfg(feature = "runtime")]
+pub mod synctex;
+#[cfg(feature = "runtime")]
+pub mod artifact;
+#[cfg(feature = "runtime")]
+pub mod compile_cache;
+#[cfg(feature = "runtime")]
+pub mod scheduler;
+#[cfg(feature = "runtime")]
+pub mod job_queue;
+#[cfg(feature = "runtime")]
+pub mod process;
+#[cfg(feature = "runtime")]
+pub mod persistent;
+#[cfg(feature = "runtime")]
+pub mod progress;
+#[cfg(feature = "runtime")]
+pub mod discovery;
+#[cfg(feature = "runtime")]
+pub mod sandbox;
+#[cfg(feature = "runtime")]
+pub mod protocol;
+#[cfg(feature = "runtime")]
+pub mod auth;
+#[cfg(feature = "runtime")]
+pub mod delta;
+#[cfg(feature = "runtime")]
+pub mod remote_client;
+#[cfg(feature = "runtime")]
+pub mod retry;
+#[cfg(feature = "runtime")]
+pub mod offline_queue;
+#[cfg(feature = "runtime")]
+pub mod capi;
+#[cfg(feature = "server")]
+pub mod server;
 
 // pub use ffi_types::*;
 
+#[cfg(feature = "runtime")]
 uniffi::setup_scaffolding!();
 // uniffi::include_scaffolding!("context");
 
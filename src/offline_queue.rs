@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::Notify;
+
+/// Documents whose remote compilation failed because the server was unreachable,
+/// kept until they can be compiled there again.
+///
+/// Only URIs are kept: a resubmission compiles the document as it is then, so a
+/// document edited while offline is compiled once, in its newest version. Each
+/// remote compilation takes an attempt number from [`OfflineQueue::start`], so a
+/// failure that is reported after a newer attempt already succeeded isn't queued.
+#[derive(Debug, Default)]
+pub struct OfflineQueue {
+    state: Mutex<QueueState>,
+    next_attempt: AtomicU64,
+    added: Notify,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    // The attempt that failed, per document.
+    queued: HashMap<String, u64>,
+    // The newest attempt that succeeded, per document.
+    compiled: HashMap<String, u64>,
+}
+
+impl OfflineQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Numbers a remote compilation that is about to start.
+    pub fn start(&self) -> u64 {
+        self.next_attempt.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Queues `uri` after `attempt` failed, unless a newer attempt already succeeded.
+    pub fn push(&self, uri: &str, attempt: u64) {
+        let Ok(mut state) = self.state.lock() else { return };
+        if state.compiled.get(uri).is_some_and(|&compiled| compiled > attempt) {
+            return;
+        }
+        let queued = state.queued.entry(uri.to_string()).or_default();
+        *queued = (*queued).max(attempt);
+        self.added.notify_one();
+    }
+
+    /// Records that `attempt` succeeded, which settles older failed attempts.
+    pub fn compiled(&self, uri: &str, attempt: u64) {
+        let Ok(mut state) = self.state.lock() else { return };
+        let compiled = state.compiled.entry(uri.to_string()).or_default();
+        *compiled = (*compiled).max(attempt);
+        if state.queued.get(uri).is_some_and(|&queued| queued <= attempt) {
+            state.queued.remove(uri);
+        }
+    }
+
+    /// Forgets `uri`, e.g. when it is closed. Returns whether it was queued.
+    pub fn remove(&self, uri: &str) -> bool {
+        self.state.lock().is_ok_and(|mut state| {
+            state.compiled.remove(uri);
+            state.queued.remove(uri).is_some()
+        })
+    }
+
+    pub fn uris(&self) -> Vec<String> {
+        let mut uris: Vec<String> = self.state.lock()
+            .map(|state| state.queued.keys().cloned().collect())
+            .unwrap_or_default();
+        uris.sort();
+        uris
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().map_or(true, |state| state.queued.is_empty())
+    }
+
+    /// Empties the queue, returning the URIs to compile again.
+    pub fn drain(&self) -> Vec<String> {
+        self.state.lock()
+            .map(|mut state| state.queued.drain().map(|(uri, _)| uri).collect())
+            .unwrap_or_default()
+    }
+
+    /// Waits until at least one document is queued.
+    pub async fn wait_for_documents(&self) {
+        while self.is_empty() {
+            self.added.notified().await;
+        }
+    }
+}
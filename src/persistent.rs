@@ -0,0 +1,344 @@
+use std::any::Any;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::backend_traits::{
+    sleep_or_forever, BackendError, CompilationBackend, CompilationRequest, CompilationResult, LocalBackend,
+};
+use crate::sandbox::SandboxPolicy;
+use crate::process::{spawn_process_tree, ProcessLimits, ProcessTreeGuard};
+use crate::progress::{CompileEvent, LogStream, ProgressTracker};
+
+/// Jobs an engine typesets before it is replaced. Definitions a document makes
+/// globally stay in the engine, so this bounds how far they leak into later jobs.
+pub const DEFAULT_MAX_JOBS_PER_SERVER: u32 = 20;
+
+const CONTROL_SCRIPT: &str = include_str!("persistent_server.cld");
+const CONTROL_SCRIPT_NAME: &str = "context-runtime-server.cld";
+const MARKER: &str = "@@context-runtime";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A local backend that keeps a warm ConTeXt engine running between compilations.
+///
+/// The engine is started once with a ConTeXt Lua document (`persistent_server.cld`)
+/// that waits for jobs on its stdin and typesets each one in the same process, so
+/// the engine start and loading the format and fonts are paid once per engine
+/// rather than per compile. Every job is a single pass; see the control document for
+/// how jobs are kept apart. Jobs run one at a time. The engine is replaced when it
+/// crashes, when a job fails, is cancelled or times out, and after `max_jobs` jobs.
+///
+/// The running engine can't be switched, so jobs that pick one with
+/// `CompileOptions::engine` are run by a fresh `context` process instead.
+///
+/// The sandbox policy and the memory limit apply to the engine; the CPU limit does
+/// not, since CPU time would add up across jobs. Use the timeout to bound single jobs.
+#[derive(Debug)]
+pub struct PersistentBackend {
+    local: LocalBackend,
+    max_jobs: u32,
+    server: tokio::sync::Mutex<Option<Server>>,
+}
+
+#[derive(Debug)]
+struct Server {
+    // Dropping the guard kills the server and any engine it is running.
+    _process_tree: ProcessTreeGuard,
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: Arc<Mutex<String>>,
+    jobs: u32,
+}
+
+enum JobEnd {
+    Done(i32),
+    Exited,
+    Cancelled,
+    TimedOut,
+}
+
+impl PersistentBackend {
+    pub fn new(mtxrun_path_config: Option<PathBuf>) -> Result<Self, BackendError> {
+        Ok(Self::from_local(LocalBackend::new(mtxrun_path_config)?))
+    }
+
+    /// Runs jobs with the executable, working directory, output directory and
+    /// limits of `local`.
+    pub fn from_local(local: LocalBackend) -> Self {
+        Self {
+            local,
+            max_jobs: DEFAULT_MAX_JOBS_PER_SERVER,
+            server: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn with_output_dir(mut self, output_dir: Option<PathBuf>) -> Self {
+        self.local = self.local.with_output_dir(output_dir);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ProcessLimits) -> Self {
+        self.local = self.local.with_limits(limits);
+        self
+    }
+
+    /// Applies to the engine, so isolation covers every job it runs.
+    pub fn with_sandbox(mut self, sandbox: SandboxPolicy) -> Self {
+        self.local = self.local.with_sandbox(sandbox);
+        self
+    }
+
+    pub fn with_max_jobs(mut self, max_jobs: u32) -> Self {
+        self.max_jobs = max_jobs.max(1);
+        self
+    }
+
+    pub fn mtxrun_path(&self) -> &Path {
+        self.local.mtxrun_path()
+    }
+
+    /// Stops the engine. The next compilation starts a new one.
+    pub async fn shutdown(&self) {
+        if let Some(mut server) = self.server.lock().await.take() {
+            server.quit().await;
+        }
+    }
+
+    async fn start_server(&self) -> Result<Server, BackendError> {
+        let working_dir = self.local.working_dir();
+        let script = working_dir.join(CONTROL_SCRIPT_NAME);
+        tokio::fs::write(&script, CONTROL_SCRIPT)
+            .await
+            .map_err(|e| BackendError::IO(e.to_string()))?;
+
+        let mut command = self.local.mtxrun_command(working_dir).await?;
+        command
+            .args(["--script", "context", "--once", "--nonstopmode"])
+            .args(self.local.sandbox().context_arguments())
+            .arg(&script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let limits = ProcessLimits { max_cpu_seconds: None, ..*self.local.limits() };
+        let (mut child, process_tree) = spawn_process_tree(&mut command, &limits)
+            .map_err(|e| BackendError::Unavailable(format!("Failed to start ConTeXt server: {}", e)))?;
+
+        let (Some(stdin), Some(stdout), Some(stderr)) = (child.stdin.take(), child.stdout.take(), child.stderr.take()) else {
+            return Err(BackendError::Setup("ConTeXt server pipes are missing".into()));
+        };
+
+        // Stderr is collected in the background and attached to the next job's log.
+        let stderr_log = Arc::new(Mutex::new(String::new()));
+        let sink = Arc::clone(&stderr_log);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Ok(mut log) = sink.lock() {
+                    log.push_str(&line);
+                    log.push('\n');
+                }
+            }
+        });
+
+        let mut server = Server {
+            _process_tree: process_tree,
+            _child: child,
+            stdin,
+            stdout: BufReader::new(stdout),
+            stderr: stderr_log,
+            jobs: 0,
+        };
+
+        match tokio::time::timeout(STARTUP_TIMEOUT, server.wait_ready()).await {
+            Ok(true) => Ok(server),
+            Ok(false) => Err(BackendError::Unavailable(format!(
+                "ConTeXt server exited during startup: {}",
+                server.take_stderr()
+            ))),
+            Err(_) => Err(BackendError::Unavailable("ConTeXt server did not start in time".into())),
+        }
+    }
+}
+
+impl Server {
+    async fn read_line(&mut self) -> Option<String> {
+        let mut buffer = Vec::new();
+        match self.stdout.read_until(b'\n', &mut buffer).await {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(String::from_utf8_lossy(&buffer).into_owned()),
+        }
+    }
+
+    // Skips the engine's startup output up to the ready marker. Returns false if the
+    // server exits.
+    async fn wait_ready(&mut self) -> bool {
+        while let Some(line) = self.read_line().await {
+            if line.trim_end() == format!("{} ready", MARKER) {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn run_job(
+        &mut self,
+        dir: &Path,
+        jobname: &str,
+        file_name: &str,
+        options: &[String],
+        log: &mut String,
+        events: Option<&UnboundedSender<CompileEvent>>,
+    ) -> Option<i32> {
+        // A fresh nonce per job, so output that imitates the done line doesn't end it.
+        let nonce = uuid::Uuid::new_v4().simple().to_string();
+        // Options are sent as mtx-context flags without the leading dashes.
+        let mut command = format!("compile\t{}\t{}\t{}\t{}", nonce, dir.display(), jobname, file_name);
+        for option in options {
+            command.push('\t');
+            command.push_str(option.trim_start_matches('-'));
+        }
+        command.push('\n');
+        self.stdin.write_all(command.as_bytes()).await.ok()?;
+        self.stdin.flush().await.ok()?;
+
+        let done = format!("{} done {} ", MARKER, nonce);
+        let mut tracker = ProgressTracker::new();
+        loop {
+            let line = self.read_line().await?;
+            if let Some(code) = line.trim_end().strip_prefix(&done) {
+                return Some(code.trim().parse().unwrap_or(1));
+            }
+
+            if let Some(events) = events {
+                if let Some(progress) = tracker.feed_line(&line) {
+                    let _ = events.send(CompileEvent::Progress(progress));
+                }
+                let _ = events.send(CompileEvent::Log { stream: LogStream::Stdout, chunk: line.clone() });
+            }
+            log.push_str(&line);
+        }
+    }
+
+    fn take_stderr(&self) -> String {
+        self.stderr.lock()
+            .map(|mut log| std::mem::take(&mut *log))
+            .unwrap_or_default()
+    }
+
+    async fn quit(&mut self) {
+        let _ = self.stdin.write_all(b"quit\n").await;
+        let _ = self.stdin.flush().await;
+    }
+}
+
+#[async_trait]
+impl CompilationBackend for PersistentBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        if request.cancel.is_cancelled() {
+            return Err(BackendError::Cancelled);
+        }
+
+        let mut slot = tokio::select! {
+            slot = self.server.lock() => slot,
+            _ = request.cancel.cancelled() => return Err(BackendError::Cancelled),
+        };
+
+        if request.options.engine.is_some() {
+            return self.local.compile(request).await;
+        }
+
+        let options = request.options.context_arguments()?;
+        let source_file = self.local.prepare_job(&request).await?;
+        let (Some(dir), Some(jobname), Some(file_name)) = (
+            source_file.parent(),
+            source_file.file_stem().and_then(|s| s.to_str()),
+            source_file.file_name().and_then(|s| s.to_str()),
+        ) else {
+            return Err(BackendError::IO("Failed to get temp file name".into()));
+        };
+
+        if slot.is_none() {
+            *slot = Some(tokio::select! {
+                server = self.start_server() => server?,
+                _ = request.cancel.cancelled() => return Err(BackendError::Cancelled),
+            });
+        }
+        let Some(server) = slot.as_mut() else {
+            return Err(BackendError::Unavailable("ConTeXt server is not running".into()));
+        };
+
+        let mut log = String::new();
+        let timeout = self.local.limits().timeout;
+        let end = tokio::select! {
+            code = server.run_job(dir, jobname, file_name, &options, &mut log, request.events.as_ref()) => {
+                code.map_or(JobEnd::Exited, JobEnd::Done)
+            }
+            _ = request.cancel.cancelled() => JobEnd::Cancelled,
+            _ = sleep_or_forever(timeout) => JobEnd::TimedOut,
+        };
+
+        let full_log = format!("{}\n\nSTDERR:\n{}", log, server.take_stderr());
+        let code = match end {
+            JobEnd::Done(code) => code,
+            // The server can't be interrupted mid-job, so it is replaced.
+            JobEnd::Cancelled => {
+                *slot = None;
+                return Err(BackendError::Cancelled);
+            }
+            JobEnd::TimedOut => {
+                *slot = None;
+                return Err(BackendError::Timeout { timeout: timeout.unwrap_or_default(), log: full_log });
+            }
+            JobEnd::Exited => {
+                *slot = None;
+                return Err(BackendError::Compilation(format!(
+                    "ConTeXt server exited unexpectedly and will be restarted for the next job\n\n{}",
+                    full_log
+                )));
+            }
+        };
+
+        // The job may have left state behind in the engine, so it is replaced.
+        if let Some(violation) = self.local.sandbox().find_violation(&full_log) {
+            *slot = None;
+            return Err(BackendError::PolicyViolation { violation, log: full_log });
+        }
+
+        server.jobs += 1;
+        if code != 0 || server.jobs >= self.max_jobs {
+            server.quit().await;
+            *slot = None;
+        }
+
+        // Still holding the slot, so the next job can't overwrite this one's output.
+        let mut result = self.local.process_output(code == 0, full_log, &source_file, &request.options).await?;
+        result.backend = self.name().to_string();
+        Ok(result)
+    }
+
+    fn fingerprint(&self) -> String {
+        format!("persistent:{}", self.local.fingerprint())
+    }
+
+    fn name(&self) -> &str {
+        "persistent"
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        self.local.health_check().await
+    }
+
+    async fn release(&self, job_id: &str) {
+        self.local.release(job_id).await
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+pub const DEFAULT_COMPILE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Bounds for a single ConTeXt run.
+///
+/// The timeout is wall-clock and applies everywhere. Memory and CPU limits are
+/// set as rlimits on Linux and ignored elsewhere; they are inherited by the TeX
+/// engine that `mtxrun` starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessLimits {
+    pub timeout: Option<Duration>,
+    /// Maximum address space per process, in bytes.
+    pub max_memory_bytes: Option<u64>,
+    pub max_cpu_seconds: Option<u64>,
+}
+
+impl Default for ProcessLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Some(DEFAULT_COMPILE_TIMEOUT),
+            max_memory_bytes: None,
+            max_cpu_seconds: None,
+        }
+    }
+}
+
+impl ProcessLimits {
+    pub fn unlimited() -> Self {
+        Self { timeout: None, max_memory_bytes: None, max_cpu_seconds: None }
+    }
+
+    /// Works out which limit killed a process from how it ended.
+    pub fn exceeded_by(&self, status: &std::process::ExitStatus, log: &str) -> Option<ResourceLimit> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            // The soft CPU limit sends SIGXCPU, which ends the process unless it is
+            // handled. A SIGKILL may be the hard limit but also the OOM killer or
+            // someone else, so it isn't blamed on the CPU limit.
+            if self.max_cpu_seconds.is_some() && status.signal() == Some(libc::SIGXCPU) {
+                return Some(ResourceLimit::Cpu);
+            }
+        }
+
+        // LuaTeX reports a failed allocation in the log and exits with an error.
+        let lowercase = log.to_lowercase();
+        if self.max_memory_bytes.is_some()
+            && !status.success()
+            && ["not enough memory", "out of memory", "memory exhausted", "cannot allocate memory"]
+                .iter()
+                .any(|needle| lowercase.contains(needle))
+        {
+            return Some(ResourceLimit::Memory);
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceLimit {
+    Memory,
+    Cpu,
+}
+
+impl fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceLimit::Memory => write!(f, "memory"),
+            ResourceLimit::Cpu => write!(f, "CPU time"),
+        }
+    }
+}
+
+/// Kills the whole process tree of a child when dropped.
+///
+/// `mtxrun` starts the TeX engine as a grandchild, so killing only the direct child
+/// (what `kill_on_drop` does) would leave the engine running. On Unix the child is
+/// started as the leader of its own process group and the group is killed instead.
+#[derive(Debug)]
+pub struct ProcessTreeGuard {
+    pid: Option<u32>,
+}
+
+impl ProcessTreeGuard {
+    pub fn kill(&mut self) {
+        if let Some(pid) = self.pid.take() {
+            kill_process_tree(pid);
+        }
+    }
+}
+
+impl Drop for ProcessTreeGuard {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Spawns `command` in a new process group, returning a guard that kills the group.
+pub fn spawn_process_tree(command: &mut Command, limits: &ProcessLimits) -> std::io::Result<(Child, ProcessTreeGuard)> {
+    #[cfg(unix)]
+    command.process_group(0);
+    command.kill_on_drop(true);
+    apply_rlimits(command, limits);
+
+    let child = command.spawn()?;
+    let guard = ProcessTreeGuard { pid: child.id() };
+    Ok((child, guard))
+}
+
+#[cfg(unix)]
+pub fn kill_process_tree(pid: u32) {
+    // A negative pid addresses the process group led by `pid`.
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn kill_process_tree(_pid: u32) {
+    // Without process groups we rely on `kill_on_drop` for the direct child.
+}
+
+#[cfg(target_os = "linux")]
+fn apply_rlimits(command: &mut Command, limits: &ProcessLimits) {
+    let memory = limits.max_memory_bytes;
+    let cpu = limits.max_cpu_seconds;
+    if memory.is_none() && cpu.is_none() {
+        return;
+    }
+
+    fn rlimit(soft: u64, hard: u64) -> libc::rlimit {
+        libc::rlimit { rlim_cur: soft as libc::rlim_t, rlim_max: hard as libc::rlim_t }
+    }
+
+    // SAFETY: the closure runs between fork and exec and only calls setrlimit,
+    // which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(bytes) = memory
+                && libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes, bytes)) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            // One second of grace between SIGXCPU and SIGKILL.
+            if let Some(seconds) = cpu
+                && libc::setrlimit(libc::RLIMIT_CPU, &rlimit(seconds, seconds + 1)) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_rlimits(_command: &mut Command, _limits: &ProcessLimits) {
+    // Only the wall-clock timeout is enforced on other platforms.
+}
@@ -0,0 +1,79 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+use regex::Regex;
+
+/// Something that happened during a running compilation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileEvent {
+    Progress(CompileProgress),
+    /// A line of output, including its trailing newline.
+    Log { stream: LogStream, chunk: String },
+    /// A remote request failed for a transient reason and is sent again after `delay`.
+    Retrying { attempt: u32, delay: Duration, reason: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CompileProgress {
+    /// The TeX run `mtxrun` is on, starting at 1. ConTeXt reruns until references settle.
+    pub pass: u32,
+    /// Pages shipped out in the current pass.
+    pub pages: u32,
+    /// The source file TeX is reading, if it reported one.
+    pub current_file: Option<String>,
+}
+
+// mtx-context     | run 1: luatex --fmt=...
+static RUN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^mtx-context\s+\|\s+run (\d+):").unwrap());
+// pages           > flushing realpage 3, userpage 3, subpage 3
+static PAGE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^pages\s+>\s+flushing realpage (\d+)").unwrap());
+// open source     > level 2, order 3, name 'chapter.tex'
+static SOURCE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(open|close) source\s+>\s+level \d+, order \d+, name '([^']*)'").unwrap());
+
+/// Follows ConTeXt's log line by line and reports when the progress changes.
+#[derive(Debug, Default)]
+pub struct ProgressTracker {
+    progress: CompileProgress,
+    open_files: Vec<String>,
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn progress(&self) -> &CompileProgress {
+        &self.progress
+    }
+
+    /// Returns the new progress if `line` changed it.
+    pub fn feed_line(&mut self, line: &str) -> Option<CompileProgress> {
+        let line = line.trim_end();
+
+        if let Some(caps) = RUN_RE.captures(line) {
+            self.progress.pass = caps[1].parse().unwrap_or(self.progress.pass + 1);
+            self.progress.pages = 0;
+            self.progress.current_file = None;
+            self.open_files.clear();
+        } else if let Some(caps) = PAGE_RE.captures(line) {
+            self.progress.pages = caps[1].parse().unwrap_or(self.progress.pages + 1);
+        } else if let Some(caps) = SOURCE_RE.captures(line) {
+            if &caps[1] == "open" {
+                self.open_files.push(caps[2].to_string());
+            } else if let Some(index) = self.open_files.iter().rposition(|f| f == &caps[2]) {
+                self.open_files.truncate(index);
+            }
+            self.progress.current_file = self.open_files.last().cloned();
+        } else {
+            return None;
+        }
+
+        Some(self.progress.clone())
+    }
+}
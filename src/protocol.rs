@@ -0,0 +1,336 @@
+//! The remote compile protocol spoken by `RemoteClient` and `context-server`.
+//!
+//! All bodies are JSON. Every request carries [`VERSION_HEADER`] with the client's
+//! [`PROTOCOL_VERSION`], and servers answer with their own. A request without the
+//! header is treated as version 1, which is what clients sent before the header existed.
+//!
+//! Clients also send [`CLIENT_HEADER`] with an id of their own. Servers keep document
+//! versions and replace queued compilations per client and `uri`, so two clients
+//! compiling a `main.tex` of their own don't interfere. Requests without it share
+//! one anonymous client.
+//!
+//! | Endpoint                  | Auth   | Body                 | Response                   |
+//! |---------------------------|--------|----------------------|----------------------------|
+//! | `GET /capabilities`       | none   |                      | [`Capabilities`]           |
+//! | `GET /health`             | none   |                      | [`HealthResponse`]         |
+//! | `POST /compile`           | bearer | [`CompileRequest`]   | [`CompileResponse`]        |
+//! | `GET /artifacts/{hash}`   | bearer |                      | the PDF                    |
+//! | `POST /jobs`              | bearer | [`CompileRequest`]   | `202` with a [`JobStatus`] |
+//! | `GET /jobs/{id}`          | bearer |                      | [`JobStatus`]              |
+//! | `GET /jobs/{id}/events`   | bearer |                      | `text/event-stream`        |
+//! | `DELETE /jobs/{id}`       | bearer |                      | [`JobStatus`]              |
+//!
+//! A document that fails to compile is still a `200` with `success: false`. Any other
+//! status comes with an [`ErrorBody`]; `5xx` means the server can't compile right now
+//! and clients may fall back to another backend.
+//!
+//! `/compile` holds the request open until the PDF is done. Servers with
+//! [`Feature::Jobs`] also take compilations as jobs, which suits long documents:
+//! submit, then follow the job's `status` events (each one a [`JobStatus`], the last
+//! one finished) or poll it, and fetch `result.output_url`. Deleting a job cancels it.
+//!
+//! Servers with [`Feature::Deltas`] remember the last [`DocumentVersion`] of every
+//! document sent with a `version`. Later requests may then carry only the edits and
+//! changed files since that `base`. A server that no longer has the base, or whose
+//! rebuilt document doesn't match `version.hash`, answers `412` with
+//! [`ErrorCode::BaseMismatch`], and the client sends the document in full instead.
+
+use serde::{Deserialize, Serialize};
+use crate::backend_traits::{CompilationError, CompilationResult, CompileOptions, ContextEngineChoice, ProjectFile};
+use crate::artifact::CompilationArtifact;
+use crate::progress::CompileProgress;
+
+/// The version this build speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// The oldest version this build still understands.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const VERSION_HEADER: &str = "x-context-protocol";
+/// A random id each client picks for itself, see the module docs.
+pub const CLIENT_HEADER: &str = "x-context-client";
+
+/// Whether a peer speaking `version` can be talked to.
+pub fn is_supported_version(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+/// `POST /compile`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompileRequest {
+    /// Identifies the document, so the server can replace its queued compilations.
+    pub uri: String,
+    /// Empty when `base` is set.
+    #[serde(default)]
+    pub content: String,
+    /// Files included by the main source, by path relative to it.
+    #[serde(default)]
+    pub files: Vec<ProjectFile>,
+    /// Only `"pdf"` is defined.
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Requires the [`Feature::Options`] capability unless it is the default.
+    #[serde(default)]
+    pub options: CompileOptions,
+    /// The version this request compiles, for servers with [`Feature::Deltas`] to
+    /// remember as the base of later requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<DocumentVersion>,
+    /// The version `delta` and `unchanged_files` refer to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<DocumentVersion>,
+    /// Edits turning the base's content into this version's, in ascending order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delta: Vec<TextEdit>,
+    /// Paths of the base's files that haven't changed; `files` then holds only new
+    /// and changed files, and base files in neither list are deleted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unchanged_files: Vec<String>,
+}
+
+impl Default for CompileRequest {
+    fn default() -> Self {
+        Self {
+            uri: String::new(),
+            content: String::new(),
+            files: Vec::new(),
+            format: default_format(),
+            options: CompileOptions::default(),
+            version: None,
+            base: None,
+            delta: Vec::new(),
+            unchanged_files: Vec::new(),
+        }
+    }
+}
+
+fn default_format() -> String {
+    "pdf".to_string()
+}
+
+/// A document as the client numbered it, with its [`document_hash`](crate::delta::document_hash).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DocumentVersion {
+    pub version: u64,
+    pub hash: String,
+}
+
+/// Replaces the bytes `start..end` of the base content with `text`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompileResponse {
+    pub success: bool,
+    pub log: String,
+    /// Where to download the PDF, absolute or relative to the server's base URL.
+    pub output_url: Option<String>,
+    /// SHA-256 of the PDF, so clients can skip downloads they already have.
+    #[serde(default)]
+    pub output_hash: Option<String>,
+    #[serde(default)]
+    pub diagnostics: Vec<RemoteDiagnostic>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteDiagnostic {
+    pub message: String,
+    /// `"error"` or `"warning"`.
+    pub severity: String,
+    pub range: Option<RemoteRange>,
+}
+
+/// A source position; `start` is the line and `end` the column.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl CompileResponse {
+    /// The result as a backend reports it, with the already downloaded PDF.
+    pub fn into_result(self, artifact: Option<CompilationArtifact>, backend: &str) -> CompilationResult {
+        let (errors, warnings): (Vec<RemoteDiagnostic>, Vec<RemoteDiagnostic>) = self.diagnostics.into_iter()
+            .filter(|d| d.severity == "error" || d.severity == "warning")
+            .partition(|d| d.severity == "error");
+        let to_error = |d: RemoteDiagnostic| CompilationError {
+            line: d.range.as_ref().map_or(0, |r| r.start),
+            column: d.range.as_ref().map_or(0, |r| r.end),
+            message: d.message,
+        };
+
+        CompilationResult {
+            success: self.success,
+            artifact,
+            log: self.log,
+            errors: errors.into_iter().map(to_error).collect(),
+            warnings: warnings.into_iter().map(to_error).collect(),
+            synctex: None,
+            backend: backend.to_string(),
+        }
+    }
+}
+
+impl RemoteDiagnostic {
+    pub fn from_error(error: CompilationError, severity: &str) -> Self {
+        Self {
+            message: error.message,
+            severity: severity.to_string(),
+            range: Some(RemoteRange { start: error.line, end: error.column }),
+        }
+    }
+}
+
+/// The body of every non-`200` response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+    #[serde(default)]
+    pub code: ErrorCode,
+}
+
+impl ErrorBody {
+    pub fn new(code: ErrorCode, error: impl Into<String>) -> Self {
+        Self { error: error.into(), code }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    Unauthorized,
+    /// The client's protocol version is outside the server's supported range.
+    UnsupportedVersion,
+    /// Malformed body or unknown format.
+    BadRequest,
+    /// `CompileRequest::options` can't be applied, e.g. a result name with a path.
+    InvalidOptions,
+    /// A newer request for the same document replaced this one.
+    Superseded,
+    /// The server can't rebuild the document from `CompileRequest::base`; send it in full.
+    BaseMismatch,
+    NotFound,
+    /// The server can't compile right now; try again or use another backend.
+    Unavailable,
+    #[default]
+    Internal,
+}
+
+/// Optional parts of the protocol a server implements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// `CompileRequest::options` is honoured.
+    Options,
+    /// `CompileRequest::files` is honoured.
+    ProjectFiles,
+    /// `output_hash` is reported for every PDF.
+    ContentHashes,
+    /// The `/jobs` endpoints.
+    Jobs,
+    /// `CompileRequest::base`, `delta` and `unchanged_files` are honoured.
+    Deltas,
+}
+
+/// `GET /capabilities`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub protocol_version: u32,
+    pub min_protocol_version: u32,
+    /// e.g. `context-server 0.1.0`.
+    #[serde(default)]
+    pub server: String,
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// Engines the server's installation can run.
+    #[serde(default)]
+    pub engines: Vec<ContextEngineChoice>,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+}
+
+impl Capabilities {
+    /// What this build's server offers.
+    pub fn current(server: impl Into<String>, engines: Vec<ContextEngineChoice>) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            server: server.into(),
+            formats: vec![default_format()],
+            engines,
+            features: vec![Feature::Options, Feature::ProjectFiles, Feature::ContentHashes, Feature::Jobs, Feature::Deltas],
+        }
+    }
+
+    /// Assumed for servers without `/capabilities`, which predate versioning.
+    pub fn legacy() -> Self {
+        Self {
+            protocol_version: 1,
+            min_protocol_version: 1,
+            server: String::new(),
+            formats: vec![default_format()],
+            engines: Vec::new(),
+            features: Vec::new(),
+        }
+    }
+
+    /// The highest version both sides speak, if any.
+    pub fn negotiate(&self) -> Option<u32> {
+        let version = self.protocol_version.min(PROTOCOL_VERSION);
+        (version >= self.min_protocol_version && version >= MIN_PROTOCOL_VERSION).then_some(version)
+    }
+
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+/// `GET /health`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthResponse {
+    /// `"ok"`, or `"unavailable"` with a `503`.
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    /// `result` is set, whether or not the document compiled.
+    Finished,
+    /// The server couldn't compile; `error` says why.
+    Failed,
+    /// Deleted, or replaced by a newer job for the same document.
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Finished | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A compile job as `/jobs` reports it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub state: JobState,
+    /// The latest progress of a running job.
+    #[serde(default)]
+    pub progress: Option<CompileProgress>,
+    #[serde(default)]
+    pub result: Option<CompileResponse>,
+    #[serde(default)]
+    pub error: Option<ErrorBody>,
+}
+
+impl JobStatus {
+    pub fn queued(job_id: impl Into<String>) -> Self {
+        Self { job_id: job_id.into(), state: JobState::Queued, progress: None, result: None, error: None }
+    }
+}
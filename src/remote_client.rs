@@ -0,0 +1,646 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::{Client, Identity, RequestBuilder, Response, StatusCode};
+use tokio::sync::{mpsc, OnceCell};
+use tokio_util::sync::CancellationToken;
+use crate::artifact::{ArtifactCache, CompilationArtifact};
+use crate::auth::{AuthProvider, Credentials, StaticCredentials};
+use crate::backend_traits::{BackendError, CompileOptions};
+use crate::delta::UploadTracker;
+use crate::progress::{CompileEvent, CompileProgress};
+use crate::retry::RetryPolicy;
+use crate::protocol::{
+    Capabilities, CompileRequest, CompileResponse, ErrorBody, ErrorCode, Feature, JobState, JobStatus,
+    CLIENT_HEADER, PROTOCOL_VERSION, VERSION_HEADER,
+};
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Servers send a keep-alive comment every 15 seconds, so an event stream that stays
+/// silent for three of them is given up on and the job is polled instead.
+pub const DEFAULT_EVENTS_IDLE_TIMEOUT: Duration = Duration::from_secs(45);
+
+/// A compile server's answer, with the PDF already downloaded into the artifact cache.
+#[derive(Debug)]
+pub struct RemoteCompilation {
+    pub response: CompileResponse,
+    pub artifact: Option<CompilationArtifact>,
+}
+
+/// The one implementation of the client side of the [`protocol`](crate::protocol).
+///
+/// Sends the version and client headers and the `AuthProvider`'s credentials with
+/// every request, checks the server's capabilities once before the first compile, and
+/// turns error bodies into `BackendError`s: `5xx` becomes `Unavailable` so callers can
+/// fall back.
+/// A `401` asks the provider to refresh its credentials, once per request.
+///
+/// Compiles as a job when the server supports it, following the job's events or
+/// polling it when the server can't stream, and deleting it when cancelled.
+///
+/// Servers with `Feature::Deltas` get only what changed since the version of the
+/// document they last accepted from this client, and the full document when they no
+/// longer have that version.
+///
+/// Requests that fail for a transient reason (connection errors, timeouts, `408`,
+/// `429` and `5xx` other than `501`) are sent again as the `RetryPolicy` allows.
+/// Compile requests and job submissions start work on the server, so after a network
+/// error they are only sent again when they never reached it.
+pub struct RemoteClient {
+    endpoint: String,
+    auth: Arc<dyn AuthProvider>,
+    http: Client,
+    cache: ArtifactCache,
+    timeout: Option<Duration>,
+    poll_interval: Duration,
+    events_idle_timeout: Duration,
+    retry: RetryPolicy,
+    capabilities: OnceCell<Capabilities>,
+    uploads: Arc<UploadTracker>,
+}
+
+impl RemoteClient {
+    pub fn new(endpoint: String, auth_token: Option<String>) -> Self {
+        Self {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            auth: Arc::new(StaticCredentials::bearer(auth_token)),
+            http: Client::new(),
+            cache: ArtifactCache::new(ArtifactCache::default_dir()),
+            timeout: None,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            events_idle_timeout: DEFAULT_EVENTS_IDLE_TIMEOUT,
+            retry: RetryPolicy::default(),
+            capabilities: OnceCell::new(),
+            uploads: Arc::new(UploadTracker::new()),
+        }
+    }
+
+    pub fn with_cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        if let Some(dir) = cache_dir {
+            self.cache = ArtifactCache::new(dir);
+        }
+        self
+    }
+
+    /// Limits the short requests: capabilities, job submissions, status polls and
+    /// cancellations, and artifact downloads. Compiles on servers without jobs and job
+    /// event streams last as long as the compile, so they aren't limited.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Replaces the token given to `new`. Fails if the provider's client certificate
+    /// can't be used.
+    pub fn with_auth(mut self, auth: Arc<dyn AuthProvider>) -> Result<Self, BackendError> {
+        if let Some(certificate) = auth.client_certificate() {
+            let identity = Identity::from_pkcs8_pem(&certificate.certificate_pem, &certificate.private_key_pem)
+                .map_err(|e| BackendError::Setup(format!("Invalid client certificate: {}", e)))?;
+            self.http = Client::builder().identity(identity).build()
+                .map_err(|e| BackendError::Setup(e.to_string()))?;
+        }
+        self.auth = auth;
+        Ok(self)
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Shares what servers acknowledged with other clients, so deltas keep working
+    /// when a client is rebuilt, e.g. for new credentials.
+    pub fn with_uploads(mut self, uploads: Arc<UploadTracker>) -> Self {
+        self.uploads = uploads;
+        self
+    }
+
+    /// How often to ask for a job's status when the server can't stream events.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// How long a job's event stream may go without data, keep-alives included,
+    /// before the job is polled instead.
+    pub fn with_events_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.events_idle_timeout = timeout;
+        self
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Servers may report the output as an absolute URL or a path relative to the endpoint.
+    pub fn resolve_url(&self, output: &str) -> String {
+        if output.starts_with("http://") || output.starts_with("https://") {
+            output.to_string()
+        } else {
+            format!("{}/{}", self.endpoint, output.trim_start_matches('/'))
+        }
+    }
+
+    /// The server's capabilities, fetched once. Servers without `/capabilities` are
+    /// assumed to speak version 1 with no optional features; they still take `files`,
+    /// which predates versioning.
+    pub async fn capabilities(&self) -> Result<&Capabilities, BackendError> {
+        self.capabilities.get_or_try_init(|| async {
+            let response = self.send(self.timed(self.request(self.http.get(self.url("capabilities")))), None).await?;
+            if matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+                return Ok(Capabilities::legacy());
+            }
+            let response = check_status(response).await?;
+            response.json::<Capabilities>().await
+                .map_err(|e| BackendError::Network(format!("Invalid capabilities: {}", e)))
+        })
+        .await
+    }
+
+    /// Compiles on the server and downloads the PDF, unless its hash is already cached.
+    /// Progress of job-based compiles is sent to `events`.
+    pub async fn compile(
+        &self,
+        request: &CompileRequest,
+        cancel: &CancellationToken,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<RemoteCompilation, BackendError> {
+        let capabilities = tokio::select! {
+            capabilities = self.check_compatible(request) => capabilities?,
+            _ = cancel.cancelled() => return Err(BackendError::Cancelled),
+        };
+
+        let deltas = capabilities.supports(Feature::Deltas);
+        let response = if capabilities.supports(Feature::Jobs) {
+            self.compile_job(request, deltas, cancel, events).await?
+        } else {
+            // Dropping the in-flight request future aborts the HTTP request.
+            tokio::select! {
+                response = self.post_compile(request, deltas, events) => response?,
+                _ = cancel.cancelled() => return Err(BackendError::Cancelled),
+            }
+        };
+
+        let artifact = match response.output_url.as_deref().map(|output| self.resolve_url(output)) {
+            Some(url) => tokio::select! {
+                artifact = self.download(&url, response.output_hash.as_deref(), events) => Some(artifact?),
+                _ = cancel.cancelled() => return Err(BackendError::Cancelled),
+            },
+            None => None,
+        };
+        Ok(RemoteCompilation { response, artifact })
+    }
+
+    async fn post_compile(
+        &self,
+        request: &CompileRequest,
+        deltas: bool,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<CompileResponse, BackendError> {
+        let response = self.upload("compile", request, deltas, events).await?;
+        response.json().await
+            .map_err(|e| BackendError::Network(format!("Invalid compile response: {}", e)))
+    }
+
+    // Posts `request` to `path`, as a delta if the server takes them, and again in full
+    // if the server doesn't have the delta's base.
+    async fn upload(
+        &self,
+        path: &str,
+        request: &CompileRequest,
+        deltas: bool,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<Response, BackendError> {
+        // A job submission is answered right away; a compile takes as long as it takes.
+        let post = |body: &CompileRequest| {
+            let builder = self.request(self.http.post(self.url(path)).json(body));
+            if path == "jobs" { self.timed(builder) } else { builder }
+        };
+        if !deltas {
+            return check_status(self.submit(post(request), events).await?).await;
+        }
+
+        let prepared = self.uploads.prepare(request);
+        let mut response = self.submit(post(&prepared.request), events).await?;
+        if prepared.is_delta() && response.status() == StatusCode::PRECONDITION_FAILED {
+            log::info!("Server lacks the base of {}, uploading it in full", request.uri);
+            self.uploads.forget(&request.uri);
+            response = self.submit(post(&UploadTracker::full(request, &prepared.upload)), events).await?;
+        }
+        let response = check_status(response).await?;
+        self.uploads.acknowledge(&request.uri, prepared.upload);
+        Ok(response)
+    }
+
+    async fn compile_job(
+        &self,
+        request: &CompileRequest,
+        deltas: bool,
+        cancel: &CancellationToken,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<CompileResponse, BackendError> {
+        let submitted = tokio::select! {
+            status = self.submit_job(request, deltas, events) => status?,
+            _ = cancel.cancelled() => return Err(BackendError::Cancelled),
+        };
+        let job_id = submitted.job_id.clone();
+
+        let status = tokio::select! {
+            status = self.wait_for_job(submitted, ProgressReporter::new(events)) => status?,
+            _ = cancel.cancelled() => {
+                // Stop the run on the server without waiting for it to confirm.
+                let credentials = self.auth.credentials().await.ok().flatten();
+                let delete = self.timed(self.request(self.http.delete(self.url(&format!("jobs/{}", job_id)))));
+                let delete = authorize(delete, credentials.as_ref());
+                tokio::spawn(async move { let _ = delete.send().await; });
+                return Err(BackendError::Cancelled);
+            }
+        };
+
+        match status.state {
+            JobState::Finished => status.result
+                .ok_or_else(|| BackendError::Network(format!("Job {} finished without a result", job_id))),
+            JobState::Cancelled => Err(BackendError::Cancelled),
+            _ => {
+                let body = status.error.unwrap_or_else(|| ErrorBody::new(ErrorCode::Internal, ""));
+                let message = format!("Job {} failed: {}", job_id, body.error);
+                // A failed job is the server's fault, like a `5xx` for `/compile`.
+                Err(match body.code {
+                    ErrorCode::Internal => BackendError::Unavailable(message),
+                    _ => backend_error(body, message),
+                })
+            }
+        }
+    }
+
+    async fn submit_job(
+        &self,
+        request: &CompileRequest,
+        deltas: bool,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<JobStatus, BackendError> {
+        let response = self.upload("jobs", request, deltas, events).await?;
+        response.json().await
+            .map_err(|e| BackendError::Network(format!("Invalid job status: {}", e)))
+    }
+
+    // Follows the job's events until it ends, polling instead if they aren't available.
+    async fn wait_for_job(&self, status: JobStatus, mut progress: ProgressReporter<'_>) -> Result<JobStatus, BackendError> {
+        progress.report(&status);
+        if status.state.is_terminal() {
+            return Ok(status);
+        }
+        if let Some(status) = self.follow_events(&status.job_id, &mut progress).await {
+            return Ok(status);
+        }
+
+        loop {
+            tokio::time::sleep(self.poll_interval).await;
+            let builder = self.timed(self.request(self.http.get(self.url(&format!("jobs/{}", status.job_id)))));
+            let response = check_status(self.send(builder, progress.events).await?).await?;
+            let current: JobStatus = response.json().await
+                .map_err(|e| BackendError::Network(format!("Invalid job status: {}", e)))?;
+            progress.report(&current);
+            if current.state.is_terminal() {
+                return Ok(current);
+            }
+        }
+    }
+
+    // The job's final status, or `None` if the stream failed, went silent or ended
+    // before the job did. The stream lasts as long as the job, so only silence ends it.
+    async fn follow_events(&self, job_id: &str, progress: &mut ProgressReporter<'_>) -> Option<JobStatus> {
+        let builder = self.request(self.http.get(self.url(&format!("jobs/{}/events", job_id))))
+            .header(reqwest::header::ACCEPT, "text/event-stream");
+        let mut response = self.send_once(builder).await.ok().filter(|response| response.status().is_success())?;
+
+        let mut buffer = Vec::new();
+        while let Ok(Ok(Some(chunk))) = tokio::time::timeout(self.events_idle_timeout, response.chunk()).await {
+            // Events end with a blank line; dropping `\r` turns `\r\n` line ends into `\n`.
+            buffer.extend(chunk.iter().filter(|&&byte| byte != b'\r'));
+            while let Some(data) = take_event_data(&mut buffer) {
+                let Ok(status) = serde_json::from_str::<JobStatus>(&data) else { continue };
+                progress.report(&status);
+                if status.state.is_terminal() {
+                    return Some(status);
+                }
+            }
+        }
+        None
+    }
+
+    /// `Ok` when the server answers `/health` with `200`.
+    pub async fn health(&self) -> Result<(), BackendError> {
+        if self.endpoint.is_empty() {
+            return Err(BackendError::Unavailable("No server URL configured".into()));
+        }
+        let builder = self.request(self.http.get(self.url("health"))).timeout(HEALTH_CHECK_TIMEOUT);
+        let response = self.send_once(builder).await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(BackendError::Unavailable(format!("Health check returned {}", response.status())))
+        }
+    }
+
+    /// Downloads a remote artifact into the cache, with the compile request's credentials
+    /// when it lives on the compile server and without them elsewhere, e.g. on a CDN or
+    /// behind a presigned URL. Skips the download when the server-reported hash is
+    /// already cached.
+    pub async fn fetch_artifact(&self, url: &str, expected_hash: Option<&str>) -> Result<CompilationArtifact, BackendError> {
+        self.download(url, expected_hash, None).await
+    }
+
+    async fn download(
+        &self,
+        url: &str,
+        expected_hash: Option<&str>,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<CompilationArtifact, BackendError> {
+        if let Some(cached) = expected_hash.and_then(|hash| self.cache.lookup(hash)) {
+            return Ok(cached);
+        }
+
+        let response = if self.is_endpoint_url(url) {
+            self.send(self.timed(self.request(self.http.get(url))), events).await?
+        } else {
+            self.send_anonymous(self.timed(self.http.get(url)), events).await?
+        };
+        if !response.status().is_success() {
+            return Err(BackendError::Network(format!("Artifact download returned {}", response.status())));
+        }
+        let bytes = response.bytes().await
+            .map_err(|e| self.request_error(e))?;
+
+        let artifact = self.cache.store(&bytes)
+            .map_err(|e| BackendError::IO(e.to_string()))?;
+
+        if let (Some(expected), Some(actual)) = (expected_hash, artifact.content_hash.as_deref())
+            && !expected.eq_ignore_ascii_case(actual)
+        {
+            return Err(BackendError::Network(format!(
+                "Artifact hash mismatch: server reported {}, downloaded {}",
+                expected, actual
+            )));
+        }
+
+        Ok(artifact)
+    }
+
+    // Refuses requests the server would misinterpret, e.g. options it would ignore.
+    async fn check_compatible(&self, request: &CompileRequest) -> Result<&Capabilities, BackendError> {
+        let capabilities = self.capabilities().await?;
+        if capabilities.negotiate().is_none() {
+            return Err(BackendError::Setup(format!(
+                "Server speaks protocol versions {}-{}, this client speaks {}",
+                capabilities.min_protocol_version, capabilities.protocol_version, PROTOCOL_VERSION
+            )));
+        }
+        if request.options != CompileOptions::default() && !capabilities.supports(Feature::Options) {
+            return Err(BackendError::InvalidOptions("The server does not support compile options".into()));
+        }
+        Ok(capabilities)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.endpoint, path)
+    }
+
+    // Whether `url` has the endpoint's scheme, host and port, so credentials may go with it.
+    fn is_endpoint_url(&self, url: &str) -> bool {
+        match (reqwest::Url::parse(url), reqwest::Url::parse(&self.endpoint)) {
+            (Ok(url), Ok(endpoint)) => url.origin() == endpoint.origin(),
+            _ => false,
+        }
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        builder
+            .header(VERSION_HEADER, PROTOCOL_VERSION.to_string())
+            .header(CLIENT_HEADER, self.uploads.client_id())
+    }
+
+    fn timed(&self, builder: RequestBuilder) -> RequestBuilder {
+        match self.timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        }
+    }
+
+    // Sends `builder` with the current credentials, repeating it after transient failures
+    // as the retry policy allows and once after refreshing rejected credentials. The
+    // last attempt's outcome is returned as is, so callers see the final error status.
+    async fn send(
+        &self,
+        builder: RequestBuilder,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<Response, BackendError> {
+        self.send_with(builder, events, true, Resend::AfterTransientErrors).await
+    }
+
+    // Like `send`, for requests that start a compile on the server.
+    async fn submit(
+        &self,
+        builder: RequestBuilder,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<Response, BackendError> {
+        self.send_with(builder, events, true, Resend::IfUnsent).await
+    }
+
+    // Like `send`, without credentials, for URLs outside the endpoint.
+    async fn send_anonymous(
+        &self,
+        builder: RequestBuilder,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+    ) -> Result<Response, BackendError> {
+        self.send_with(builder, events, false, Resend::AfterTransientErrors).await
+    }
+
+    async fn send_with(
+        &self,
+        builder: RequestBuilder,
+        events: Option<&mpsc::UnboundedSender<CompileEvent>>,
+        authenticated: bool,
+        resend: Resend,
+    ) -> Result<Response, BackendError> {
+        let mut credentials = if authenticated { self.auth.credentials().await? } else { None };
+        let mut refreshed = !authenticated;
+        let mut attempt = 1;
+        loop {
+            let Some(next) = builder.try_clone() else {
+                return authorize(builder, credentials.as_ref()).send().await
+                    .map_err(|e| self.request_error(e));
+            };
+
+            let (reason, retry_after) = match authorize(next, credentials.as_ref()).send().await {
+                Ok(response) if response.status() == StatusCode::UNAUTHORIZED && !refreshed => {
+                    refreshed = true;
+                    match self.auth.refresh(credentials.as_ref()).await? {
+                        Some(fresh) if credentials.as_ref() != Some(&fresh) => {
+                            credentials = Some(fresh);
+                            continue;
+                        }
+                        _ => return Ok(response),
+                    }
+                }
+                Ok(response) if is_transient_status(response.status()) && self.retry.allows_retry(attempt) => {
+                    (format!("Server returned {}", response.status()), retry_after(&response))
+                }
+                Ok(response) => return Ok(response),
+                Err(e) if resend.after(&e) && self.retry.allows_retry(attempt) => {
+                    (e.to_string(), None)
+                }
+                Err(e) => return Err(self.request_error(e)),
+            };
+
+            let backoff = self.retry.backoff(attempt);
+            let delay = retry_after.map_or(backoff, |after| after.max(backoff)).min(self.retry.max_backoff);
+            if let Some(events) = events {
+                let _ = events.send(CompileEvent::Retrying { attempt, delay, reason });
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // A request that reached the server but ran past `timeout` is reported as a
+    // timeout, like a local run that takes too long; anything else as a network error.
+    fn request_error(&self, error: reqwest::Error) -> BackendError {
+        match self.timeout {
+            Some(timeout) if error.is_timeout() && !error.is_connect() => BackendError::Timeout { timeout, log: String::new() },
+            _ => BackendError::Network(error.to_string()),
+        }
+    }
+
+    async fn send_once(&self, builder: RequestBuilder) -> Result<Response, BackendError> {
+        let credentials = self.auth.credentials().await?;
+        authorize(builder, credentials.as_ref()).send().await
+            .map_err(|e| BackendError::Network(e.to_string()))
+    }
+}
+
+impl fmt::Debug for RemoteClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteClient")
+            .field("endpoint", &self.endpoint)
+            .field("timeout", &self.timeout)
+            .field("retry", &self.retry)
+            .finish_non_exhaustive()
+    }
+}
+
+// Which requests that failed without a response `send_with` repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resend {
+    // Reads and deletions, which are safe to repeat after any transient error.
+    AfterTransientErrors,
+    // Compiles, which the server may already be running after a timeout or a dropped
+    // connection. Repeating them then would run the compile again from the start.
+    IfUnsent,
+}
+
+impl Resend {
+    fn after(self, error: &reqwest::Error) -> bool {
+        match self {
+            Resend::AfterTransientErrors => error.is_connect() || error.is_timeout() || error.is_request(),
+            Resend::IfUnsent => error.is_connect(),
+        }
+    }
+}
+
+fn authorize(builder: RequestBuilder, credentials: Option<&Credentials>) -> RequestBuilder {
+    match credentials {
+        Some(credentials) => credentials.apply(builder),
+        None => builder,
+    }
+}
+
+// Maps a non-success status and its `ErrorBody` to the matching backend error.
+async fn check_status(response: Response) -> Result<Response, BackendError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let body = serde_json::from_str::<ErrorBody>(&text)
+        .unwrap_or_else(|_| ErrorBody::new(ErrorCode::Internal, text.trim()));
+    let message = if body.error.is_empty() {
+        format!("Server returned {}", status)
+    } else {
+        format!("Server returned {}: {}", status, body.error)
+    };
+
+    if is_transient_status(status) || status.is_server_error() {
+        return Err(BackendError::Unavailable(message));
+    }
+    if status == StatusCode::UNAUTHORIZED {
+        return Err(BackendError::Unauthorized(message));
+    }
+    Err(backend_error(body, message))
+}
+
+fn backend_error(body: ErrorBody, message: String) -> BackendError {
+    match body.code {
+        ErrorCode::Unavailable => BackendError::Unavailable(message),
+        ErrorCode::UnsupportedVersion => BackendError::Setup(message),
+        ErrorCode::Unauthorized => BackendError::Unauthorized(message),
+        ErrorCode::Superseded => BackendError::Cancelled,
+        ErrorCode::InvalidOptions => BackendError::InvalidOptions(body.error),
+        _ => BackendError::Compilation(message),
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// `Retry-After` in seconds; the HTTP date form isn't used by compile servers.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+// Takes the next complete server-sent event out of `buffer` and returns its data.
+// Comments, such as keep-alives, come back as empty data.
+fn take_event_data(buffer: &mut Vec<u8>) -> Option<String> {
+    let end = buffer.windows(2).position(|pair| pair == b"\n\n")?;
+    let event: Vec<u8> = buffer.drain(..end + 2).collect();
+    let event = String::from_utf8_lossy(&event);
+    let data: Vec<&str> = event.lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+    Some(data.join("\n"))
+}
+
+// Sends a job's progress to the caller's events, once per change.
+struct ProgressReporter<'a> {
+    events: Option<&'a mpsc::UnboundedSender<CompileEvent>>,
+    last: Option<CompileProgress>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(events: Option<&'a mpsc::UnboundedSender<CompileEvent>>) -> Self {
+        Self { events, last: None }
+    }
+
+    fn report(&mut self, status: &JobStatus) {
+        if let (Some(events), Some(progress)) = (self.events, &status.progress)
+            && self.last.as_ref() != Some(progress)
+        {
+            self.last = Some(progress.clone());
+            let _ = events.send(CompileEvent::Progress(progress.clone()));
+        }
+    }
+}
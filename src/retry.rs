@@ -0,0 +1,53 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How often and how patiently to repeat a request that failed for a transient reason,
+/// such as a dropped connection, a timeout or a `503`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 turns retries off.
+    pub max_attempts: u32,
+    /// The delay before the first retry, doubled for every further one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Up to this percentage of each delay is randomly taken off, so clients that
+    /// lost the connection together don't all come back at once.
+    pub jitter_percent: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            jitter_percent: 50,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self { max_attempts: 1, ..Default::default() }
+    }
+
+    /// Whether another attempt may follow attempt number `attempt`, counting from 1.
+    pub fn allows_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// The delay after failed attempt number `attempt`, counting from 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(1).min(16);
+        let delay = self.initial_backoff.saturating_mul(1 << doublings).min(self.max_backoff);
+        let jitter = f64::from(self.jitter_percent.min(100)) / 100.0;
+        delay.mul_f64(1.0 - jitter * random_fraction())
+    }
+}
+
+// Good enough for jitter: every `RandomState` is seeded differently.
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -1,26 +1,41 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
-use bumpalo::Bump;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::sync::CancellationToken;
 use crate::{
     highlight::{Highlight, highlight},
-    diagnostic::Diagnostic, // This is your internal Diagnostic struct
-    syntax::{SyntaxKind, SyntaxTree},
+    diagnostic::{Diagnostic, DiagnosticSource}, // This is your internal Diagnostic struct
+    syntax::SyntaxTree,
+    analysis,
     parser::parse_text,
+    synctex::{PdfRect, SyncTex},
+    compile_cache::{CachingBackend, CompileCacheLimits},
+    process::ProcessLimits,
+    backend_manager::BackendManager,
+    progress::CompileEvent,
+    discovery::{self, ContextInstallation},
+    sandbox::{PolicyViolation, SandboxPolicy},
+    persistent::PersistentBackend,
+    retry::RetryPolicy,
 };
 
 // Corrected import to match your backend_traits.rs
 use crate::backend_traits::{
-    BackendError, CompilationBackend, CompilationRequest, CompilationResult,
+    BackendError, BackendHealth, CompilationBackend, CompileOptions, CompilationRequest, CompilationResult,
     LocalBackend, RemoteBackend, CompilationError, 
 };
 
 #[derive(Debug)]
 pub struct ContextRuntime {
-    backend: Arc<RwLock<Box<dyn CompilationBackend>>>,
+    backend: RwLock<Arc<dyn CompilationBackend>>,
+    // The local ConTeXt backend on its own, for callers that handle the server themselves
+    local_backend: Option<Arc<dyn CompilationBackend>>,
     config: RuntimeConfig,
     documents: RwLock<HashMap<String, Document>>,
     diagnostics: RwLock<HashMap<String, Vec<Diagnostic>>>, // This is `crate::diagnostic::Diagnostic`
+    synctex: RwLock<HashMap<String, SyncTex>>,
+    compile_options: RwLock<HashMap<String, CompileOptions>>,
 }
 
 // ... Document, RuntimeConfig, Default for RuntimeConfig unchanged ...
@@ -28,7 +43,6 @@ pub struct ContextRuntime {
 pub struct Document {
     source: String,
     syntax_tree: SyntaxTree,
-    arena: Box<Bump>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -37,6 +51,20 @@ pub struct RuntimeConfig {
     pub server_url: Option<String>,
     pub auth_token: Option<String>,
     pub local_executable: Option<PathBuf>,
+    pub output_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    /// `None` disables result caching.
+    pub compile_cache: Option<CompileCacheLimits>,
+    /// Timeout and resource limits for local ConTeXt runs.
+    pub process_limits: ProcessLimits,
+    /// What local ConTeXt runs may do; trusted by default.
+    pub sandbox: SandboxPolicy,
+    /// Options for documents without their own, see `set_compile_options`.
+    pub compile_options: CompileOptions,
+    /// Retries of failed requests to the compile server.
+    pub remote_retry: RetryPolicy,
+    /// Keep a warm ConTeXt engine between local compilations, see `PersistentBackend`.
+    pub persistent_server: bool,
 }
 
 impl Default for RuntimeConfig {
@@ -46,6 +74,14 @@ impl Default for RuntimeConfig {
             server_url: None,
             auth_token: None,
             local_executable: None,
+            output_dir: None,
+            cache_dir: None,
+            compile_cache: Some(CompileCacheLimits::default()),
+            process_limits: ProcessLimits::default(),
+            sandbox: SandboxPolicy::default(),
+            compile_options: CompileOptions::default(),
+            remote_retry: RetryPolicy::default(),
+            persistent_server: false,
         }
     }
 }
@@ -53,35 +89,108 @@ impl Default for RuntimeConfig {
 
 impl ContextRuntime {
     pub fn new_with_backend(config: RuntimeConfig, backend: Box<dyn CompilationBackend>) -> Arc<Self> {
+        Self::with_backends(config, Arc::from(backend), None)
+    }
+
+    fn with_backends(
+        config: RuntimeConfig,
+        backend: Arc<dyn CompilationBackend>,
+        local_backend: Option<Arc<dyn CompilationBackend>>,
+    ) -> Arc<Self> {
         Arc::new(Self {
-            backend: Arc::new(RwLock::new(backend)),
+            backend: RwLock::new(backend),
+            local_backend,
             config,
             documents: RwLock::new(HashMap::new()),
             diagnostics: RwLock::new(HashMap::new()),
+            synctex: RwLock::new(HashMap::new()),
+            compile_options: RwLock::new(HashMap::new()),
         })
     }
 
     pub fn new(config: RuntimeConfig) -> Arc<Self> {
-        let backend = Self::create_backend(&config);
-        Self::new_with_backend(config, backend)
+        // The manager and `compile_document_locally` share one local backend and so
+        // one working directory and one compile cache.
+        let (name, local) = Self::create_local_backend(&config);
+        let local = local.map(|backend| Self::with_cache(&config, backend));
+        let shared = local.as_ref().ok().map(Arc::clone);
+        let backend = Self::create_backend(&config, (name, local));
+        Self::with_backends(config, Arc::from(backend), shared)
+    }
+
+    /// Wraps `backend` in a `CachingBackend` when the config enables the compile cache.
+    /// Each backend gets its own, so a compile that skips the manager is cached too.
+    pub fn with_cache(config: &RuntimeConfig, backend: Box<dyn CompilationBackend>) -> Arc<dyn CompilationBackend> {
+        match config.compile_cache {
+            Some(limits) => Arc::new(CachingBackend::new(backend, limits)),
+            None => Arc::from(backend),
+        }
     }
 
-    fn create_backend(config: &RuntimeConfig) -> Box<dyn CompilationBackend> {
-        if config.remote {
-            Box::new(RemoteBackend::new(
-                config.server_url.clone().unwrap_or_default(),
-                config.auth_token.clone(),
-            ))
+    // The preferred backend comes first and the other one is the fallback. A backend
+    // that can't be set up (no mtxrun, no server URL) is left out and reported instead.
+    fn create_backend(
+        config: &RuntimeConfig,
+        local: (&'static str, Result<Arc<dyn CompilationBackend>, BackendError>),
+    ) -> Box<dyn CompilationBackend> {
+        let (name, remote) = Self::create_remote_backend(config);
+        let remote = (name, remote.map(|backend| Self::with_cache(config, backend)));
+        let ordered = if config.remote { [remote, local] } else { [local, remote] };
+
+        let manager = ordered.into_iter().fold(BackendManager::new(), |manager, backend| match backend {
+            (_, Ok(backend)) => manager.with_shared_backend(backend),
+            (name, Err(e)) => manager.with_setup_error(name, e),
+        });
+        Box::new(manager)
+    }
+
+    fn create_local_backend(config: &RuntimeConfig) -> (&'static str, Result<Box<dyn CompilationBackend>, BackendError>) {
+        let local_backend = match LocalBackend::new(config.local_executable.clone()) {
+            Ok(backend) => backend
+                .with_output_dir(config.output_dir.clone())
+                .with_limits(config.process_limits)
+                .with_sandbox(config.sandbox.clone()),
+            Err(e) => return ("local", Err(e)),
+        };
+
+        if config.persistent_server {
+            ("persistent", Ok(Box::new(PersistentBackend::from_local(local_backend))))
         } else {
-            let local_backend = LocalBackend::new(config.local_executable.clone())
-                .expect("Failed to create local backend"); // This unwrap will panic on `BackendError::Unavailable`
-            Box::new(local_backend)
+            ("local", Ok(Box::new(local_backend)))
         }
     }
 
+    fn create_remote_backend(config: &RuntimeConfig) -> (&'static str, Result<Box<dyn CompilationBackend>, BackendError>) {
+        let backend = match &config.server_url {
+            Some(url) if !url.is_empty() => Ok(Box::new(
+                RemoteBackend::new(url.clone(), config.auth_token.clone())
+                    .with_cache_dir(config.cache_dir.clone())
+                    .with_retry(config.remote_retry.clone()),
+            ) as Box<dyn CompilationBackend>),
+            _ => Err(BackendError::Unavailable("No server URL configured".into())),
+        };
+        ("remote", backend)
+    }
+
+    /// Health of every configured backend, including ones that couldn't be set up.
+    pub async fn backend_health(&self) -> Vec<BackendHealth> {
+        let backend = self.backend.read().ok().map(|backend| Arc::clone(&*backend));
+        match backend {
+            Some(backend) => backend.health_report().await,
+            None => Vec::new(),
+        }
+    }
+
+    /// The ConTeXt installation local compilations use, probed for its engine and
+    /// version. `None` if no `mtxrun` was found.
+    pub async fn context_installation(&self) -> Option<ContextInstallation> {
+        let (path, source) = discovery::find_mtxrun(self.config.local_executable.as_deref())?;
+        Some(discovery::probe(&path, source).await)
+    }
+
     pub fn set_backend(&self, backend: Box<dyn CompilationBackend>) {
         let mut write_guard = self.backend.write().unwrap();
-        *write_guard = backend;
+        *write_guard = Arc::from(backend);
     }
 
     pub fn with_document<F, R>(&self, uri: &str, f: F) -> Option<R>
@@ -93,13 +202,11 @@ impl ContextRuntime {
     }
 
     pub fn open_document(&self, uri: String, content: String) -> Result<(), RuntimeError> {
-        let arena = Box::new(Bump::new());
         let syntax_tree = parse_text(&content);
 
         let document = Document {
             source: content,
             syntax_tree,
-            arena,
         };
 
         self.documents.write()
@@ -128,6 +235,8 @@ impl ContextRuntime {
             document.source = new_source;
             document.syntax_tree = new_tree;
 
+            // `update_diagnostics` reads the documents again.
+            drop(documents);
             self.update_diagnostics(uri)?;
         }
 
@@ -137,6 +246,25 @@ impl ContextRuntime {
     pub fn close_document(&self, uri: &str) {
         self.documents.write().unwrap().remove(uri);
         self.diagnostics.write().unwrap().remove(uri);
+        self.synctex.write().unwrap().remove(uri);
+        self.compile_options.write().unwrap().remove(uri);
+    }
+
+    /// Overrides the configured compile options for one document, or goes back to
+    /// them with `None`.
+    pub fn set_compile_options(&self, uri: &str, options: Option<CompileOptions>) -> Result<(), RuntimeError> {
+        let mut all_options = self.compile_options.write().map_err(|_| RuntimeError::LockPoisoned)?;
+        match options {
+            Some(options) => all_options.insert(uri.to_string(), options),
+            None => all_options.remove(uri),
+        };
+        Ok(())
+    }
+
+    pub fn compile_options(&self, uri: &str) -> CompileOptions {
+        self.compile_options.read().ok()
+            .and_then(|options| options.get(uri).cloned())
+            .unwrap_or_else(|| self.config.compile_options.clone())
     }
 
     pub fn get_highlights(&self, uri: &str) -> Vec<Highlight> {
@@ -156,11 +284,10 @@ impl ContextRuntime {
     }
 
     fn update_diagnostics(&self, uri: &str) -> Result<(), RuntimeError> {
-        let mut diagnostics = Vec::new();
-
-        if let Some(doc) = self.documents.read().unwrap().get(uri) {
-            self.collect_syntax_diagnostics(&doc.syntax_tree, &mut diagnostics);
-        }
+        let diagnostics = self.documents.read().unwrap()
+            .get(uri)
+            .map(|doc| analysis::diagnostics(&doc.syntax_tree))
+            .unwrap_or_default();
 
         let mut diag_map = self.diagnostics.write()
             .map_err(|_| RuntimeError::LockPoisoned)?;
@@ -169,79 +296,68 @@ impl ContextRuntime {
         Ok(())
     }
 
-    fn collect_syntax_diagnostics(&self, tree: &SyntaxTree, diagnostics: &mut Vec<Diagnostic>) {
-        for node in tree.root().descendants() {
-            match node.kind() {
-                SyntaxKind::Command => {
-                    if let Some(name_token) = node.first_token() {
-                        let name = name_token.text().trim_start_matches('\\');
-                        if !self.is_known_command(name) {
-                            diagnostics.push(Diagnostic::warning( // Uses crate::diagnostic::Diagnostic
-                                name_token.text_range().start().into(),
-                                name_token.text_range().len().into(),
-                                format!("Unknown command: \\{}", name),
-                            ));
-                        }
-                    }
-                }
-                SyntaxKind::Environment => {
-                    if let Some(name_token) = node.first_token() {
-                        let name = name_token.text().trim_start_matches(r"\start");
-                        if !self.is_known_environment(name) {
-                            diagnostics.push(Diagnostic::warning( // Uses crate::diagnostic::Diagnostic
-                                name_token.text_range().start().into(),
-                                name_token.text_range().len().into(),
-                                format!("Unknown environment: {}", name),
-                            ));
-                        }
-                    }
-                }
-                SyntaxKind::Error => {
-                    if let Some(token) = node.first_token() {
-                        diagnostics.push(Diagnostic::error( // Uses crate::diagnostic::Diagnostic
-                            token.text_range().start().into(),
-                            token.text_range().len().into(),
-                            "Syntax error".to_string(),
-                        ));
-                    }
-                }
-                _ => {}
-            }
-        }
+    pub async fn compile_document(&self, uri: &str) -> Result<CompilationResult, RuntimeError> {
+        self.compile_document_cancellable(uri, CancellationToken::new()).await
     }
 
-    fn is_known_command(&self, name: &str) -> bool {
-        matches!(name,
-            "setupbodyfont" | "setuppapersize" | "setupmargins" | "setuphead" |
-            "setuplist" | "setupitemize" | "setupenumerate" | "setupdescription" |
-            "definefont" | "definecolor" | "definelayout" | "setupcolor" |
-            "input" | "component" | "product" | "environment" | "project" |
-            "em" | "bf" | "it" | "tt" | "rm" | "sf" | "sc" | "sl" |
-            "item" | "head" | "subhead" | "subsubhead" | "title" | "subject" |
-            "page" | "blank" | "space" | "par" | "break" | "hfill" | "vfill" |
-            "starttext" | "stoptext" | "startdocument" | "stopdocument"
-        )
+    /// Like `compile_document`, but stops the backend when `cancel` is triggered
+    /// and returns `RuntimeError::Cancelled`.
+    pub async fn compile_document_cancellable(
+        &self,
+        uri: &str,
+        cancel: CancellationToken,
+    ) -> Result<CompilationResult, RuntimeError> {
+        self.compile_document_streaming(uri, cancel, None).await
     }
 
-    fn is_known_environment(&self, name: &str) -> bool {
-        matches!(name,
-            "document" | "text" | "itemize" | "enumerate" | "description" |
-            "table" | "tabulate" | "figure" | "float" | "framed" |
-            "typing" | "verbatim" | "quote" | "quotation" | "lines" |
-            "formula" | "math" | "alignment" | "combinations" | "columns"
-        )
+    /// Like `compile_document_cancellable`, additionally sending progress and log
+    /// lines to `events` while the backend runs.
+    pub async fn compile_document_streaming(
+        &self,
+        uri: &str,
+        cancel: CancellationToken,
+        events: Option<UnboundedSender<CompileEvent>>,
+    ) -> Result<CompilationResult, RuntimeError> {
+        // Clone the backend out of the lock so the guard isn't held across the await.
+        let backend = Arc::clone(&*self.backend.read().map_err(|_| RuntimeError::LockPoisoned)?);
+        self.compile_document_with(backend, uri, cancel, events).await
     }
 
-    pub async fn compile_document(&self, uri: &str) -> Result<CompilationResult, RuntimeError> {
+    /// Whether `compile_document_locally` has a local ConTeXt to compile with.
+    pub fn has_local_backend(&self) -> bool {
+        self.local_backend.is_some()
+    }
+
+    /// Compiles with local ConTeXt only, never the compile server. For callers that
+    /// talk to the server themselves and fall back when it is unreachable.
+    pub async fn compile_document_locally(
+        &self,
+        uri: &str,
+        cancel: CancellationToken,
+        events: Option<UnboundedSender<CompileEvent>>,
+    ) -> Result<CompilationResult, RuntimeError> {
+        let backend = self.local_backend.clone()
+            .ok_or_else(|| RuntimeError::Unavailable("No local ConTeXt installation found".into()))?;
+        self.compile_document_with(backend, uri, cancel, events).await
+    }
+
+    async fn compile_document_with(
+        &self,
+        backend: Arc<dyn CompilationBackend>,
+        uri: &str,
+        cancel: CancellationToken,
+        events: Option<UnboundedSender<CompileEvent>>,
+    ) -> Result<CompilationResult, RuntimeError> {
         let content = self.get_document_source(uri)
             .ok_or(RuntimeError::DocumentNotFound(uri.to_string()))?;
 
-        let backend_guard = self.backend.read().map_err(|_| RuntimeError::LockPoisoned)?;
-        let backend = backend_guard.as_ref();
-
-        let compilation_result = backend.compile(CompilationRequest {
+        let mut compilation_result = backend.compile(CompilationRequest {
             content,
             job_id: uri.to_string(),
+            cancel,
+            events,
+            options: self.compile_options(uri),
+            ..Default::default()
         })
         .await
         .map_err(|e: BackendError| { // Explicitly map BackendError to RuntimeError
@@ -254,11 +370,27 @@ impl ContextRuntime {
                 },
                 BackendError::Unavailable(msg) => RuntimeError::Unavailable(format!("Backend unavailable: {}", msg)),
                 BackendError::Setup(msg) => RuntimeError::Unavailable(format!("Backend setup error: {}", msg)),
+                BackendError::Unauthorized(details) => RuntimeError::AuthenticationFailed { uri: uri.to_string(), details },
                 BackendError::IO(msg) => RuntimeError::CompilationError {
                     line: 0,
                     column: 0,
                     message: format!("IO error during compilation: {}", msg),
                 },
+                BackendError::Cancelled => RuntimeError::Cancelled(uri.to_string()),
+                BackendError::InvalidOptions(msg) => RuntimeError::CompilationError {
+                    line: 0,
+                    column: 0,
+                    message: format!("Invalid compile options: {}", msg),
+                },
+                BackendError::Timeout { timeout, log } => RuntimeError::Timeout {
+                    message: format!("Compilation timed out after {}s", timeout.as_secs()),
+                    log,
+                },
+                BackendError::ResourceExceeded { resource, log } => RuntimeError::LimitExceeded {
+                    message: format!("Compilation exceeded its {} limit", resource),
+                    log,
+                },
+                BackendError::PolicyViolation { violation, log } => RuntimeError::PolicyViolation { violation, log },
             }
         })?; // Apply the mapping and then unwrap
 
@@ -266,9 +398,38 @@ impl ContextRuntime {
         // update the diagnostics based on the compilation result
         self.update_compilation_diagnostics(uri, &compilation_result)?;
 
+        if let Some(synctex) = compilation_result.synctex.take() {
+            self.synctex.write()
+                .map_err(|_| RuntimeError::LockPoisoned)?
+                .insert(uri.to_string(), synctex);
+        }
+
         Ok(compilation_result)
     }
 
+    /// Maps a 1-based line/column in `uri` to the area it produced in the last compiled PDF.
+    pub fn forward_search(&self, uri: &str, line: u32, column: u32) -> Option<PdfRect> {
+        let synctex = self.synctex.read().ok()?;
+        let data = synctex.get(uri)?;
+        data.forward_search(data.main_input()?, line, column)
+    }
+
+    /// Maps a point on a page of `uri`'s last compiled PDF back to a source location.
+    /// Returns `uri` for the main file, or the input path for included files.
+    pub fn inverse_search(&self, uri: &str, page: u32, x: f64, y: f64) -> Option<SourceLocation> {
+        let synctex = self.synctex.read().ok()?;
+        let data = synctex.get(uri)?;
+        let position = data.inverse_search(page, x, y)?;
+        let is_main = data.main_input()
+            .and_then(|tag| data.input_path(tag))
+            .is_some_and(|main| main == position.file);
+
+        Some(SourceLocation {
+            uri: if is_main { uri.to_string() } else { position.file.to_string_lossy().into_owned() },
+            line: position.line,
+            column: position.column,
+        })
+    }
 
     fn update_compilation_diagnostics(
         &self,
@@ -281,14 +442,8 @@ impl ContextRuntime {
         let diagnostics = diag_map.entry(uri.to_string())
             .or_default();
 
-        diagnostics.retain(|d| {
-            // Keep syntax diagnostics, remove compilation errors/warnings (if they have a specific tag/source)
-            // Or, for now, just append. If you have "source" in Diagnostic, you could filter by source.
-            // For this example, let's assume we just append, and the client will handle duplicates if necessary,
-            // or you add a `source` field to Diagnostic.
-            true // Keep all existing diagnostics. New ones will be added.
-        });
-
+        // The previous compile's diagnostics are replaced; the parser's stay.
+        diagnostics.retain(|d| d.source != DiagnosticSource::Compilation);
 
         if let Some(document) = self.documents.read().unwrap().get(uri) {
             for error in &result.errors {
@@ -298,7 +453,7 @@ impl ContextRuntime {
                         // FIX: Explicitly cast to usize
                         (error.column.saturating_sub(error.line).max(1)) as usize, // Basic attempt to derive length from start/end if available, otherwise 1
                         error.message.clone(),
-                    ));
+                    ).with_source(DiagnosticSource::Compilation));
                 }
             }
 
@@ -309,7 +464,7 @@ impl ContextRuntime {
                         // FIX: Explicitly cast to usize
                         (warning.column.saturating_sub(warning.line).max(1)) as usize, // Same as above
                         warning.message.clone(),
-                    ));
+                    ).with_source(DiagnosticSource::Compilation));
                 }
             }
         }
@@ -374,6 +529,13 @@ impl ContextRuntime {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub uri: String,
+    pub line: u32,
+    pub column: u32,
+}
+
 // ... RuntimeError enum remains the same ...
 #[derive(Debug, thiserror::Error)]
 pub enum RuntimeError {
@@ -389,4 +551,26 @@ pub enum RuntimeError {
     DocumentNotFound(String),
     #[error("Backend unavailable: {0}")]
     Unavailable(String),
+    #[error("Compilation cancelled: {0}")]
+    Cancelled(String),
+    #[error("Authentication failed: {details}")]
+    AuthenticationFailed {
+        uri: String,
+        details: String,
+    },
+    #[error("{message}")]
+    Timeout {
+        message: String,
+        log: String,
+    },
+    #[error("{message}")]
+    LimitExceeded {
+        message: String,
+        log: String,
+    },
+    #[error("Compilation blocked by the sandbox policy: {violation}")]
+    PolicyViolation {
+        violation: PolicyViolation,
+        log: String,
+    },
 }
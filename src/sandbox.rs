@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use regex::Regex;
+use tokio::process::Command;
+use which::which;
+
+/// What a local ConTeXt run is allowed to do.
+///
+/// The default trusts the document, like running `context` by hand. Documents from
+/// untrusted sources should use [`SandboxPolicy::hardened`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxPolicy {
+    /// Lets TeX run programs (`\write18`, `os.execute`). When off, kpathsea's
+    /// `shell_escape` is disabled and ConTeXt runs with `--sandbox`.
+    pub allow_shell_escape: bool,
+    /// Keeps reads and writes inside the working directory and the TeX tree, using
+    /// `--paranoid` and kpathsea's `openin_any`/`openout_any`.
+    pub restrict_file_access: bool,
+    /// Linux only: runs mtxrun under bubblewrap (`bwrap`) in new namespaces, with no
+    /// network. Only the system libraries, the TeX tree and the working directory are
+    /// visible, see [`SandboxPolicy::readable_paths`]; home directories are not.
+    pub isolate: bool,
+    /// Extra directories the isolated process may write to, e.g. the ConTeXt cache
+    /// (`TEXMFCACHE`) when it isn't generated ahead of time.
+    pub writable_paths: Vec<PathBuf>,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::trusted()
+    }
+}
+
+impl SandboxPolicy {
+    pub fn trusted() -> Self {
+        Self {
+            allow_shell_escape: true,
+            restrict_file_access: false,
+            isolate: false,
+            writable_paths: Vec::new(),
+        }
+    }
+
+    /// Everything off that a document doesn't need, isolated where the platform allows.
+    pub fn hardened() -> Self {
+        Self {
+            allow_shell_escape: false,
+            restrict_file_access: true,
+            isolate: cfg!(target_os = "linux"),
+            writable_paths: Vec::new(),
+        }
+    }
+
+    pub fn with_writable_path(mut self, path: PathBuf) -> Self {
+        self.writable_paths.push(path);
+        self
+    }
+
+    pub fn is_trusted(&self) -> bool {
+        *self == Self::trusted()
+    }
+
+    /// Flags added to the `mtx-context` command line.
+    pub fn context_arguments(&self) -> Vec<String> {
+        let mut arguments = Vec::new();
+        if !self.allow_shell_escape {
+            arguments.push("--sandbox".to_string());
+        }
+        if self.restrict_file_access {
+            arguments.push("--paranoid".to_string());
+        }
+        arguments
+    }
+
+    /// A command that runs `program` in `working_dir` under this policy, wrapped in
+    /// bubblewrap when `isolate` is set.
+    pub async fn command(&self, program: &Path, working_dir: &Path) -> Result<Command, SandboxError> {
+        let mut command = if self.isolate {
+            let bwrap = bubblewrap()?;
+            let mut command = Command::new(bwrap);
+            for path in self.readable_paths(program).await {
+                command.arg("--ro-bind-try").arg(&path).arg(&path);
+            }
+            command.args(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"]);
+            for path in std::iter::once(working_dir).chain(self.writable_paths.iter().map(PathBuf::as_path)) {
+                command.arg("--bind").arg(path).arg(path);
+            }
+            command
+                .args(["--unshare-all", "--die-with-parent", "--new-session", "--chdir"])
+                .arg(working_dir)
+                .arg("--")
+                .arg(program);
+            command
+        } else {
+            Command::new(program)
+        };
+
+        // kpathsea reads these from the environment; they override texmf.cnf.
+        if !self.allow_shell_escape {
+            command.env("shell_escape", "f");
+        }
+        if self.restrict_file_access {
+            command.env("openin_any", "p").env("openout_any", "p");
+        }
+
+        command.current_dir(working_dir);
+        Ok(command)
+    }
+
+    /// What an isolated run of `program` can read: the system directories programs
+    /// need to start, and the TeX tree `program` belongs to. Paths that don't exist
+    /// are skipped when binding.
+    pub async fn readable_paths(&self, program: &Path) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = SYSTEM_PATHS.iter().map(PathBuf::from).collect();
+        for path in tex_tree(program).await {
+            if !paths.iter().any(|parent| path.starts_with(parent)) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// Checks that the policy can be enforced on this machine.
+    pub fn check(&self) -> Result<(), SandboxError> {
+        if self.isolate {
+            bubblewrap()?;
+        }
+        Ok(())
+    }
+
+    /// The first thing in `log` the policy blocked, if any.
+    pub fn find_violation(&self, log: &str) -> Option<PolicyViolation> {
+        if self.is_trusted() {
+            return None;
+        }
+
+        log.lines().find_map(|line| {
+            let line = line.trim_end();
+            if let Some(caps) = SHELL_ESCAPE_RE.captures(line) {
+                Some(PolicyViolation { kind: ViolationKind::ShellEscape, detail: caps[1].trim().to_string() })
+            } else if let Some(caps) = FILE_ACCESS_RE.captures(line) {
+                Some(PolicyViolation { kind: ViolationKind::FileAccess, detail: caps[1].trim().to_string() })
+            } else {
+                SANDBOX_RE.captures(line)
+                    .map(|caps| PolicyViolation { kind: ViolationKind::Blocked, detail: caps[1].trim().to_string() })
+            }
+        })
+    }
+}
+
+// Enough of the system for mtxrun and the engine to load their libraries and fonts.
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64",
+    "/etc/ld.so.cache", "/etc/ld.so.conf", "/etc/ld.so.conf.d", "/etc/alternatives",
+    "/etc/fonts", "/etc/texmf", "/etc/localtime", "/var/lib/texmf",
+];
+
+// How long `mtxrun --expand-var` may take before the tree is left at the installation.
+const TEX_TREE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// The trees `mtxrun --expand-var TEXMF` names, plus the installation `program` lives
+// in (kpathsea's `selfautoparent`, two levels above its directory). Looked up once
+// per program, also when mtxrun fails or hangs, so later runs don't wait again.
+async fn tex_tree(program: &Path) -> Vec<PathBuf> {
+    static TREES: LazyLock<Mutex<HashMap<PathBuf, Vec<PathBuf>>>> = LazyLock::new(Default::default);
+
+    let program = program.canonicalize().unwrap_or_else(|_| program.to_path_buf());
+    if let Some(trees) = TREES.lock().ok().and_then(|trees| trees.get(&program).cloned()) {
+        return trees;
+    }
+
+    let mut trees: Vec<PathBuf> = program.parent()
+        .map(|dir| dir.ancestors().nth(2).filter(|root| root.parent().is_some()).unwrap_or(dir))
+        .map(Path::to_path_buf)
+        .into_iter()
+        .collect();
+    let output = Command::new(&program).args(["--expand-var", "TEXMF"]).kill_on_drop(true).output();
+    if let Ok(Ok(output)) = tokio::time::timeout(TEX_TREE_TIMEOUT, output).await {
+        let expansion = String::from_utf8_lossy(&output.stdout);
+        trees.extend(
+            expansion.split([',', ';', ':', '{', '}', '\n'])
+                .map(|entry| entry.trim().trim_start_matches("!!"))
+                .filter(|entry| entry.starts_with('/'))
+                .map(PathBuf::from)
+                .filter(|path| path.parent().is_some() && path.is_dir()),
+        );
+    }
+
+    if let Ok(mut cache) = TREES.lock() {
+        cache.insert(program, trees.clone());
+    }
+    trees
+}
+
+// runsystem(curl https://example.com)...disabled.
+static SHELL_ESCAPE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"runsystem\((.*)\)\.\.\.disabled").unwrap());
+// tex: Not writing to /home/user/.bashrc (openout_any = p).
+static FILE_ACCESS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)not (?:writing to|reading from) (.+) \(open(?:in|out)_any = p\)").unwrap());
+// sandbox         > call to 'os.execute' is blocked
+static SANDBOX_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^sandbox\s+>\s+(.*(?:blocked|forbidden|not permitted).*)$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The document tried to run a program.
+    ShellEscape,
+    /// The document tried to read or write a file outside the allowed paths.
+    FileAccess,
+    /// ConTeXt's own sandbox blocked something else, e.g. loading a library.
+    Blocked,
+}
+
+/// Something the document tried that the sandbox policy blocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub kind: ViolationKind,
+    /// The command, file or call as it appears in the log.
+    pub detail: String,
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ViolationKind::ShellEscape => write!(f, "shell escape blocked: {}", self.detail),
+            ViolationKind::FileAccess => write!(f, "file access blocked: {}", self.detail),
+            ViolationKind::Blocked => write!(f, "blocked by sandbox: {}", self.detail),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SandboxError {
+    #[error("Process isolation needs bubblewrap (bwrap), which was not found: {0}")]
+    BubblewrapMissing(String),
+    #[error("Process isolation is only supported on Linux")]
+    Unsupported,
+}
+
+fn bubblewrap() -> Result<PathBuf, SandboxError> {
+    if !cfg!(target_os = "linux") {
+        return Err(SandboxError::Unsupported);
+    }
+    which("bwrap").map_err(|e| SandboxError::BubblewrapMissing(e.to_string()))
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_COMPILE_DELAY: Duration = Duration::from_millis(500);
+
+// The waiting or running task per document, with the generation that scheduled it.
+type ScheduledTasks = Arc<Mutex<HashMap<String, (u64, JoinHandle<()>)>>>;
+
+/// Debounces compilations per document for live preview.
+///
+/// A scheduled job runs once the document has been idle for the configured delay.
+/// Scheduling again for the same document aborts the previous job, whether it is
+/// still waiting or already compiling, so only the newest edit produces a result.
+#[derive(Debug)]
+pub struct CompileScheduler {
+    runtime: Handle,
+    delay: RwLock<Duration>,
+    tasks: ScheduledTasks,
+    next_generation: AtomicU64,
+}
+
+impl CompileScheduler {
+    pub fn new(runtime: Handle, delay: Duration) -> Self {
+        Self {
+            runtime,
+            delay: RwLock::new(delay),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            next_generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_delay(&self, delay: Duration) {
+        if let Ok(mut current) = self.delay.write() {
+            *current = delay;
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay.read().map(|d| *d).unwrap_or(DEFAULT_COMPILE_DELAY)
+    }
+
+    /// Runs `job` after the idle delay, superseding anything scheduled for `uri`.
+    /// The job is created only when it fires, so it sees the latest document state.
+    pub fn schedule<F, Fut>(&self, uri: &str, job: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let delay = self.delay();
+        let tasks = Arc::clone(&self.tasks);
+        let key = uri.to_string();
+
+        // Hold the lock while spawning so the task can't clean up before it is registered.
+        let Ok(mut pending) = self.tasks.lock() else { return };
+        if let Some((_, previous)) = pending.remove(uri) {
+            previous.abort();
+        }
+
+        let task = self.runtime.spawn(async move {
+            tokio::time::sleep(delay).await;
+            job().await;
+
+            if let Ok(mut pending) = tasks.lock()
+                && pending.get(&key).is_some_and(|(g, _)| *g == generation)
+            {
+                pending.remove(&key);
+            }
+        });
+
+        pending.insert(uri.to_string(), (generation, task));
+    }
+
+    /// Drops the pending or running job for `uri`. Returns whether there was one.
+    pub fn cancel(&self, uri: &str) -> bool {
+        self.tasks.lock()
+            .ok()
+            .and_then(|mut pending| pending.remove(uri))
+            .map(|(_, task)| task.abort())
+            .is_some()
+    }
+
+    pub fn cancel_all(&self) {
+        if let Ok(mut pending) = self.tasks.lock() {
+            for (_, (_, task)) in pending.drain() {
+                task.abort();
+            }
+        }
+    }
+
+    pub fn is_scheduled(&self, uri: &str) -> bool {
+        self.tasks.lock()
+            .map(|pending| pending.contains_key(uri))
+            .unwrap_or(false)
+    }
+}
+
+impl Drop for CompileScheduler {
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use axum::Router;
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, Json, Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::{get, post};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot, watch};
+use crate::artifact::{ArtifactCache, ArtifactData};
+use crate::backend_traits::{BackendError, CompilationBackend, CompilationRequest, CompilationResult, ContextEngineChoice, ProjectFile};
+use crate::delta;
+use crate::protocol::{
+    self, Capabilities, CompileRequest, CompileResponse, DocumentVersion, ErrorBody, ErrorCode, HealthResponse,
+    JobState, JobStatus, RemoteDiagnostic, CLIENT_HEADER, PROTOCOL_VERSION, VERSION_HEADER,
+};
+use crate::job_queue::{JobPriority, JobQueue, DEFAULT_MAX_CONCURRENT_JOBS};
+use crate::progress::CompileEvent;
+
+/// Requests larger than this are rejected, which bounds memory per request.
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+pub const DEFAULT_JOB_RETENTION: Duration = Duration::from_secs(10 * 60);
+pub const DEFAULT_DOCUMENT_RETENTION: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_MAX_DOCUMENTS: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Clients must send `Authorization: Bearer <token>`. `None` accepts everyone.
+    pub auth_token: Option<String>,
+    /// Where compiled PDFs are kept for `/artifacts/{hash}`.
+    pub artifact_dir: PathBuf,
+    pub max_concurrent: usize,
+    pub max_request_bytes: usize,
+    /// Reported by `/capabilities`; empty when unknown.
+    pub engines: Vec<ContextEngineChoice>,
+    /// How long a finished job stays available at `/jobs/{id}`.
+    pub job_retention: Duration,
+    /// How long a document is kept after its last compile: its version as the base
+    /// of deltas, and the backend's working files.
+    pub document_retention: Duration,
+    /// How many documents are kept at most; the least recently compiled go first.
+    pub max_documents: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            auth_token: None,
+            artifact_dir: std::env::temp_dir().join("context-server").join("artifacts"),
+            max_concurrent: DEFAULT_MAX_CONCURRENT_JOBS,
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            engines: Vec::new(),
+            job_retention: DEFAULT_JOB_RETENTION,
+            document_retention: DEFAULT_DOCUMENT_RETENTION,
+            max_documents: DEFAULT_MAX_DOCUMENTS,
+        }
+    }
+}
+
+/// Serves the remote compile [`protocol`](crate::protocol) on top of any backend,
+/// usually a `LocalBackend`. `POST /compile` answers with an `output_url` pointing at
+/// `/artifacts/{hash}`.
+///
+/// Compilations go through a `JobQueue`, so at most `max_concurrent` run at once and
+/// a newer request for the same document replaces one that hasn't started yet. This
+/// holds for `/compile` and `/jobs` alike. Documents are told apart by the client id
+/// in [`CLIENT_HEADER`] and their `uri`, and each one is compiled under its own job id,
+/// so clients never see each other's queued jobs, delta bases or files.
+#[derive(Debug)]
+pub struct CompileServer {
+    backend: Arc<dyn CompilationBackend>,
+    jobs: JobQueue,
+    remote_jobs: Mutex<HashMap<String, Arc<RemoteJob>>>,
+    // The last version of each document, by client and URI, that deltas can be based on.
+    documents: Mutex<HashMap<String, StoredDocument>>,
+    // When each document was last compiled, to forget idle ones.
+    last_used: Mutex<HashMap<String, Instant>>,
+    artifacts: ArtifactCache,
+    config: ServerConfig,
+}
+
+#[derive(Debug)]
+struct StoredDocument {
+    version: DocumentVersion,
+    content: String,
+    files: Vec<ProjectFile>,
+}
+
+// A compilation submitted to `/jobs`. Clients watch `status` until it is terminal.
+#[derive(Debug)]
+struct RemoteJob {
+    status: watch::Sender<JobStatus>,
+    finished_at: OnceLock<Instant>,
+}
+
+impl CompileServer {
+    /// Must be called within a Tokio runtime, which runs the compilations.
+    pub fn new(backend: Box<dyn CompilationBackend>, config: ServerConfig) -> Arc<Self> {
+        Arc::new(Self {
+            backend: Arc::from(backend),
+            jobs: JobQueue::new(tokio::runtime::Handle::current(), config.max_concurrent),
+            remote_jobs: Mutex::new(HashMap::new()),
+            documents: Mutex::new(HashMap::new()),
+            last_used: Mutex::new(HashMap::new()),
+            artifacts: ArtifactCache::new(config.artifact_dir.clone()),
+            config,
+        })
+    }
+
+    pub fn router(self: &Arc<Self>) -> Router {
+        Router::new()
+            .route("/compile", post(compile))
+            .route("/artifacts/{hash}", get(artifact))
+            .route("/health", get(health))
+            .route("/capabilities", get(capabilities))
+            .route("/jobs", post(submit_job))
+            .route("/jobs/{id}", get(job_status).delete(cancel_job))
+            .route("/jobs/{id}/events", get(job_events))
+            .layer(DefaultBodyLimit::max(self.config.max_request_bytes))
+            .with_state(Arc::clone(self))
+    }
+
+    /// Serves requests on `listener` until the task is dropped.
+    pub async fn serve(self: &Arc<Self>, listener: TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+
+    // The rejection for requests with an unsupported protocol version or a wrong token.
+    fn admit(&self, headers: &HeaderMap) -> Option<Response> {
+        check_version(headers).or_else(|| {
+            (!self.authorized(headers))
+                .then(|| error_response(StatusCode::UNAUTHORIZED, ErrorCode::Unauthorized, "Missing or invalid bearer token"))
+        })
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.config.auth_token else { return true };
+        headers.get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes()))
+    }
+
+    // Rebuilds a delta from its base and remembers versioned documents under `scope`.
+    // The error says why the base can't be used.
+    fn resolve(&self, scope: &str, mut body: CompileRequest) -> Result<CompileRequest, String> {
+        let Some(version) = body.version.clone() else {
+            return Ok(body);
+        };
+        let mut documents = self.documents.lock().map_err(|_| "Document store unavailable".to_string())?;
+
+        if let Some(base) = body.base.take() {
+            let stored = documents.get(scope)
+                .filter(|stored| stored.version == base)
+                .ok_or_else(|| format!("Version {} of {} is not available", base.version, body.uri))?;
+            body.content = delta::apply(&stored.content, &body.delta)
+                .ok_or_else(|| "The delta doesn't apply to its base".to_string())?;
+
+            let mut files = Vec::with_capacity(body.unchanged_files.len() + body.files.len());
+            for path in &body.unchanged_files {
+                let file = stored.files.iter().find(|file| &file.path == path)
+                    .ok_or_else(|| format!("The base has no file {}", path))?;
+                files.push(file.clone());
+            }
+            files.append(&mut body.files);
+            body.files = files;
+        } else if !body.delta.is_empty() || !body.unchanged_files.is_empty() {
+            return Err("A delta needs a base".to_string());
+        }
+        body.delta.clear();
+        body.unchanged_files.clear();
+
+        if delta::document_hash(&body.content, &body.files) != version.hash {
+            return Err(format!("Version {} of {} doesn't match its hash", version.version, body.uri));
+        }
+
+        documents.insert(scope.to_string(), StoredDocument {
+            version,
+            content: body.content.clone(),
+            files: body.files.clone(),
+        });
+        Ok(body)
+    }
+
+    // Marks `scope` as just compiled and forgets documents idle for longer than
+    // `document_retention`, then the least recently compiled beyond `max_documents`:
+    // their delta bases and the backend's working files. The files are released by a
+    // job of the document's own, which the queue runs only after the document's
+    // running and queued compiles, so no compile loses its files halfway through.
+    fn touch(&self, scope: &str) {
+        let evicted = {
+            let Ok(mut last_used) = self.last_used.lock() else { return };
+            last_used.insert(scope.to_string(), Instant::now());
+
+            let retention = self.config.document_retention;
+            let mut by_age: Vec<(String, Instant)> = last_used.iter()
+                .map(|(scope, used)| (scope.clone(), *used))
+                .collect();
+            by_age.sort_by_key(|(_, used)| *used);
+            let excess = by_age.len().saturating_sub(self.config.max_documents.max(1));
+            let evicted: Vec<String> = by_age.into_iter()
+                .enumerate()
+                .filter(|(index, (_, used))| *index < excess || used.elapsed() >= retention)
+                .map(|(_, (scope, _))| scope)
+                .collect();
+            for scope in &evicted {
+                last_used.remove(scope);
+            }
+            evicted
+        };
+        if evicted.is_empty() {
+            return;
+        }
+
+        if let Ok(mut documents) = self.documents.lock() {
+            for scope in &evicted {
+                documents.remove(scope);
+            }
+        }
+        for scope in evicted {
+            let backend = Arc::clone(&self.backend);
+            let job_id = scope.clone();
+            self.jobs.submit(format!("release-{}", uuid::Uuid::new_v4()), &scope, JobPriority::Normal, move |_| async move {
+                backend.release(&job_id).await;
+            });
+        }
+    }
+
+    async fn run(&self, scope: String, body: CompileRequest) -> Result<CompilationResult, BackendError> {
+        let (sender, receiver) = oneshot::channel();
+        let backend = Arc::clone(&self.backend);
+        let request = CompilationRequest {
+            content: body.content,
+            job_id: scope.clone(),
+            files: body.files,
+            options: body.options,
+            ..Default::default()
+        };
+
+        let cancel = self.jobs.submit_replacing(uuid::Uuid::new_v4().to_string(), &scope, JobPriority::Normal, move |cancel| async move {
+            let result = backend.compile(CompilationRequest { cancel, ..request }).await;
+            let _ = sender.send(result);
+        });
+
+        // Dropping this future (the client went away) cancels the job.
+        let _cancel_on_drop = cancel.drop_guard();
+        receiver.await.unwrap_or(Err(BackendError::Cancelled))
+    }
+
+    fn submit_job(self: &Arc<Self>, scope: String, body: CompileRequest) -> JobStatus {
+        let id = uuid::Uuid::new_v4().to_string();
+        let job = Arc::new(RemoteJob {
+            status: watch::channel(JobStatus::queued(id.clone())).0,
+            finished_at: OnceLock::new(),
+        });
+
+        if let Ok(mut remote_jobs) = self.remote_jobs.lock() {
+            let retention = self.config.job_retention;
+            remote_jobs.retain(|_, job| job.finished_at.get().is_none_or(|finished| finished.elapsed() < retention));
+            remote_jobs.insert(id.clone(), Arc::clone(&job));
+        }
+
+        let server = Arc::clone(self);
+        let tracked = Arc::clone(&job);
+        let request = CompilationRequest {
+            content: body.content,
+            job_id: scope.clone(),
+            files: body.files,
+            options: body.options,
+            ..Default::default()
+        };
+
+        self.jobs.submit_replacing(id, &scope, JobPriority::Normal, move |cancel| async move {
+            if !cancel.is_cancelled() {
+                tracked.status.send_modify(|status| status.state = JobState::Running);
+            }
+
+            let (events, mut receiver) = mpsc::unbounded_channel();
+            let compile = server.backend.compile(CompilationRequest { cancel, events: Some(events), ..request });
+            let forward_progress = async {
+                while let Some(event) = receiver.recv().await {
+                    if let CompileEvent::Progress(progress) = event {
+                        tracked.status.send_modify(|status| status.progress = Some(progress));
+                    }
+                }
+            };
+            let (result, ()) = tokio::join!(compile, forward_progress);
+
+            let outcome = result.and_then(|result| server.respond(result)).or_else(failed_compilation);
+            tracked.status.send_modify(|status| match outcome {
+                Ok(response) => {
+                    status.state = JobState::Finished;
+                    status.result = Some(response);
+                }
+                Err(BackendError::Cancelled) => status.state = JobState::Cancelled,
+                Err(e) => {
+                    log::warn!("Job {} failed: {}", status.job_id, e);
+                    status.state = JobState::Failed;
+                    status.error = Some(error_body(e).1);
+                }
+            });
+            let _ = tracked.finished_at.set(Instant::now());
+        });
+
+        job.status.borrow().clone()
+    }
+
+    fn remote_job(&self, id: &str) -> Option<Arc<RemoteJob>> {
+        self.remote_jobs.lock().ok()?.get(id).cloned()
+    }
+
+    // Moves the PDF into the artifact store and builds the protocol response.
+    fn respond(&self, result: CompilationResult) -> Result<CompileResponse, BackendError> {
+        let stored = match result.artifact.map(|artifact| artifact.data) {
+            Some(ArtifactData::Bytes(bytes)) => Some(self.artifacts.store(&bytes)),
+            Some(ArtifactData::File(path)) => Some(std::fs::read(&path).and_then(|bytes| self.artifacts.store(&bytes))),
+            Some(ArtifactData::Url(_)) | None => None,
+        };
+        let stored = stored.transpose().map_err(|e| BackendError::IO(e.to_string()))?;
+
+        let diagnostics = result.errors.into_iter().map(|e| RemoteDiagnostic::from_error(e, "error"))
+            .chain(result.warnings.into_iter().map(|w| RemoteDiagnostic::from_error(w, "warning")))
+            .collect();
+
+        Ok(CompileResponse {
+            success: result.success,
+            log: result.log,
+            output_url: stored.as_ref()
+                .and_then(|artifact| artifact.content_hash.as_ref())
+                .map(|hash| format!("/artifacts/{}", hash)),
+            output_hash: stored.and_then(|artifact| artifact.content_hash),
+            diagnostics,
+        })
+    }
+}
+
+async fn compile(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Json(body): Json<CompileRequest>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers).or_else(|| check_request(&body)) {
+        return rejection;
+    }
+    let scope = document_scope(&headers, &body.uri);
+    server.touch(&scope);
+    let body = match server.resolve(&scope, body) {
+        Ok(body) => body,
+        Err(message) => return error_response(StatusCode::PRECONDITION_FAILED, ErrorCode::BaseMismatch, &message),
+    };
+
+    log::info!("Compiling {} ({} bytes, {} files)", body.uri, body.content.len(), body.files.len());
+    let result = server.run(scope, body).await
+        .and_then(|result| server.respond(result))
+        .or_else(failed_compilation);
+
+    match result {
+        Ok(response) => versioned(Json(response)),
+        Err(e) => {
+            log::warn!("Compilation failed: {}", e);
+            let (status, body) = error_body(e);
+            versioned((status, Json(body)))
+        }
+    }
+}
+
+async fn submit_job(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Json(body): Json<CompileRequest>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers).or_else(|| check_request(&body)) {
+        return rejection;
+    }
+    let scope = document_scope(&headers, &body.uri);
+    server.touch(&scope);
+    let body = match server.resolve(&scope, body) {
+        Ok(body) => body,
+        Err(message) => return error_response(StatusCode::PRECONDITION_FAILED, ErrorCode::BaseMismatch, &message),
+    };
+
+    log::info!("Queueing {} ({} bytes, {} files)", body.uri, body.content.len(), body.files.len());
+    versioned((StatusCode::ACCEPTED, Json(server.submit_job(scope, body))))
+}
+
+async fn job_status(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers) {
+        return rejection;
+    }
+    match server.remote_job(&id) {
+        Some(job) => versioned(Json(job.status.borrow().clone())),
+        None => error_response(StatusCode::NOT_FOUND, ErrorCode::NotFound, "Job not found"),
+    }
+}
+
+// Sends the current status right away, then every change until the job ends.
+async fn job_events(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers) {
+        return rejection;
+    }
+    let Some(job) = server.remote_job(&id) else {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::NotFound, "Job not found");
+    };
+
+    let events = futures_util::stream::unfold((job.status.subscribe(), true), |(mut receiver, first)| async move {
+        if !first && (receiver.borrow().state.is_terminal() || receiver.changed().await.is_err()) {
+            return None;
+        }
+        let status = receiver.borrow_and_update().clone();
+        let event = Event::default().event("status").json_data(&status)
+            .unwrap_or_else(|_| Event::default().comment("unserializable status"));
+        Some((Ok::<_, Infallible>(event), (receiver, false)))
+    });
+    versioned(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+// Cancellation is asynchronous: the returned status may still be running.
+async fn cancel_job(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers) {
+        return rejection;
+    }
+    let Some(job) = server.remote_job(&id) else {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::NotFound, "Job not found");
+    };
+
+    server.jobs.cancel(&id);
+    versioned(Json(job.status.borrow().clone()))
+}
+
+async fn artifact(
+    State(server): State<Arc<CompileServer>>,
+    headers: HeaderMap,
+    Path(hash): Path<String>,
+) -> Response {
+    if let Some(rejection) = server.admit(&headers) {
+        return rejection;
+    }
+
+    let Some(path) = server.artifacts.lookup(&hash).and_then(|artifact| artifact.path().map(PathBuf::from)) else {
+        return error_response(StatusCode::NOT_FOUND, ErrorCode::NotFound, "Artifact not found");
+    };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => versioned(([(header::CONTENT_TYPE, "application/pdf")], Body::from(bytes))),
+        Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal, &e.to_string()),
+    }
+}
+
+async fn health(State(server): State<Arc<CompileServer>>) -> Response {
+    match server.backend.health_check().await {
+        Ok(()) => versioned(Json(HealthResponse { status: "ok".to_string(), error: None })),
+        Err(e) => {
+            let body = HealthResponse { status: "unavailable".to_string(), error: Some(e.to_string()) };
+            versioned((StatusCode::SERVICE_UNAVAILABLE, Json(body)))
+        }
+    }
+}
+
+async fn capabilities(State(server): State<Arc<CompileServer>>) -> Response {
+    let server_name = format!("context-server {}", env!("CARGO_PKG_VERSION"));
+    versioned(Json(Capabilities::current(server_name, server.config.engines.clone())))
+}
+
+// The rejection for an unsupported version. Requests without the header come from
+// clients that predate it, which spoke version 1.
+fn check_version(headers: &HeaderMap) -> Option<Response> {
+    let value = headers.get(VERSION_HEADER)?;
+    match value.to_str().ok().and_then(|v| v.trim().parse::<u32>().ok()) {
+        Some(version) if protocol::is_supported_version(version) => None,
+        _ => Some(error_response(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::UnsupportedVersion,
+            &format!(
+                "Unsupported protocol version {:?}, this server speaks {}-{}",
+                value, protocol::MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ),
+        )),
+    }
+}
+
+// Documents are kept per client, see `CompileServer`. The client id comes first and
+// can't contain a newline, so no two clients share a scope.
+fn document_scope(headers: &HeaderMap, uri: &str) -> String {
+    let client = headers.get(CLIENT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    format!("{}\n{}", client, uri)
+}
+
+// Limits and sandbox violations are the document's fault, so they are reported as a
+// failed compilation rather than a server error.
+fn failed_compilation(error: BackendError) -> Result<CompileResponse, BackendError> {
+    let message = error.to_string();
+    let log = match error {
+        BackendError::Timeout { log, .. }
+        | BackendError::ResourceExceeded { log, .. }
+        | BackendError::PolicyViolation { log, .. } => log,
+        other => return Err(other),
+    };
+
+    Ok(CompileResponse {
+        success: false,
+        log: format!("{}\n\n{}", message, log),
+        output_url: None,
+        output_hash: None,
+        diagnostics: vec![RemoteDiagnostic { message, severity: "error".to_string(), range: None }],
+    })
+}
+
+fn check_request(body: &CompileRequest) -> Option<Response> {
+    if body.format != "pdf" {
+        return Some(error_response(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::BadRequest,
+            &format!("Unsupported output format: {}", body.format),
+        ));
+    }
+    // Clients can't point the compile at files of the server's.
+    body.options.validate_untrusted().err().map(|e| {
+        let (status, body) = error_body(e);
+        versioned((status, Json(body)))
+    })
+}
+
+fn error_body(error: BackendError) -> (StatusCode, ErrorBody) {
+    let (status, code) = match &error {
+        BackendError::Cancelled => (StatusCode::CONFLICT, ErrorCode::Superseded),
+        BackendError::InvalidOptions(_) => (StatusCode::BAD_REQUEST, ErrorCode::InvalidOptions),
+        BackendError::Unavailable(_) | BackendError::Setup(_) => (StatusCode::SERVICE_UNAVAILABLE, ErrorCode::Unavailable),
+        BackendError::Network(_) => (StatusCode::BAD_GATEWAY, ErrorCode::Unavailable),
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::Internal),
+    };
+    let message = match error {
+        BackendError::InvalidOptions(message) => message,
+        other => other.to_string(),
+    };
+    (status, ErrorBody::new(code, message))
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: &str) -> Response {
+    versioned((status, Json(ErrorBody::new(code, message))))
+}
+
+fn versioned(response: impl IntoResponse) -> Response {
+    ([(VERSION_HEADER, PROTOCOL_VERSION.to_string())], response).into_response()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use flate2::read::GzDecoder;
+use thiserror::Error;
+
+// SyncTeX stores positions in scaled points (multiplied by `Unit`).
+// 65536 sp per TeX point, 72.27 TeX points per inch, 72 PDF points per inch.
+const SP_PER_BP: f64 = 65536.0 * 72.27 / 72.0;
+
+#[derive(Debug, Error)]
+pub enum SyncTexError {
+    #[error("IO Error: {0}")]
+    IO(String),
+    #[error("Malformed synctex data: {0}")]
+    Format(String),
+}
+
+/// A rectangle on a PDF page, in PDF points measured from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PdfRect {
+    pub page: u32,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PdfRect {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+
+    fn distance_to(&self, x: f64, y: f64) -> f64 {
+        let dx = (self.x - x).max(0.0).max(x - (self.x + self.width));
+        let dy = (self.y - y).max(0.0).max(y - (self.y + self.height));
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    fn area(&self) -> f64 {
+        self.width * self.height
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcePosition {
+    pub file: PathBuf,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    HBox,
+    VBox,
+    VoidHBox,
+    VoidVBox,
+    Kern,
+    Glue,
+    Math,
+    Current,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncRecord {
+    pub kind: RecordKind,
+    pub input: u32,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub rect: PdfRect,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncTex {
+    inputs: HashMap<u32, PathBuf>,
+    records: Vec<SyncRecord>,
+    main_input: Option<u32>,
+}
+
+impl SyncTex {
+    /// Loads a `.synctex.gz` or plain `.synctex` file.
+    pub fn load(path: &Path) -> Result<Self, SyncTexError> {
+        let raw = std::fs::read(path).map_err(|e| SyncTexError::IO(e.to_string()))?;
+
+        let text = if path.extension().is_some_and(|ext| ext == "gz") {
+            let mut decoded = String::new();
+            GzDecoder::new(raw.as_slice())
+                .read_to_string(&mut decoded)
+                .map_err(|e| SyncTexError::IO(e.to_string()))?;
+            decoded
+        } else {
+            String::from_utf8_lossy(&raw).into_owned()
+        };
+
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SyncTexError> {
+        let mut synctex = SyncTex::default();
+        let mut unit = 1.0;
+        let mut magnification = 1.0;
+        let mut x_offset = 0.0;
+        let mut y_offset = 0.0;
+        let mut page = 0;
+        let mut in_content = false;
+
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("Input:") {
+                let (tag, path) = rest.split_once(':')
+                    .ok_or_else(|| SyncTexError::Format(format!("bad input line: {}", line)))?;
+                let tag = tag.parse()
+                    .map_err(|_| SyncTexError::Format(format!("bad input tag: {}", line)))?;
+                synctex.inputs.insert(tag, PathBuf::from(path));
+                continue;
+            }
+
+            if !in_content {
+                if let Some(value) = line.strip_prefix("Unit:") {
+                    unit = value.trim().parse().unwrap_or(1.0);
+                } else if let Some(value) = line.strip_prefix("Magnification:") {
+                    magnification = value.trim().parse::<f64>().unwrap_or(1000.0) / 1000.0;
+                } else if let Some(value) = line.strip_prefix("X Offset:") {
+                    x_offset = value.trim().parse().unwrap_or(0.0);
+                } else if let Some(value) = line.strip_prefix("Y Offset:") {
+                    y_offset = value.trim().parse().unwrap_or(0.0);
+                } else if line.starts_with("Content:") {
+                    in_content = true;
+                }
+                continue;
+            }
+
+            if line.starts_with("Postamble:") {
+                break;
+            }
+
+            let Some(first) = line.chars().next() else { continue };
+            let rest = &line[first.len_utf8()..];
+
+            let kind = match first {
+                '{' => {
+                    page = rest.parse()
+                        .map_err(|_| SyncTexError::Format(format!("bad page line: {}", line)))?;
+                    continue;
+                }
+                '[' => RecordKind::VBox,
+                '(' => RecordKind::HBox,
+                'v' => RecordKind::VoidVBox,
+                'h' => RecordKind::VoidHBox,
+                'k' => RecordKind::Kern,
+                'g' => RecordKind::Glue,
+                '$' => RecordKind::Math,
+                'x' => RecordKind::Current,
+                _ => continue,
+            };
+
+            let to_bp = |value: f64, offset: f64| (value * unit + offset) * magnification / SP_PER_BP;
+
+            if let Some(record) = parse_record(kind, rest) {
+                let top = record.y - record.height;
+                synctex.records.push(SyncRecord {
+                    kind,
+                    input: record.input,
+                    line: record.line,
+                    column: record.column,
+                    rect: PdfRect {
+                        page,
+                        x: to_bp(record.x, x_offset),
+                        y: to_bp(top, y_offset),
+                        width: to_bp(record.width, 0.0),
+                        height: to_bp(record.height + record.depth, 0.0),
+                    },
+                });
+            }
+        }
+
+        if !in_content {
+            return Err(SyncTexError::Format("missing Content section".into()));
+        }
+
+        Ok(synctex)
+    }
+
+    /// Marks the input whose file name matches `source_file` as the main document.
+    pub fn with_main_input(mut self, source_file: &Path) -> Self {
+        self.main_input = self.inputs.iter()
+            .filter(|(_, path)| path.file_name() == source_file.file_name())
+            .map(|(tag, _)| *tag)
+            .min();
+        self
+    }
+
+    pub fn main_input(&self) -> Option<u32> {
+        self.main_input
+    }
+
+    pub fn input_path(&self, tag: u32) -> Option<&Path> {
+        self.inputs.get(&tag).map(PathBuf::as_path)
+    }
+
+    pub fn records(&self) -> &[SyncRecord] {
+        &self.records
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.records.iter().map(|r| r.rect.page).max().unwrap_or(0)
+    }
+
+    /// Finds the PDF area produced by `line` (1-based) of the given input.
+    /// Falls back to the nearest line with output when the exact line produced none.
+    pub fn forward_search(&self, input: u32, line: u32, column: u32) -> Option<PdfRect> {
+        self.records.iter()
+            .filter(|r| r.input == input && r.rect.page > 0)
+            .min_by_key(|r| {
+                let line_distance = r.line.abs_diff(line);
+                let column_distance = r.column.map_or(0, |c| c.abs_diff(column));
+                // Prefer boxes with actual extent over kerns/glue at the same position.
+                let is_point = matches!(r.kind, RecordKind::Kern | RecordKind::Glue | RecordKind::Math | RecordKind::Current);
+                (line_distance, column_distance, is_point, r.rect.page)
+            })
+            .map(|r| r.rect)
+    }
+
+    /// Finds the source position for a point on `page` (1-based), in PDF points from the top-left.
+    pub fn inverse_search(&self, page: u32, x: f64, y: f64) -> Option<SourcePosition> {
+        let on_page = || self.records.iter().filter(|r| r.rect.page == page && r.line > 0);
+
+        let best = on_page()
+            .filter(|r| r.rect.contains(x, y) && r.rect.area() > 0.0)
+            .min_by(|a, b| a.rect.area().total_cmp(&b.rect.area()))
+            .or_else(|| on_page().min_by(|a, b| {
+                a.rect.distance_to(x, y).total_cmp(&b.rect.distance_to(x, y))
+            }))?;
+
+        Some(SourcePosition {
+            file: self.inputs.get(&best.input)?.clone(),
+            line: best.line,
+            column: best.column.unwrap_or(0),
+        })
+    }
+}
+
+struct RawRecord {
+    input: u32,
+    line: u32,
+    column: Option<u32>,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    depth: f64,
+}
+
+// Record bodies look like `tag,line[,column]:x,y[:W,H,D]`, kerns carry only `:W`.
+fn parse_record(kind: RecordKind, body: &str) -> Option<RawRecord> {
+    let mut sections = body.split(':');
+    let link: Vec<&str> = sections.next()?.split(',').collect();
+    let position: Vec<&str> = sections.next()?.split(',').collect();
+    let size: Vec<f64> = sections.next()
+        .map(|s| s.split(',').filter_map(|v| v.parse().ok()).collect())
+        .unwrap_or_default();
+
+    let input = link.first()?.parse().ok()?;
+    let line = link.get(1)?.parse().ok()?;
+    let column = link.get(2).and_then(|c| c.parse::<i64>().ok())
+        .and_then(|c| u32::try_from(c).ok());
+
+    let (width, height, depth) = match kind {
+        RecordKind::Kern => (size.first().copied().unwrap_or(0.0), 0.0, 0.0),
+        _ => (
+            size.first().copied().unwrap_or(0.0),
+            size.get(1).copied().unwrap_or(0.0),
+            size.get(2).copied().unwrap_or(0.0),
+        ),
+    };
+
+    Some(RawRecord {
+        input,
+        line,
+        column,
+        x: position.first()?.parse().ok()?,
+        y: position.get(1)?.parse().ok()?,
+        // Kerns can be negative; the rectangle always spans left to right.
+        width: width.abs(),
+        height,
+        depth,
+    })
+}
@@ -29,15 +29,16 @@ pub enum SyntaxKind {
 pub type SyntaxNode = RowanSyntaxNode<ConTeXtLanguage>;
 pub type SyntaxToken = RowanSyntaxToken<ConTeXtLanguage>;
 
+// Only the green tree is kept: it owns its text, and holding on to a `Bump`
+// would make documents `!Sync` and keep the runtime off worker threads.
 #[derive(Debug)]
 pub struct SyntaxTree {
-    arena: Bump,        
     green: GreenNode, 
 }
 
 impl SyntaxTree {
-    pub fn new(arena: Bump, green: GreenNode) -> Self {
-        Self { arena, green }
+    pub fn new(green: GreenNode) -> Self {
+        Self { green }
     }
     
     pub fn root(&self) -> SyntaxNode {
@@ -72,11 +73,7 @@ impl<'a> SyntaxTreeBuilder<'a> {
     }
     
     pub fn finish(self) -> SyntaxTree {
-        let arena = Bump::new();
-        SyntaxTree::new(
-            arena,
-            self.builder.finish()
-        )
+        SyntaxTree::new(self.builder.finish())
     }
 }
 
@@ -0,0 +1,187 @@
+//! A wasm-bindgen API over the [`analysis`](crate::analysis) core, for editors that
+//! run in a browser. Build it with
+//!
+//! ```text
+//! cargo build --lib --target wasm32-unknown-unknown --no-default-features --features wasm
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/debug/context_runtime.wasm
+//! ```
+//!
+//! Offsets are UTF-16 code units, like indices into JavaScript strings, and results
+//! are plain objects:
+//!
+//! ```js
+//! const doc = new ContextDocument("\\starttext \\section{Intro} \\stoptext");
+//! doc.update(20, 25, "Introduction");
+//! doc.outline();        // [{ level: 3, title: "Introduction", start: 11, end: 19 }]
+//! doc.completions(14);  // [{ label: "\\section", kind: "command", start: 11, end: 14 }, ...]
+//! ```
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use crate::analysis::{self, Analysis, CompletionKind};
+
+/// An open document, re-analysed after every edit.
+#[wasm_bindgen]
+pub struct ContextDocument {
+    source: String,
+    analysis: Analysis,
+}
+
+#[derive(Serialize)]
+struct HighlightJs {
+    start: usize,
+    end: usize,
+    kind: String,
+}
+
+#[derive(Serialize)]
+struct DiagnosticJs {
+    start: usize,
+    end: usize,
+    severity: String,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct OutlineItemJs {
+    level: u32,
+    title: String,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Serialize)]
+struct CompletionJs {
+    label: String,
+    kind: CompletionKind,
+    start: usize,
+    end: usize,
+}
+
+#[wasm_bindgen]
+impl ContextDocument {
+    #[wasm_bindgen(constructor)]
+    pub fn new(source: String) -> Self {
+        let analysis = Analysis::new(&source);
+        Self { source, analysis }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source(&self) -> String {
+        self.source.clone()
+    }
+
+    /// Replaces `start..end` with `text`.
+    pub fn update(&mut self, start: usize, end: usize, text: &str) -> Result<(), JsError> {
+        let range = match (byte_offset(&self.source, start), byte_offset(&self.source, end)) {
+            (Some(start), Some(end)) if start <= end => start..end,
+            _ => return Err(JsError::new(&format!("Invalid range {}..{}", start, end))),
+        };
+        self.source.replace_range(range, text);
+        self.analysis = Analysis::new(&self.source);
+        Ok(())
+    }
+
+    pub fn highlights(&self) -> Result<JsValue, JsError> {
+        let offsets = Utf16Offsets::new(&self.source);
+        let highlights: Vec<_> = self.analysis.highlights().into_iter()
+            .map(|highlight| HighlightJs {
+                start: offsets.get(highlight.range.start),
+                end: offsets.get(highlight.range.end),
+                kind: highlight.kind.to_string(),
+            })
+            .collect();
+        to_js(&highlights)
+    }
+
+    pub fn diagnostics(&self) -> Result<JsValue, JsError> {
+        let offsets = Utf16Offsets::new(&self.source);
+        let diagnostics: Vec<_> = self.analysis.diagnostics().into_iter()
+            .map(|diagnostic| DiagnosticJs {
+                start: offsets.get(diagnostic.range.start),
+                end: offsets.get(diagnostic.range.end),
+                severity: diagnostic.severity.to_string(),
+                message: diagnostic.message,
+            })
+            .collect();
+        to_js(&diagnostics)
+    }
+
+    /// The headings, in source order.
+    pub fn outline(&self) -> Result<JsValue, JsError> {
+        let offsets = Utf16Offsets::new(&self.source);
+        let items: Vec<_> = analysis::outline(&self.source).into_iter()
+            .map(|item| OutlineItemJs {
+                level: item.level,
+                title: item.title,
+                start: offsets.get(item.range.start),
+                end: offsets.get(item.range.end),
+            })
+            .collect();
+        to_js(&items)
+    }
+
+    /// Commands and environments completing the partial command before `offset`.
+    pub fn completions(&self, offset: usize) -> Result<JsValue, JsError> {
+        let Some(offset) = byte_offset(&self.source, offset) else {
+            return Err(JsError::new(&format!("Invalid offset {}", offset)));
+        };
+        let offsets = Utf16Offsets::new(&self.source);
+        let completions: Vec<_> = analysis::completions(&self.source, offset).into_iter()
+            .map(|completion| CompletionJs {
+                label: completion.label,
+                kind: completion.kind,
+                start: offsets.get(completion.range.start),
+                end: offsets.get(completion.range.end),
+            })
+            .collect();
+        to_js(&completions)
+    }
+}
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    serde_wasm_bindgen::to_value(value).map_err(|e| JsError::new(&e.to_string()))
+}
+
+// The byte offset of a UTF-16 offset, `None` past the end or inside a character.
+fn byte_offset(source: &str, utf16: usize) -> Option<usize> {
+    let mut units = 0;
+    for (byte, c) in source.char_indices() {
+        if units == utf16 {
+            return Some(byte);
+        }
+        units += c.len_utf16();
+    }
+    (units == utf16).then_some(source.len())
+}
+
+// UTF-16 offsets of every byte offset, built once per request instead of counting
+// from the start for each result.
+struct Utf16Offsets {
+    // Empty for ASCII sources, where both are the same.
+    table: Vec<usize>,
+}
+
+impl Utf16Offsets {
+    fn new(source: &str) -> Self {
+        if source.is_ascii() {
+            return Self { table: Vec::new() };
+        }
+        let mut table = Vec::with_capacity(source.len() + 1);
+        let mut units = 0;
+        for c in source.chars() {
+            table.extend(std::iter::repeat_n(units, c.len_utf8()));
+            units += c.len_utf16();
+        }
+        table.push(units);
+        Self { table }
+    }
+
+    fn get(&self, byte: usize) -> usize {
+        match self.table.get(byte) {
+            Some(units) => *units,
+            None if self.table.is_empty() => byte,
+            None => *self.table.last().unwrap_or(&0),
+        }
+    }
+}
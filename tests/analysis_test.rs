@@ -0,0 +1,47 @@
+use context_runtime::analysis::{
+    completions, is_known_command, is_known_environment, outline, Analysis, CompletionKind, OutlineItem,
+};
+
+#[test]
+fn test_outline_lists_headings_in_order() {
+    let source = r"\starttext
+\chapter{Introduction}
+\section[sec:goals]{Goals}
+\startsection[title={Scope and limits}, reference=sec:scope]
+\stopsection
+\subject{Unnumbered}
+\stoptext";
+
+    let items = outline(source);
+    let titles: Vec<(u32, &str)> = items.iter().map(|item| (item.level, item.title.as_str())).collect();
+    assert_eq!(titles, [(2, "Introduction"), (3, "Goals"), (3, "Scope and limits"), (3, "Unnumbered")]);
+    assert_eq!(items[0], OutlineItem { level: 2, title: "Introduction".to_string(), range: 11..19 });
+    assert_eq!(&source[items[2].range.clone()], r"\startsection");
+}
+
+#[test]
+fn test_completions_match_the_partial_command() {
+    let source = r"\starttext \setuph";
+    let found = completions(source, source.len());
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].label, r"\setuphead");
+    assert_eq!(found[0].kind, CompletionKind::Command);
+    assert_eq!(&source[found[0].range.clone()], r"\setuph");
+
+    let environments: Vec<String> = completions(r"\startitem", 10).into_iter().map(|c| c.label).collect();
+    assert_eq!(environments, [r"\startitemize"]);
+
+    // Not after a command, or inside a character.
+    assert!(completions(r"\starttext plain", 16).is_empty());
+    assert!(completions("\\ü", 2).is_empty());
+}
+
+#[test]
+fn test_sections_are_known_commands() {
+    assert!(is_known_command("section") && is_known_command("setuphead"));
+    assert!(is_known_environment("chapter") && is_known_environment("itemize"));
+    assert!(!is_known_command("frobnicate"));
+
+    let analysis = Analysis::new(r"\starttext \section{Intro} \stoptext");
+    assert!(!analysis.highlights().is_empty());
+}
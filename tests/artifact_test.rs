@@ -0,0 +1,94 @@
+use context_runtime::artifact::{content_hash, count_pdf_pages, ArtifactData, CompilationArtifact};
+use tempfile::TempDir;
+
+const TWO_PAGE_PDF: &[u8] = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R >> endobj
+4 0 obj << /Type/Page /Parent 2 0 R >> endobj
+%%EOF
+";
+
+#[test]
+fn test_count_pdf_pages_ignores_page_tree_nodes() {
+    assert_eq!(count_pdf_pages(TWO_PAGE_PDF), Some(2));
+}
+
+#[test]
+fn test_count_pdf_pages_falls_back_to_count() {
+    let compressed = b"%PDF-1.5\n2 0 obj << /Type /Pages /Count 12 >> endobj\n%%EOF";
+    assert_eq!(count_pdf_pages(compressed), Some(12));
+    assert_eq!(count_pdf_pages(b"%PDF-1.5\n%%EOF"), None);
+}
+
+#[test]
+fn test_from_bytes_keeps_pdf_in_memory() {
+    let artifact = CompilationArtifact::from_bytes(TWO_PAGE_PDF.to_vec());
+
+    assert_eq!(artifact.bytes(), Some(TWO_PAGE_PDF));
+    assert_eq!(artifact.content_hash.as_deref(), Some(content_hash(TWO_PAGE_PDF).as_str()));
+    assert_eq!(artifact.page_count, Some(2));
+    assert_eq!(content_hash(TWO_PAGE_PDF).len(), 64);
+}
+
+#[test]
+fn test_persist_uses_content_addressed_names() {
+    let output_dir = TempDir::new().expect("Failed to create temp dir");
+
+    let first = CompilationArtifact::persist(TWO_PAGE_PDF, output_dir.path(), "report").unwrap();
+    let again = CompilationArtifact::persist(TWO_PAGE_PDF, output_dir.path(), "report").unwrap();
+    let changed = CompilationArtifact::persist(b"%PDF-1.4\n/Type /Page\n", output_dir.path(), "report").unwrap();
+
+    let first_path = first.path().expect("Persisted artifact should be a file");
+    assert!(first_path.starts_with(output_dir.path()));
+    assert!(first_path.file_name().unwrap().to_string_lossy().starts_with("report-"));
+    assert_eq!(std::fs::read(first_path).unwrap(), TWO_PAGE_PDF);
+    assert_eq!(first.data, again.data);
+    assert_ne!(first.data, changed.data);
+    assert!(matches!(changed.data, ArtifactData::File(ref path) if path.exists()));
+}
+
+#[test]
+fn test_concurrent_persists_of_the_same_pdf_all_succeed() {
+    let output_dir = TempDir::new().expect("Failed to create temp dir");
+    let pdf = TWO_PAGE_PDF.repeat(4096);
+
+    std::thread::scope(|scope| {
+        let writers: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| CompilationArtifact::persist(&pdf, output_dir.path(), "report")))
+            .collect();
+        for writer in writers {
+            let artifact = writer.join().unwrap().expect("Concurrent persist failed");
+            assert_eq!(std::fs::read(artifact.path().unwrap()).unwrap(), pdf);
+        }
+    });
+    assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_documents_with_similar_names_get_their_own_files() {
+    use context_runtime::backend_traits::{CompilationBackend, CompilationRequest, LocalBackend};
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    // Copies the source into the PDF after a pause, so both runs overlap.
+    let script = "#!/bin/sh\nfor last; do :; done\nsleep 0.2\n{ printf '%%PDF-1.4\\n'; cat \"$last\"; } > \"${last%.tex}.pdf\"\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let backend = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+    let compile = |job_id: &str, content: &str| backend.compile(CompilationRequest {
+        content: content.to_string(),
+        job_id: job_id.to_string(),
+        ..Default::default()
+    });
+    let (nested, flat) = tokio::join!(compile("a/b.tex", "nested"), compile("a_b.tex", "flat"));
+
+    let pdf = |result: context_runtime::backend_traits::CompilationResult| {
+        String::from_utf8_lossy(result.artifact.expect("No PDF").bytes().unwrap()).into_owned()
+    };
+    assert!(pdf(nested.expect("Compilation failed")).ends_with("nested"));
+    assert!(pdf(flat.expect("Compilation failed")).ends_with("flat"));
+}
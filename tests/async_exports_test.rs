@@ -0,0 +1,59 @@
+use std::net::TcpListener;
+use std::time::Duration;
+
+use context_runtime::ffi::ContextRuntimeHandle;
+use context_runtime::ffi_bridge::{ContextErrorFfi, RetryPolicyFfi, RuntimeConfigFfi};
+
+// The handle owns a tokio runtime, so the tests drive its futures from a separate
+// one, the way a foreign executor would.
+fn executor() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor")
+}
+
+#[test]
+fn test_compile_async_throws_for_missing_documents() {
+    let handle = ContextRuntimeHandle::new();
+    let result = executor().block_on(handle.compile_async("missing.tex".to_string()));
+    assert_eq!(result.map(|r| r.success), Err(ContextErrorFfi::DocumentNotFound { uri: "missing.tex".to_string() }));
+}
+
+#[test]
+fn test_dropping_compile_async_cancels_the_job() {
+    // Accepts connections but never answers, so the remote compilation hangs.
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(format!("http://{}", listener.local_addr().unwrap())),
+        remote_retry: Some(RetryPolicyFfi { max_attempts: 1, initial_backoff_ms: 0, max_backoff_ms: 0, jitter_percent: 0 }),
+        ..Default::default()
+    });
+    let uri = "hanging.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Waiting \stoptext".to_string()).expect("Failed to open");
+
+    let executor = executor();
+    let timed_out = executor.block_on(async {
+        tokio::time::timeout(Duration::from_millis(300), handle.compile_async(uri.clone())).await
+    });
+    assert!(timed_out.is_err(), "The compilation should still be running");
+
+    executor.block_on(async {
+        for _ in 0..50 {
+            if handle.get_active_jobs().is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("The job outlived its future: {:?}", handle.get_active_jobs());
+    });
+}
+
+#[test]
+fn test_backend_queries_are_awaitable() {
+    let handle = ContextRuntimeHandle::new();
+    let executor = executor();
+
+    let health = executor.block_on(handle.get_backend_health()).expect("Health check failed");
+    assert!(!health.is_empty());
+    executor.block_on(handle.discover_context_installations()).expect("Discovery failed");
+    executor.block_on(handle.get_context_installation()).expect("Lookup failed");
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use context_runtime::auth::{AuthProvider, ClientCertificate, Credentials, StaticCredentials};
+use context_runtime::backend_traits::{BackendError, CompileOptions};
+use context_runtime::protocol::CompileRequest;
+use context_runtime::remote_client::RemoteClient;
+use context_runtime::retry::RetryPolicy;
+use tokio_util::sync::CancellationToken;
+
+fn compile_request() -> CompileRequest {
+    CompileRequest {
+        uri: "auth.tex".to_string(),
+        content: r"\starttext Auth \stoptext".to_string(),
+        files: Vec::new(),
+        options: CompileOptions::default(),
+        ..Default::default()
+    }
+}
+
+// Hands out "stale" until refreshed, then "fresh".
+#[derive(Default)]
+struct ExpiringToken {
+    refreshes: AtomicUsize,
+}
+
+#[async_trait]
+impl AuthProvider for ExpiringToken {
+    async fn credentials(&self) -> Result<Option<Credentials>, BackendError> {
+        let token = if self.refreshes.load(Ordering::SeqCst) == 0 { "stale" } else { "fresh" };
+        Ok(Some(Credentials::Bearer(token.to_string())))
+    }
+
+    async fn refresh(&self, rejected: Option<&Credentials>) -> Result<Option<Credentials>, BackendError> {
+        assert_eq!(rejected, Some(&Credentials::Bearer("stale".to_string())));
+        self.refreshes.fetch_add(1, Ordering::SeqCst);
+        self.credentials().await
+    }
+}
+
+#[tokio::test]
+async fn test_unauthorized_request_is_repeated_after_refresh() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities").with_status(404).create_async().await;
+    let rejected = server.mock("POST", "/compile")
+        .match_header("authorization", "Bearer stale")
+        .with_status(401)
+        .expect(1)
+        .create_async()
+        .await;
+    let accepted = server.mock("POST", "/compile")
+        .match_header("authorization", "Bearer fresh")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({ "success": true, "log": "ok" }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+
+    let auth = Arc::new(ExpiringToken::default());
+    let client = RemoteClient::new(server.url(), None)
+        .with_auth(Arc::clone(&auth) as Arc<dyn AuthProvider>)
+        .expect("No client certificate to set up");
+    let compiled = client.compile(&compile_request(), &CancellationToken::new(), None).await
+        .expect("The refreshed token should be accepted");
+
+    assert!(compiled.response.success);
+    assert_eq!(auth.refreshes.load(Ordering::SeqCst), 1);
+    rejected.assert_async().await;
+    accepted.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_rejected_api_key_fails_without_retrying() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities").with_status(404).create_async().await;
+    let compile = server.mock("POST", "/compile")
+        .match_header("x-api-key", "revoked")
+        .with_status(401)
+        .expect(1)
+        .create_async()
+        .await;
+
+    // Static credentials have nothing to refresh to, so the first 401 is final.
+    let auth = StaticCredentials::new(Some(Credentials::ApiKey {
+        header: "X-Api-Key".to_string(),
+        key: "revoked".to_string(),
+    }));
+    let client = RemoteClient::new(server.url(), None)
+        .with_retry(RetryPolicy::none())
+        .with_auth(Arc::new(auth))
+        .expect("No client certificate to set up");
+    let result = client.compile(&compile_request(), &CancellationToken::new(), None).await;
+
+    assert!(matches!(result, Err(BackendError::Unauthorized(_))), "got {:?}", result.map(|c| c.response));
+    compile.assert_async().await;
+}
+
+#[test]
+fn test_invalid_client_certificate_is_a_setup_error() {
+    let auth = StaticCredentials::default().with_client_certificate(Some(ClientCertificate {
+        certificate_pem: b"not a certificate".to_vec(),
+        private_key_pem: b"not a key".to_vec(),
+    }));
+    let result = RemoteClient::new("https://compile.example".to_string(), None).with_auth(Arc::new(auth));
+
+    assert!(matches!(result, Err(BackendError::Setup(_))), "got {:?}", result);
+}
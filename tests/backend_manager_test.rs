@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use context_runtime::backend_manager::BackendManager;
+use context_runtime::backend_traits::{
+    BackendError, CompilationBackend, CompilationRequest, CompilationResult,
+};
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy)]
+enum Behaviour {
+    Succeed,
+    Offline,
+    RejectDocument,
+    RejectCredentials,
+}
+
+#[derive(Debug)]
+struct FakeBackend {
+    name: &'static str,
+    behaviour: Behaviour,
+    calls: Arc<AtomicUsize>,
+}
+
+fn fake(name: &'static str, behaviour: Behaviour) -> (Box<FakeBackend>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    (Box::new(FakeBackend { name, behaviour, calls: Arc::clone(&calls) }), calls)
+}
+
+#[async_trait]
+impl CompilationBackend for FakeBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, _request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        match self.behaviour {
+            Behaviour::Succeed => Ok(CompilationResult {
+                success: true,
+                artifact: None,
+                log: String::new(),
+                errors: vec![],
+                warnings: vec![],
+                synctex: None,
+                backend: self.name.to_string(),
+            }),
+            Behaviour::Offline => Err(BackendError::Network("connection refused".into())),
+            Behaviour::RejectDocument => Err(BackendError::Compilation("Server returned 400".into())),
+            Behaviour::RejectCredentials => Err(BackendError::Unauthorized("Server returned 401".into())),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        match self.behaviour {
+            Behaviour::Offline => Err(BackendError::Network("connection refused".into())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_falls_back_when_preferred_backend_is_offline() {
+    let (remote, remote_calls) = fake("remote", Behaviour::Offline);
+    let (local, local_calls) = fake("local", Behaviour::Succeed);
+    let manager = BackendManager::new().with_backend(remote).with_backend(local);
+
+    let result = manager.compile(CompilationRequest::default()).await.expect("Fallback failed");
+    assert_eq!(result.backend, "local");
+
+    // The offline backend is tried last while it is considered down.
+    manager.compile(CompilationRequest::default()).await.unwrap();
+    assert_eq!(remote_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(local_calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_document_errors_do_not_fall_back() {
+    let (remote, _) = fake("remote", Behaviour::RejectDocument);
+    let (local, local_calls) = fake("local", Behaviour::Succeed);
+    let manager = BackendManager::new().with_backend(remote).with_backend(local);
+
+    let result = manager.compile(CompilationRequest::default()).await;
+    assert!(matches!(result, Err(BackendError::Compilation(_))));
+    assert_eq!(local_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_rejected_credentials_do_not_fall_back() {
+    let (remote, _) = fake("remote", Behaviour::RejectCredentials);
+    let (local, local_calls) = fake("local", Behaviour::Succeed);
+    let manager = BackendManager::new().with_backend(remote).with_backend(local);
+
+    let result = manager.compile(CompilationRequest::default()).await;
+    assert!(matches!(result, Err(BackendError::Unauthorized(_))));
+    assert_eq!(local_calls.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn test_reports_setup_errors_and_health() {
+    let (remote, _) = fake("remote", Behaviour::Offline);
+    let manager = BackendManager::new()
+        .with_backend(remote)
+        .with_setup_error("local", BackendError::Unavailable("mtxrun executable not found".into()));
+
+    let report = manager.health_report().await;
+    let names: Vec<(&str, bool)> = report.iter().map(|h| (h.name.as_str(), h.healthy)).collect();
+    assert_eq!(names, vec![("remote", false), ("local", false)]);
+
+    match manager.compile(CompilationRequest::default()).await {
+        Err(BackendError::Unavailable(msg)) => {
+            assert!(msg.contains("mtxrun executable not found"));
+            assert!(msg.contains("connection refused"));
+        }
+        other => panic!("Expected unavailable, got {:?}", other.map(|r| r.success)),
+    }
+}
@@ -0,0 +1,50 @@
+/* Opens, edits, analyses and closes a document through the C API. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "context_runtime.h"
+
+#define CHECK(condition)                                                       \
+    do {                                                                       \
+        if (!(condition)) {                                                    \
+            const char *error = context_last_error();                          \
+            fprintf(stderr, "%s:%d: %s failed (%s)\n", __FILE__, __LINE__,     \
+                    #condition, error ? error : "no error");                   \
+            exit(1);                                                           \
+        }                                                                      \
+    } while (0)
+
+int main(void) {
+    ContextHandle *handle = context_runtime_new(NULL);
+    CHECK(handle != NULL);
+
+    CHECK(context_open(handle, "smoke.tex", "\\starttext Hello \\unknown \\stoptext") == CONTEXT_STATUS_OK);
+    CHECK(context_update(handle, "smoke.tex", 11, 16, "Hallo") == CONTEXT_STATUS_OK);
+
+    char *source = context_document_source(handle, "smoke.tex");
+    CHECK(source != NULL && strcmp(source, "\\starttext Hallo \\unknown \\stoptext") == 0);
+    context_string_free(source);
+
+    ContextHighlightList highlights = {0};
+    CHECK(context_highlights(handle, "smoke.tex", &highlights) == CONTEXT_STATUS_OK);
+    CHECK(highlights.len > 0);
+    context_highlight_list_free(&highlights);
+
+    ContextDiagnosticList diagnostics = {0};
+    CHECK(context_diagnostics(handle, "smoke.tex", &diagnostics) == CONTEXT_STATUS_OK);
+    for (size_t i = 0; i < diagnostics.len; i++) {
+        printf("diagnostic %zu..%zu: %s\n", diagnostics.items[i].start, diagnostics.items[i].end,
+               diagnostics.items[i].message);
+    }
+    context_diagnostic_list_free(&diagnostics);
+
+    CHECK(context_update(handle, "missing.tex", 0, 0, "x") == CONTEXT_STATUS_DOCUMENT_NOT_FOUND);
+    CHECK(context_update(handle, "smoke.tex", 0, 1000, "x") == CONTEXT_STATUS_INVALID_RANGE);
+
+    CHECK(context_close(handle, "smoke.tex") == CONTEXT_STATUS_OK);
+    context_runtime_free(handle);
+
+    printf("C API smoke test passed\n");
+    return 0;
+}
@@ -0,0 +1,80 @@
+#![cfg(unix)]
+
+use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationRequest, LocalBackend};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio_util::sync::CancellationToken;
+
+// Simulates mtxrun starting a long-running engine as a grandchild process.
+fn create_hanging_mtxrun(dir: &Path, pid_file: &Path) -> PathBuf {
+    let path = dir.join("mtxrun");
+    let script = format!("#!/bin/sh\nsleep 30 &\necho $! > '{}'\nwait\n", pid_file.display());
+    std::fs::write(&path, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to set executable permissions");
+    path
+}
+
+fn process_exists(pid: &str) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        // Zombies are already dead, they are just waiting to be reaped.
+        .map(|stat| !stat.contains(") Z "))
+        .unwrap_or(false)
+}
+
+#[tokio::test]
+async fn test_cancel_kills_mtxrun_process_tree() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let pid_file = temp_dir.path().join("engine.pid");
+    let mtxrun = create_hanging_mtxrun(temp_dir.path(), &pid_file);
+    let backend = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+
+    let cancel = CancellationToken::new();
+    let request = CompilationRequest {
+        content: r"\starttext Slow \stoptext".to_string(),
+        job_id: "slow.tex".to_string(),
+        cancel: cancel.clone(),
+        ..Default::default()
+    };
+
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        canceller.cancel();
+    });
+
+    let started = Instant::now();
+    let result = backend.compile(request).await;
+    assert!(matches!(result, Err(BackendError::Cancelled)), "Expected cancellation, got {:?}", result.map(|r| r.success));
+    assert!(started.elapsed() < Duration::from_secs(5), "Cancellation should not wait for the engine");
+
+    let pid = std::fs::read_to_string(&pid_file).expect("Engine pid not recorded");
+    let pid = pid.trim();
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while process_exists(pid) && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(!process_exists(pid), "Engine process {pid} survived cancellation");
+}
+
+#[tokio::test]
+async fn test_cancelled_request_does_not_start_compile() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let pid_file = temp_dir.path().join("engine.pid");
+    let mtxrun = create_hanging_mtxrun(temp_dir.path(), &pid_file);
+    let backend = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+
+    let request = CompilationRequest {
+        content: r"\starttext Never \stoptext".to_string(),
+        job_id: "never.tex".to_string(),
+        ..Default::default()
+    };
+    request.cancel.cancel();
+
+    let result = tokio::time::timeout(Duration::from_secs(5), backend.compile(request))
+        .await
+        .expect("Cancelled compile should return promptly");
+    assert!(matches!(result, Err(BackendError::Cancelled)));
+}
@@ -0,0 +1,66 @@
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use context_runtime::capi::*;
+
+fn c(value: &str) -> CString {
+    CString::new(value).unwrap()
+}
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(context_last_error()) }.to_string_lossy().into_owned()
+}
+
+#[test]
+fn test_documents_round_trip_through_the_c_api() {
+    unsafe {
+        let handle = context_runtime_new(ptr::null());
+        assert!(!handle.is_null());
+        let uri = c("capi.tex");
+
+        assert_eq!(context_open(handle, uri.as_ptr(), c(r"\starttext Hello \stoptext").as_ptr()), ContextStatus::Ok);
+        assert_eq!(context_update(handle, uri.as_ptr(), 11, 16, c("Hallo").as_ptr()), ContextStatus::Ok);
+
+        let source = context_document_source(handle, uri.as_ptr());
+        assert_eq!(CStr::from_ptr(source).to_str(), Ok(r"\starttext Hallo \stoptext"));
+        context_string_free(source);
+
+        let mut highlights = ContextHighlightList { items: ptr::null_mut(), len: 0 };
+        assert_eq!(context_highlights(handle, uri.as_ptr(), &mut highlights), ContextStatus::Ok);
+        assert!(highlights.len > 0);
+        let first = &*highlights.items;
+        assert!(first.start < first.end);
+        context_highlight_list_free(&mut highlights);
+        assert!(highlights.items.is_null());
+
+        let mut diagnostics = ContextDiagnosticList { items: ptr::null_mut(), len: 0 };
+        assert_eq!(context_diagnostics(handle, uri.as_ptr(), &mut diagnostics), ContextStatus::Ok);
+        context_diagnostic_list_free(&mut diagnostics);
+
+        assert_eq!(context_close(handle, uri.as_ptr()), ContextStatus::Ok);
+        assert!(context_document_source(handle, uri.as_ptr()).is_null());
+        context_runtime_free(handle);
+    }
+}
+
+#[test]
+fn test_failures_set_the_last_error() {
+    unsafe {
+        let handle = context_runtime_new(ptr::null());
+        let uri = c("errors.tex");
+
+        assert_eq!(context_update(handle, uri.as_ptr(), 0, 0, c("x").as_ptr()), ContextStatus::DocumentNotFound);
+        assert_eq!(last_error(), "Document not found: errors.tex");
+
+        context_open(handle, uri.as_ptr(), c("Grüße").as_ptr());
+        assert_eq!(context_update(handle, uri.as_ptr(), 3, 3, c("x").as_ptr()), ContextStatus::InvalidRange);
+        assert_eq!(context_update(handle, uri.as_ptr(), 0, 100, c("x").as_ptr()), ContextStatus::InvalidRange);
+        assert_eq!(context_open(handle, ptr::null(), c("x").as_ptr()), ContextStatus::InvalidArgument);
+        assert_eq!(last_error(), "`uri` is null");
+
+        let mut result = ptr::null_mut();
+        assert_eq!(context_compile(handle, c("missing.tex").as_ptr(), &mut result), ContextStatus::DocumentNotFound);
+        assert!(result.is_null());
+        context_runtime_free(handle);
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use context_runtime::artifact::CompilationArtifact;
+use context_runtime::backend_traits::{
+    BackendError, CompilationBackend, CompilationRequest, CompilationResult, ProjectFile,
+};
+use context_runtime::compile_cache::{cache_key, CachingBackend, CompileCacheLimits};
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts compilations and returns a PDF sized by `pdf_size`.
+#[derive(Debug)]
+struct CountingBackend {
+    calls: Arc<AtomicUsize>,
+    pdf_size: usize,
+}
+
+#[async_trait]
+impl CompilationBackend for CountingBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(CompilationResult {
+            success: true,
+            artifact: Some(CompilationArtifact::from_bytes(vec![b'%'; self.pdf_size])),
+            log: format!("compiled {}", request.job_id),
+            errors: vec![],
+            warnings: vec![],
+            synctex: None,
+            backend: "counting".to_string(),
+        })
+    }
+
+    fn fingerprint(&self) -> String {
+        "counting".to_string()
+    }
+}
+
+fn caching_backend(limits: CompileCacheLimits, pdf_size: usize) -> (CachingBackend, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let inner = CountingBackend { calls: Arc::clone(&calls), pdf_size };
+    (CachingBackend::new(Box::new(inner), limits), calls)
+}
+
+fn request(job_id: &str, content: &str) -> CompilationRequest {
+    CompilationRequest {
+        content: content.to_string(),
+        job_id: job_id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_identical_requests_hit_cache() {
+    let (backend, calls) = caching_backend(CompileCacheLimits::default(), 16);
+
+    let first = backend.compile(request("doc.tex", "A")).await.unwrap();
+    let second = backend.compile(request("doc.tex", "A")).await.unwrap();
+
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(first.artifact, second.artifact);
+    assert_eq!(backend.stats().hits, 1);
+    assert_eq!(backend.stats().misses, 1);
+
+    backend.compile(request("doc.tex", "B")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_cache_key_covers_included_files() {
+    let mut with_chapter = request("doc.tex", "A");
+    with_chapter.files.push(ProjectFile { path: "chapter.tex".into(), content: "one".into() });
+
+    let mut edited_chapter = with_chapter.clone();
+    edited_chapter.files[0].content = "two".into();
+
+    let base = cache_key(&request("doc.tex", "A"), "local");
+    assert_ne!(base, cache_key(&with_chapter, "local"));
+    assert_ne!(cache_key(&with_chapter, "local"), cache_key(&edited_chapter, "local"));
+    assert_ne!(base, cache_key(&request("doc.tex", "A"), "remote"));
+    assert_ne!(base, cache_key(&request("other.tex", "A"), "local"));
+}
+
+#[tokio::test]
+async fn test_least_recently_used_entry_is_evicted() {
+    let limits = CompileCacheLimits { max_entries: 2, ..Default::default() };
+    let (backend, calls) = caching_backend(limits, 16);
+
+    backend.compile(request("a.tex", "A")).await.unwrap();
+    backend.compile(request("b.tex", "B")).await.unwrap();
+    backend.compile(request("a.tex", "A")).await.unwrap(); // touch a
+    backend.compile(request("c.tex", "C")).await.unwrap(); // evicts b
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    backend.compile(request("a.tex", "A")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    backend.compile(request("b.tex", "B")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    assert_eq!(backend.stats().entries, 2);
+}
+
+#[tokio::test]
+async fn test_byte_limit_bounds_cache() {
+    let limits = CompileCacheLimits { max_entries: 10, max_bytes: 1024 };
+    let (backend, calls) = caching_backend(limits, 600);
+
+    backend.compile(request("a.tex", "A")).await.unwrap();
+    backend.compile(request("b.tex", "B")).await.unwrap();
+    assert!(backend.stats().bytes <= 1024);
+    assert_eq!(backend.stats().entries, 1);
+
+    backend.compile(request("a.tex", "A")).await.unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
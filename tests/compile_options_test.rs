@@ -0,0 +1,73 @@
+use context_runtime::backend_traits::{BackendError, CompileOptions, ContextEngineChoice};
+use std::collections::BTreeMap;
+
+fn options() -> CompileOptions {
+    CompileOptions {
+        engine: Some(ContextEngineChoice::Mkiv),
+        draft: true,
+        modes: vec!["screen".to_string()],
+        arguments: BTreeMap::from([("edition".to_string(), "2".to_string())]),
+        result: Some("handout".to_string()),
+        runs: Some(3),
+        environments: vec!["env-style".to_string()],
+    }
+}
+
+#[test]
+fn test_options_become_context_flags() {
+    assert!(CompileOptions::default().context_arguments().unwrap().is_empty());
+    assert_eq!(
+        options().context_arguments().unwrap(),
+        vec![
+            "--luatex",
+            "--once",
+            "--mode=screen,draft",
+            "--arguments=edition=2",
+            "--runs=3",
+            "--environment=env-style",
+            "--result=handout",
+        ]
+    );
+}
+
+#[test]
+fn test_rejects_options_that_break_flags() {
+    let with = |change: fn(&mut CompileOptions)| {
+        let mut options = options();
+        change(&mut options);
+        options.context_arguments()
+    };
+
+    assert!(matches!(with(|o| o.result = Some("../escape".into())), Err(BackendError::InvalidOptions(_))));
+    assert!(matches!(with(|o| o.modes.push("a,b".into())), Err(BackendError::InvalidOptions(_))));
+    assert!(matches!(with(|o| o.environments.push("env\tx".into())), Err(BackendError::InvalidOptions(_))));
+    assert!(matches!(with(|o| o.runs = Some(0)), Err(BackendError::InvalidOptions(_))));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_local_backend_passes_options_and_collects_renamed_result() {
+    use context_runtime::backend_traits::{CompilationBackend, CompilationRequest, LocalBackend};
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    // Echoes its arguments and writes the PDF under the `--result` name.
+    let script = "#!/bin/sh\necho \"args: $*\"\nresult=\nfor arg; do case \"$arg\" in --result=*) result=\"${arg#--result=}\";; esac; done\nprintf '%%PDF-1.4\\n/Type /Page\\n%%%%EOF\\n' > \"$result.pdf\"\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let backend = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+    let result = backend.compile(CompilationRequest {
+        content: r"\starttext Options \stoptext".to_string(),
+        job_id: "options.tex".to_string(),
+        options: options(),
+        ..Default::default()
+    })
+    .await
+    .expect("Compilation failed");
+
+    assert!(result.success);
+    assert!(result.log.contains("--luatex --once --mode=screen,draft"), "Unexpected log: {}", result.log);
+    assert!(result.artifact.is_some(), "PDF written under the result name was not collected");
+}
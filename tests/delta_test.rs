@@ -0,0 +1,145 @@
+use context_runtime::backend_traits::ProjectFile;
+use context_runtime::delta::{apply, diff, document_hash};
+use context_runtime::protocol::{CompileRequest, TextEdit};
+use context_runtime::remote_client::RemoteClient;
+use mockito::Matcher;
+use tokio_util::sync::CancellationToken;
+
+fn file(path: &str, content: &str) -> ProjectFile {
+    ProjectFile { path: path.to_string(), content: content.to_string() }
+}
+
+fn compile_request(content: &str, chapter: &str) -> CompileRequest {
+    CompileRequest {
+        uri: "book.tex".to_string(),
+        content: content.to_string(),
+        files: vec![file("env.tex", r"\setupbodyfont[11pt]"), file("chapter.tex", chapter)],
+        ..Default::default()
+    }
+}
+
+async fn mock_delta_capabilities(server: &mut mockito::ServerGuard) {
+    server.mock("GET", "/capabilities")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "protocol_version": 1,
+            "min_protocol_version": 1,
+            "features": ["project_files", "deltas"],
+        }).to_string())
+        .create_async()
+        .await;
+}
+
+const COMPILED: &str = r#"{ "success": true, "log": "ok" }"#;
+
+#[test]
+fn test_diff_round_trips_through_apply() {
+    let cases = [
+        ("", "new"),
+        ("same", "same"),
+        (r"\starttext Hello \stoptext", r"\starttext Hello, world \stoptext"),
+        ("Grüße", "Grüne"),
+        ("ααα", "αα"),
+        ("prefix only", "prefix"),
+    ];
+    for (old, new) in cases {
+        let edits = diff(old, new);
+        assert!(edits.len() <= 1);
+        assert_eq!(apply(old, &edits).as_deref(), Some(new), "{:?} -> {:?}", old, new);
+    }
+
+    assert_eq!(diff("abcdef", "abXYef"), [TextEdit { start: 2, end: 4, text: "XY".to_string() }]);
+    // Out of order or inside a character.
+    let bad = [TextEdit { start: 3, end: 4, text: String::new() }, TextEdit { start: 0, end: 1, text: String::new() }];
+    assert_eq!(apply("abcdef", &bad), None);
+    assert_eq!(apply("ü", &[TextEdit { start: 1, end: 2, text: String::new() }]), None);
+}
+
+#[test]
+fn test_document_hash_ignores_file_order() {
+    let files = [file("a.tex", "A"), file("b.tex", "B")];
+    let reversed = [file("b.tex", "B"), file("a.tex", "A")];
+    assert_eq!(document_hash("main", &files), document_hash("main", &reversed));
+    assert_ne!(document_hash("main", &files), document_hash("main", &files[..1]));
+    assert_ne!(document_hash("ab", &[]), document_hash("a", &[file("b", "")]));
+}
+
+#[tokio::test]
+async fn test_second_compile_sends_only_changes() {
+    let mut server = mockito::Server::new_async().await;
+    mock_delta_capabilities(&mut server).await;
+    let first = compile_request(r"\starttext \input chapter \stoptext", "Chapter one.");
+    let second = compile_request(r"\starttext \input chapter \page \stoptext", "Chapter one, revised.");
+
+    let full = server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "content": first.content,
+            "version": { "version": 1, "hash": document_hash(&first.content, &first.files) },
+        })))
+        .with_body(COMPILED)
+        .expect(1)
+        .create_async()
+        .await;
+    let delta = server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "content": "",
+            "files": [{ "path": "chapter.tex", "content": "Chapter one, revised." }],
+            "unchanged_files": ["env.tex"],
+            "base": { "version": 1 },
+            "version": { "version": 2, "hash": document_hash(&second.content, &second.files) },
+            "delta": [{ "start": 27, "end": 27, "text": r"page \" }],
+        })))
+        .with_body(COMPILED)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None);
+    let cancel = CancellationToken::new();
+    client.compile(&first, &cancel, None).await.expect("Full upload failed");
+    client.compile(&second, &cancel, None).await.expect("Delta upload failed");
+
+    full.assert_async().await;
+    delta.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_missing_base_falls_back_to_full_upload() {
+    let mut server = mockito::Server::new_async().await;
+    mock_delta_capabilities(&mut server).await;
+    let first = compile_request(r"\starttext \input chapter \stoptext", "Chapter one.");
+    let second = compile_request(r"\starttext \input chapter \page \stoptext", "Chapter one.");
+
+    server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({ "content": first.content })))
+        .with_body(COMPILED)
+        .create_async()
+        .await;
+    // The server restarted and lost version 1.
+    let rejected = server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({ "base": { "version": 1 } })))
+        .with_status(412)
+        .with_body(serde_json::json!({ "error": "Version 1 of book.tex is not available", "code": "base_mismatch" }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+    let resent = server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "content": second.content,
+            "files": [{ "path": "env.tex" }, { "path": "chapter.tex" }],
+            "version": { "version": 2 },
+        })))
+        .with_body(COMPILED)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None);
+    let cancel = CancellationToken::new();
+    client.compile(&first, &cancel, None).await.expect("Full upload failed");
+    let compiled = client.compile(&second, &cancel, None).await.expect("Fallback upload failed");
+
+    assert!(compiled.response.success);
+    rejected.assert_async().await;
+    resent.assert_async().await;
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationError, CompilationRequest, CompilationResult};
+use context_runtime::diagnostic::{DiagnosticSeverity, DiagnosticSource};
+use context_runtime::runtime::{ContextRuntime, RuntimeConfig};
+use std::any::Any;
+
+// Every compile reports the same error and warning on the document's first line.
+#[derive(Debug)]
+struct FailingBackend;
+
+#[async_trait]
+impl CompilationBackend for FailingBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, _request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        Ok(CompilationResult {
+            success: false,
+            artifact: None,
+            log: String::new(),
+            errors: vec![CompilationError { line: 1, column: 1, message: "Undefined control sequence".to_string() }],
+            warnings: vec![CompilationError { line: 1, column: 1, message: "Overfull \\hbox".to_string() }],
+            synctex: None,
+            backend: "failing".to_string(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "failing"
+    }
+}
+
+#[tokio::test]
+async fn test_compiling_again_replaces_the_previous_compile_diagnostics() {
+    let runtime = ContextRuntime::new_with_backend(RuntimeConfig::default(), Box::new(FailingBackend));
+    let uri = "doc.tex";
+    // The unknown environment is reported by the parser as well.
+    runtime.open_document(uri.to_string(), r"\starttext \startunknown x \stopunknown \stoptext".to_string()).unwrap();
+    let syntax = runtime.get_diagnostics(uri);
+    assert!(!syntax.is_empty());
+
+    for _ in 0..2 {
+        runtime.compile_document(uri).await.expect("Compilation failed");
+        let diagnostics = runtime.get_diagnostics(uri);
+        let compiled: Vec<_> = diagnostics.iter()
+            .filter(|d| d.source == DiagnosticSource::Compilation)
+            .map(|d| d.severity)
+            .collect();
+        assert_eq!(compiled, [DiagnosticSeverity::Error, DiagnosticSeverity::Warning]);
+        assert_eq!(diagnostics.len(), syntax.len() + 2);
+    }
+}
@@ -0,0 +1,80 @@
+use context_runtime::discovery::{self, ContextEngine, InstallationSource, parse_version_output};
+
+const LMTX_VERSION_OUTPUT: &str = "\
+mtx-context     | ConTeXt Process Management 1.05
+mtx-context     |
+mtx-context     | main context file: /home/user/context/tex/texmf-context/tex/context/base/mkxl/context.mkxl
+mtx-context     | current version: 2024.04.01 09:33
+";
+
+const MKIV_VERSION_OUTPUT: &str = "\
+mtx-context     | ConTeXt Process Management 1.04
+mtx-context     |
+mtx-context     | main context file: /usr/share/texmf/tex/context/base/mkiv/context.mkiv
+mtx-context     | current version: 2019.05.14 21:03
+";
+
+#[test]
+fn test_parses_lmtx_version() {
+    let (engine, version) = parse_version_output(LMTX_VERSION_OUTPUT);
+    assert_eq!(engine, ContextEngine::LuaMetaTeX);
+    assert_eq!(version.as_deref(), Some("2024.04.01 09:33"));
+}
+
+#[test]
+fn test_parses_mkiv_version() {
+    let (engine, version) = parse_version_output(MKIV_VERSION_OUTPUT);
+    assert_eq!(engine, ContextEngine::LuaTeX);
+    assert_eq!(version.as_deref(), Some("2019.05.14 21:03"));
+    assert_eq!(parse_version_output("command not found"), (ContextEngine::Unknown, None));
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use tempfile::TempDir;
+
+    fn create_mtxrun(dir: &Path, version_output: &str) -> PathBuf {
+        std::fs::create_dir_all(dir).expect("Failed to create bin dir");
+        let path = dir.join("mtxrun");
+        std::fs::write(&path, format!("#!/bin/sh\ncat <<'EOF'\n{}EOF\n", version_output))
+            .expect("Failed to write dummy mtxrun");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .expect("Failed to set executable permissions");
+        path
+    }
+
+    #[tokio::test]
+    async fn test_probe_reports_engine_and_version() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+
+        let lmtx = create_mtxrun(&temp_dir.path().join("lmtx"), LMTX_VERSION_OUTPUT);
+        let installation = discovery::probe(&lmtx, InstallationSource::Configured).await;
+        assert_eq!(installation.engine, ContextEngine::LuaMetaTeX);
+        assert!(installation.is_supported(), "Unexpected warnings: {:?}", installation.warnings);
+
+        let mkiv = create_mtxrun(&temp_dir.path().join("mkiv"), MKIV_VERSION_OUTPUT);
+        let installation = discovery::probe(&mkiv, InstallationSource::Configured).await;
+        assert_eq!(installation.engine, ContextEngine::LuaTeX);
+        assert!(!installation.is_supported());
+        assert!(installation.warnings.iter().any(|w| w.contains("older than")));
+    }
+
+    #[test]
+    fn test_finds_standalone_install_from_context_root() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let bin = temp_dir.path().join("tex").join("texmf-linux-64").join("bin");
+        let mtxrun = create_mtxrun(&bin, LMTX_VERSION_OUTPUT);
+
+        temp_env::with_vars(
+            [("CONTEXTROOT", Some(temp_dir.path().as_os_str())), ("PATH", Some("".as_ref()))],
+            || {
+                let (path, source) = discovery::find_mtxrun(None).expect("Installation not found");
+                assert_eq!(path, mtxrun);
+                assert_eq!(source, InstallationSource::Environment);
+            },
+        );
+    }
+}
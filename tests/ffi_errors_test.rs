@@ -0,0 +1,112 @@
+use context_runtime::backend_traits::BackendError;
+use context_runtime::ffi::ContextRuntimeHandle;
+use context_runtime::ffi_bridge::{CompileOptionsFfi, ContextErrorFfi, RetryPolicyFfi, RuntimeConfigFfi};
+
+#[test]
+fn test_missing_documents_are_thrown() {
+    let handle = ContextRuntimeHandle::new();
+
+    let missing = ContextErrorFfi::DocumentNotFound { uri: "missing.tex".to_string() };
+    assert_eq!(handle.update("missing.tex".to_string(), 0, 0, "x".to_string()), Err(missing.clone()));
+    assert_eq!(handle.compile("missing.tex".to_string()), Err(missing.clone()));
+    let compiled = futures_util::FutureExt::now_or_never(handle.compile_async("missing.tex".to_string()));
+    assert_eq!(compiled.and_then(Result::err), Some(missing));
+}
+
+#[test]
+fn test_update_rejects_invalid_ranges() {
+    let handle = ContextRuntimeHandle::new();
+    let uri = "ranges.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Grüße \stoptext".to_string()).expect("Failed to open");
+
+    // Past the end, reversed, and splitting the two bytes of `ü`.
+    for (start, end) in [(0, 100), (5, 2), (14, 14)] {
+        let result = handle.update(uri.clone(), start, end, "x".to_string());
+        assert!(
+            matches!(result, Err(ContextErrorFfi::InvalidRange { start: s, end: e, length: 28, .. }) if s == start && e == end),
+            "{}..{}: {:?}", start, end, result
+        );
+    }
+
+    handle.update(uri.clone(), 11, 18, "Hallo".to_string()).expect("Valid range rejected");
+    assert_eq!(handle.get_document_source(uri).as_deref(), Some(r"\starttext Hallo \stoptext"));
+}
+
+#[test]
+fn test_invalid_compile_options_are_thrown() {
+    let handle = ContextRuntimeHandle::new();
+    let options = CompileOptionsFfi { modes: vec!["print,draft".to_string()], ..Default::default() };
+
+    let result = handle.set_compile_options("options.tex".to_string(), Some(options));
+    assert!(matches!(result, Err(ContextErrorFfi::InvalidOptions { .. })), "{:?}", result);
+    handle.set_compile_options("options.tex".to_string(), None).expect("Resetting options failed");
+}
+
+#[test]
+fn test_backend_errors_keep_their_kind() {
+    let from = |error| ContextErrorFfi::from_backend_error("doc.tex", error);
+    let timeout = BackendError::Timeout { timeout: std::time::Duration::from_secs(5), log: "pass 1".to_string() };
+    assert_eq!(
+        from(timeout),
+        ContextErrorFfi::Timeout { details: "Compilation timed out after 5s".to_string(), log: "pass 1".to_string() }
+    );
+    assert!(matches!(from(BackendError::Network("reset".into())), ContextErrorFfi::Network { .. }));
+    assert!(matches!(from(BackendError::Unauthorized("401".into())), ContextErrorFfi::AuthenticationFailed { .. }));
+    assert_eq!(from(BackendError::Cancelled), ContextErrorFfi::Cancelled { uri: "doc.tex".to_string() });
+}
+
+#[cfg(unix)]
+#[test]
+fn test_local_timeouts_are_thrown_with_the_log() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    std::fs::write(&mtxrun, "#!/bin/sh
+echo 'pass 1'
+sleep 10
+").expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: false,
+        local_executable: Some(mtxrun.display().to_string()),
+        compile_timeout_ms: 200,
+        compile_cache_entries: 0,
+        ..Default::default()
+    });
+    let uri = "slow.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Slow \stoptext".to_string()).expect("Failed to open");
+
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    match executor.block_on(handle.compile_async(uri)) {
+        Err(ContextErrorFfi::Timeout { log, .. }) => assert!(log.contains("pass 1"), "{}", log),
+        other => panic!("Expected a timeout, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_rejected_remote_compilations_do_not_fall_back() {
+    let mut server = mockito::Server::new();
+    server.mock("GET", "/capabilities").with_status(404).create();
+    let compile = server.mock("POST", "/compile")
+        .with_status(400)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"error": "Malformed request", "code": "bad_request"}"#)
+        .expect(1)
+        .create();
+
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(server.url()),
+        remote_retry: Some(RetryPolicyFfi { max_attempts: 1, initial_backoff_ms: 0, max_backoff_ms: 0, jitter_percent: 0 }),
+        ..Default::default()
+    });
+    let uri = "rejected.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Rejected".to_string()).expect("Failed to open");
+
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    let result = executor.block_on(handle.compile_async(uri));
+    assert!(matches!(result, Err(ContextErrorFfi::Compilation { .. })), "{:?}", result);
+    compile.assert();
+}
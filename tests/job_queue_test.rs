@@ -0,0 +1,178 @@
+use context_runtime::job_queue::{JobPriority, JobQueue, JobState};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+
+#[tokio::test]
+async fn test_concurrency_limit_is_respected() {
+    let queue = JobQueue::new(Handle::current(), 2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    for i in 0..6 {
+        let running = Arc::clone(&running);
+        let peak = Arc::clone(&peak);
+        queue.submit(format!("job{i}"), &format!("doc{i}.tex"), JobPriority::Normal, move |_| async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    let states: Vec<JobState> = queue.snapshot().into_iter().map(|job| job.state).collect();
+    assert_eq!(states.iter().filter(|s| **s == JobState::Running).count(), 2);
+    assert_eq!(states.len(), 6);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+    assert!(queue.is_empty());
+}
+
+#[tokio::test]
+async fn test_same_document_is_serialized_and_coalesced() {
+    let queue = JobQueue::new(Handle::current(), 4);
+    let release = Arc::new(Notify::new());
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let (first_release, first_log) = (Arc::clone(&release), Arc::clone(&log));
+    queue.submit("first", "doc.tex", JobPriority::Normal, move |_| async move {
+        first_release.notified().await;
+        first_log.lock().unwrap().push("first");
+    });
+
+    for id in ["second", "third"] {
+        let log = Arc::clone(&log);
+        queue.submit_replacing(id, "doc.tex", JobPriority::Normal, move |cancel| async move {
+            let outcome = if cancel.is_cancelled() { "cancelled" } else { "ran" };
+            log.lock().unwrap().push(if id == "second" { outcome } else { id });
+        });
+    }
+
+    // "third" replaced the queued "second", which saw an already-cancelled token.
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(*log.lock().unwrap(), vec!["cancelled"]);
+    let ids: Vec<String> = queue.snapshot().into_iter().map(|job| job.id).collect();
+    assert_eq!(ids, vec!["first", "third"]);
+
+    release.notify_one();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(*log.lock().unwrap(), vec!["cancelled", "first", "third"]);
+}
+
+#[tokio::test]
+async fn test_background_jobs_do_not_replace_requested_ones() {
+    let queue = JobQueue::new(Handle::current(), 4);
+    let release = Arc::new(Notify::new());
+
+    let first_release = Arc::clone(&release);
+    queue.submit("running", "doc.tex", JobPriority::Normal, move |_| async move {
+        first_release.notified().await;
+    });
+    let requested = queue.submit("requested", "doc.tex", JobPriority::Normal, |_| async {});
+    queue.submit_replacing("auto", "doc.tex", JobPriority::Background, |_| async {});
+    queue.submit_replacing("auto_newer", "doc.tex", JobPriority::Background, |_| async {});
+
+    assert!(!requested.is_cancelled());
+    let ids: Vec<String> = queue.snapshot().into_iter().map(|job| job.id).collect();
+    assert_eq!(ids, vec!["running", "requested", "auto_newer"]);
+    release.notify_one();
+}
+
+#[tokio::test]
+async fn test_requested_jobs_are_never_replaced() {
+    let queue = JobQueue::new(Handle::current(), 4);
+    let release = Arc::new(Notify::new());
+
+    let first_release = Arc::clone(&release);
+    queue.submit("running", "doc.tex", JobPriority::Normal, move |_| async move {
+        first_release.notified().await;
+    });
+    let first = queue.submit("first", "doc.tex", JobPriority::Normal, |_| async {});
+    let second = queue.submit("second", "doc.tex", JobPriority::Normal, |_| async {});
+    let replacing = queue.submit_replacing("replacing", "doc.tex", JobPriority::Normal, |_| async {});
+
+    assert!(!first.is_cancelled() && !second.is_cancelled() && !replacing.is_cancelled());
+    let ids: Vec<String> = queue.snapshot().into_iter().map(|job| job.id).collect();
+    assert_eq!(ids, vec!["running", "first", "second", "replacing"]);
+    release.notify_one();
+}
+
+#[tokio::test]
+async fn test_foreground_document_runs_first() {
+    let queue = JobQueue::new(Handle::current(), 1);
+    let release = Arc::new(Notify::new());
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let blocker = Arc::clone(&release);
+    queue.submit("blocker", "busy.tex", JobPriority::Normal, move |_| async move {
+        blocker.notified().await;
+    });
+
+    for (id, uri, priority) in [
+        ("background", "a.tex", JobPriority::Background),
+        ("normal", "b.tex", JobPriority::Normal),
+        ("viewed", "c.tex", JobPriority::Background),
+    ] {
+        let order = Arc::clone(&order);
+        queue.submit(id, uri, priority, move |_| async move {
+            order.lock().unwrap().push(id);
+        });
+    }
+    queue.set_foreground(Some("c.tex".to_string()));
+
+    let queued: Vec<String> = queue.snapshot().into_iter().skip(1).map(|job| job.id).collect();
+    assert_eq!(queued, vec!["viewed", "normal", "background"]);
+
+    release.notify_one();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(*order.lock().unwrap(), vec!["viewed", "normal", "background"]);
+}
+
+#[tokio::test]
+async fn test_cancel_running_job_triggers_token() {
+    let queue = JobQueue::new(Handle::current(), 1);
+    let observed = Arc::new(Mutex::new(None));
+
+    let seen = Arc::clone(&observed);
+    queue.submit("slow", "doc.tex", JobPriority::Normal, move |cancel| async move {
+        tokio::select! {
+            _ = cancel.cancelled() => *seen.lock().unwrap() = Some("cancelled"),
+            _ = tokio::time::sleep(Duration::from_secs(5)) => *seen.lock().unwrap() = Some("finished"),
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(queue.cancel("slow"));
+    assert!(!queue.cancel("missing"));
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    assert_eq!(*observed.lock().unwrap(), Some("cancelled"));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_shutting_down_the_runtime_with_queued_jobs() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let queue = JobQueue::new(runtime.handle().clone(), 1);
+    let started = Arc::new(Notify::new());
+
+    let running = Arc::clone(&started);
+    queue.submit("running", "a.tex", JobPriority::Normal, move |_| async move {
+        running.notify_one();
+        std::future::pending::<()>().await;
+    });
+    queue.submit("queued", "b.tex", JobPriority::Normal, |_| async {});
+    runtime.block_on(started.notified());
+
+    // Freeing the running job's worker starts the queued job on a runtime that is gone.
+    let (done, finished) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        drop(runtime);
+        drop(queue);
+        let _ = done.send(());
+    });
+    assert!(finished.recv_timeout(Duration::from_secs(5)).is_ok(), "Dropping the runtime hung");
+}
@@ -1,4 +1,4 @@
-use context_runtime::runtime::{RuntimeConfig, ContextRuntime};
+use context_runtime::runtime::{RuntimeConfig, ContextRuntime, RuntimeError};
 use std::path::{PathBuf, Path};
 use tempfile::TempDir;
 use tokio::fs;
@@ -14,7 +14,8 @@ async fn create_dummy_executable(dir: &Path, name: &str) -> PathBuf {
     // On Unix, ensure it's executable. On Windows, just creating a file is enough for Command::new.
     #[cfg(unix)]
     {
-        fs::write(&dummy_path, "#!/bin/sh\necho 'dummy mtxrun output'\nexit 0")
+        // Writes a stub PDF next to the source file, which is passed as the last argument
+        fs::write(&dummy_path, "#!/bin/sh\necho 'dummy mtxrun output'\nfor last; do :; done\nprintf '%%PDF-1.4\\n/Type /Page\\n%%%%EOF\\n' > \"${last%.tex}.pdf\"\nexit 0")
             .await
             .expect("Failed to write dummy executable");
         // Make it executable
@@ -55,6 +56,7 @@ async fn test_local_backend_with_explicit_mtxrun_path() {
         server_url: None,
         auth_token: None,
         local_executable: Some(mtxrun_path.clone()), // Explicit absolute path
+        ..Default::default()
     };
 
     let result = tokio::spawn(async move {
@@ -72,7 +74,7 @@ async fn test_local_backend_with_explicit_mtxrun_path() {
 
             assert!(compile_result.success);
             assert!(compile_result.log.contains("dummy mtxrun output")); // Check dummy output
-            assert!(compile_result.pdf_path.is_some());
+            assert!(compile_result.artifact.is_some());
             assert!(compile_result.errors.is_empty());
             assert!(compile_result.warnings.is_empty());
             Ok::<(), String>(())
@@ -91,34 +93,35 @@ async fn test_local_backend_with_non_existent_explicit_path() {
         server_url: None,
         auth_token: None,
         local_executable: Some(non_existent_path.clone()), // Non-existent path
+        ..Default::default()
     };
 
     let result = tokio::spawn(async move {
         let config_clone = config.clone();
-        tokio::task::spawn_blocking(move || {
-            // We expect LocalBackend::new to fail here
-            let runtime_creation_result = std::panic::catch_unwind(|| {
-                ContextRuntime::new(config_clone)
-            });
-
-            assert!(runtime_creation_result.is_err(), "Runtime creation should have panicked");
-            let panic_payload = runtime_creation_result.unwrap_err();
-            let err_msg = panic_payload.downcast_ref::<String>()
-                                        .expect("Panic payload not a String");
+        // A missing executable no longer panics; the runtime reports it when compiling.
+        let runtime = ContextRuntime::new(config_clone);
+        let uri = "test_missing.tex".to_string();
+        runtime.open_document(uri.clone(), r"\starttext Missing \stoptext".to_string())
+            .expect("Failed to open document");
+
+        let err_msg = match runtime.compile_document(&uri).await {
+            Err(RuntimeError::Unavailable(msg)) => msg,
+            other => panic!("Expected an unavailable backend, got {:?}", other.map(|r| r.success)),
+        };
 
-            // The error message from BackendError::Unavailable
-            assert!(err_msg.contains("Configured mtxrun executable not found"));
-            assert!(err_msg.contains(&non_existent_path.to_string_lossy().to_string()));
+        // The error message from BackendError::Unavailable
+        assert!(err_msg.contains("Configured mtxrun executable not found"));
+        assert!(err_msg.contains(&non_existent_path.to_string_lossy().to_string()));
 
-            Ok::<(), String>(())
-        }).await.expect("Blocking task failed")
+        Ok::<(), String>(())
     }).await;
     assert!(result.is_ok(), "Test failed: {:?}", result.err());
 }
 
 
-#[tokio::test]
-async fn test_local_backend_with_path_lookup_success() {
+// Plain test: it builds its own runtime inside `temp_env::with_var`.
+#[test]
+fn test_local_backend_with_path_lookup_success() {
     // This test is harder because we need to modify the PATH for the test process.
     // temp-env crate is good for this, but it requires a careful approach
     // with `tokio::spawn` and the fact that env vars are process-wide.
@@ -129,7 +132,9 @@ async fn test_local_backend_with_path_lookup_success() {
 
     // Let's create a dummy mtxrun and put it in a temp directory.
     let temp_dir = TempDir::new().expect("Failed to create temp dir for PATH test");
-    let dummy_mtxrun_path = create_dummy_executable(temp_dir.path(), "mtxrun").await;
+    let _dummy_mtxrun_path = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(create_dummy_executable(temp_dir.path(), "mtxrun"));
 
     // Use `temp-env` to temporarily modify the PATH for this test.
     // This MUST be done carefully to avoid interfering with other tests,
@@ -155,6 +160,7 @@ async fn test_local_backend_with_path_lookup_success() {
             server_url: None,
             auth_token: None,
             local_executable: None, // Rely on PATH lookup
+            ..Default::default()
         };
 
         let result = tokio::runtime::Builder::new_current_thread()
@@ -170,15 +176,15 @@ async fn test_local_backend_with_path_lookup_success() {
 
                     runtime.open_document(uri.clone(), content).expect("Failed to open document");
 
-                    // Create a new tokio runtime to block on the async compile_document call
-                    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-                    let compile_result = rt.block_on(runtime.compile_document(&uri)).expect("Failed to compile");
+                    let compile_result = tokio::runtime::Handle::current().block_on(runtime.compile_document(&uri))
+                        .expect("Failed to compile");
 
                     assert!(compile_result.success);
                     assert!(compile_result.log.contains("dummy mtxrun output")); // Check dummy output
-                    assert!(compile_result.pdf_path.is_some());
+                    assert!(compile_result.artifact.is_some());
                     assert!(compile_result.errors.is_empty());
                     assert!(compile_result.warnings.is_empty());
+                    assert_eq!(compile_result.backend, "local");
                     Ok::<(), String>(())
                 }).await.expect("Blocking task failed")
             });
@@ -187,8 +193,9 @@ async fn test_local_backend_with_path_lookup_success() {
 }
 
 
-#[tokio::test]
-async fn test_local_backend_with_path_lookup_failure() {
+// Plain test: it builds its own runtime inside `temp_env::with_var`.
+#[test]
+fn test_local_backend_with_path_lookup_failure() {
     // This test aims to confirm that if mtxrun is NOT in PATH and not explicitly provided,
     // `LocalBackend::new` or `compile` fails as expected.
 
@@ -199,7 +206,7 @@ async fn test_local_backend_with_path_lookup_failure() {
     // This guarantees mtxrun won't be found unless we put it there.
     let temp_dir = TempDir::new().expect("Failed to create temp dir for PATH failure test");
     let empty_path_dir = temp_dir.path().join("empty_path");
-    fs::create_dir(&empty_path_dir).await.unwrap();
+    std::fs::create_dir(&empty_path_dir).unwrap();
 
     temp_env::with_var("PATH", Some(empty_path_dir.to_string_lossy().to_string()), || {
         let config = RuntimeConfig {
@@ -207,6 +214,7 @@ async fn test_local_backend_with_path_lookup_failure() {
             server_url: None,
             auth_token: None,
             local_executable: None, // Rely on PATH lookup, which should fail
+            ..Default::default()
         };
 
         let result = tokio::runtime::Builder::new_current_thread()
@@ -215,22 +223,23 @@ async fn test_local_backend_with_path_lookup_failure() {
             .unwrap()
             .block_on(async move {
                 let config_clone = config.clone();
-                tokio::task::spawn_blocking(move || {
-                    // Expect LocalBackend::new to fail with BackendError::Unavailable
-                    let runtime_creation_result = std::panic::catch_unwind(|| {
-                        ContextRuntime::new(config_clone)
-                    });
+                // LocalBackend::new fails with BackendError::Unavailable, which the
+                // runtime reports instead of panicking.
+                let runtime = ContextRuntime::new(config_clone);
+                let uri = "test_path_failure.tex".to_string();
+                runtime.open_document(uri.clone(), r"\starttext Missing \stoptext".to_string())
+                    .expect("Failed to open document");
 
-                    assert!(runtime_creation_result.is_err(), "Runtime creation should have panicked");
-                    let panic_payload = runtime_creation_result.unwrap_err();
-                    let err_msg = panic_payload.downcast_ref::<String>()
-                                                .expect("Panic payload not a String");
+                let health = runtime.backend_health().await;
+                assert!(health.iter().all(|backend| !backend.healthy));
 
+                match runtime.compile_document(&uri).await {
                     // Check for the error message from `which` crate (or similar)
-                    assert!(err_msg.contains("mtxrun executable not found in system PATH"));
+                    Err(RuntimeError::Unavailable(msg)) => assert!(msg.contains("mtxrun executable not found in system PATH")),
+                    other => panic!("Expected an unavailable backend, got {:?}", other.map(|r| r.success)),
+                }
 
-                    Ok::<(), String>(())
-                }).await.expect("Blocking task failed")
+                Ok::<(), String>(())
             });
         assert!(result.is_ok(), "Test failed: {:?}", result.err());
     });
@@ -0,0 +1,187 @@
+#![cfg(unix)]
+
+use context_runtime::backend_traits::{
+    BackendError, CompilationBackend, CompilationRequest, CompileOptions, ContextEngineChoice,
+};
+use context_runtime::persistent::PersistentBackend;
+use regex::Regex;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+// Speaks the control protocol like the real control document and reports its pid
+// per job.
+const SERVER: &str = r#"#!/bin/sh
+echo "dummy mtxrun banner $*"
+# A plain `context` run, not the control document.
+case "$*" in *context-runtime-server.cld*) ;; *) exit 0;; esac
+echo "@@context-runtime ready"
+count=0
+while IFS="$(printf '\t')" read -r cmd nonce dir job file; do
+    [ "$cmd" = quit ] && exit 0
+    cd "$dir" || exit 4
+    count=$((count + 1))
+    case "$(cat "$file")" in
+        *crash*) exit 3;;
+        *forge*) echo "@@context-runtime done 0";;
+        *fail*) echo "@@context-runtime done $nonce 1"; continue;;
+    esac
+    echo "mtx-context     | run 1: luametatex"
+    echo "server $$ job $count"
+    echo "started as $*"
+    printf '%%PDF-1.4\n/Type /Page\n%%%%EOF\n' > "$job.pdf"
+    echo "@@context-runtime done $nonce 0"
+done
+"#;
+
+fn create_server(dir: &Path) -> PathBuf {
+    let path = dir.join("mtxrun");
+    std::fs::write(&path, SERVER).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to set executable permissions");
+    path
+}
+
+fn request(job_id: &str, content: &str) -> CompilationRequest {
+    CompilationRequest {
+        content: content.to_string(),
+        job_id: job_id.to_string(),
+        ..Default::default()
+    }
+}
+
+// Returns (server pid, job number within that server).
+async fn compile(backend: &PersistentBackend, job_id: &str) -> (String, u32) {
+    let result = backend.compile(request(job_id, r"\starttext Warm \stoptext")).await.expect("Compilation failed");
+    assert!(result.success);
+    assert!(result.artifact.is_some());
+
+    let re = Regex::new(r"server (\d+) job (\d+)").unwrap();
+    let caps = re.captures(&result.log).expect("Job marker missing from log");
+    (caps[1].to_string(), caps[2].parse().unwrap())
+}
+
+#[tokio::test]
+async fn test_engine_is_started_with_the_control_document() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let result = backend.compile(request("a.tex", r"\starttext Warm \stoptext")).await.expect("Compilation failed");
+    assert!(result.log.contains("started as --script context --once --nonstopmode"), "{}", result.log);
+    backend.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_is_reused_between_jobs() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let (first_pid, first_job) = compile(&backend, "a.tex").await;
+    let (second_pid, second_job) = compile(&backend, "b.tex").await;
+
+    assert_eq!(first_pid, second_pid);
+    assert_eq!((first_job, second_job), (1, 2));
+    backend.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_server_is_replaced_after_max_jobs() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path())))
+        .unwrap()
+        .with_max_jobs(2);
+
+    let (first, _) = compile(&backend, "a.tex").await;
+    let (second, _) = compile(&backend, "a.tex").await;
+    let (third, job) = compile(&backend, "a.tex").await;
+
+    assert_eq!(first, second);
+    assert_ne!(second, third);
+    assert_eq!(job, 1);
+}
+
+#[tokio::test]
+async fn test_server_restarts_after_crash() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let (before, _) = compile(&backend, "a.tex").await;
+    let crashed = backend.compile(request("boom.tex", "crash")).await;
+    assert!(matches!(crashed, Err(BackendError::Compilation(_))));
+
+    let (after, job) = compile(&backend, "a.tex").await;
+    assert_ne!(before, after);
+    assert_eq!(job, 1);
+}
+
+#[tokio::test]
+async fn test_server_is_replaced_after_a_failed_job() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let (before, _) = compile(&backend, "a.tex").await;
+    let failed = backend.compile(request("bad.tex", "fail")).await.expect("Compilation failed");
+    assert!(!failed.success);
+
+    let (after, job) = compile(&backend, "a.tex").await;
+    assert_ne!(before, after);
+    assert_eq!(job, 1);
+    backend.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_runtime_keeps_its_engine_between_compiles() {
+    use context_runtime::runtime::{ContextRuntime, RuntimeConfig};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let runtime = ContextRuntime::new(RuntimeConfig {
+        remote: false,
+        local_executable: Some(create_server(temp_dir.path())),
+        compile_cache: None,
+        persistent_server: true,
+        ..Default::default()
+    });
+    runtime.open_document("warm.tex".to_string(), r"\starttext Warm \stoptext".to_string()).unwrap();
+
+    let re = Regex::new(r"server (\d+) job (\d+)").unwrap();
+    let mut jobs = Vec::new();
+    for _ in 0..2 {
+        let result = runtime.compile_document("warm.tex").await.expect("Compilation failed");
+        assert_eq!(result.backend, "persistent");
+        let caps = re.captures(&result.log).expect("Job marker missing from log");
+        jobs.push((caps[1].to_string(), caps[2].to_string()));
+    }
+    assert_eq!(jobs[0].0, jobs[1].0, "The second compile started a new engine");
+    assert_eq!((jobs[0].1.as_str(), jobs[1].1.as_str()), ("1", "2"));
+}
+
+#[tokio::test]
+async fn test_printed_done_lines_do_not_end_the_job() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let result = backend.compile(request("forged.tex", "forge")).await.expect("Compilation failed");
+    assert!(result.log.contains("server"), "The job ended at the forged line: {}", result.log);
+
+    // The protocol is still in step for the next job.
+    let (_, job) = compile(&backend, "a.tex").await;
+    assert_eq!(job, 2);
+    backend.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_jobs_that_pick_an_engine_run_without_the_server() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let backend = PersistentBackend::new(Some(create_server(temp_dir.path()))).unwrap();
+
+    let mut request = request("engine.tex", r"\starttext Engine \stoptext");
+    request.options = CompileOptions { engine: Some(ContextEngineChoice::Mkiv), ..Default::default() };
+    let result = backend.compile(request).await.expect("Compilation failed");
+    assert!(result.log.contains("dummy mtxrun banner --script context"), "Not a fresh run: {}", result.log);
+    assert!(result.log.contains("--luatex"));
+
+    // The server was never started for it.
+    let (_, job) = compile(&backend, "a.tex").await;
+    assert_eq!(job, 1);
+    backend.shutdown().await;
+}
@@ -0,0 +1,100 @@
+#![cfg(unix)]
+
+use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationRequest, LocalBackend};
+use context_runtime::process::{ProcessLimits, ResourceLimit};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn create_mtxrun(dir: &Path, body: &str) -> PathBuf {
+    let path = dir.join("mtxrun");
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+        .expect("Failed to set executable permissions");
+    path
+}
+
+fn request() -> CompilationRequest {
+    CompilationRequest {
+        content: r"\def\loop{\loop}\loop".to_string(),
+        job_id: "loop.tex".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_timeout_returns_partial_log() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = create_mtxrun(temp_dir.path(), "echo 'mtx-context     | run 1: luatex'\nsleep 30");
+    let backend = LocalBackend::new(Some(mtxrun))
+        .expect("Failed to create local backend")
+        .with_limits(ProcessLimits { timeout: Some(Duration::from_millis(300)), ..ProcessLimits::unlimited() });
+
+    let started = Instant::now();
+    match backend.compile(request()).await {
+        Err(BackendError::Timeout { timeout, log }) => {
+            assert_eq!(timeout, Duration::from_millis(300));
+            assert!(log.contains("run 1: luatex"), "Partial log missing: {log}");
+        }
+        other => panic!("Expected timeout, got {:?}", other.map(|r| r.success)),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_background_processes_do_not_hold_up_the_result() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    // Like a document that ran `\executesystemcommand{sleep 30 &}`: the sleep keeps the pipes open.
+    let mtxrun = create_mtxrun(temp_dir.path(), "echo 'mtx-context     | run 1: luatex'\nsleep 30 &\nexit 1");
+    let backend = LocalBackend::new(Some(mtxrun))
+        .expect("Failed to create local backend")
+        .with_limits(ProcessLimits { timeout: Some(Duration::from_secs(20)), ..ProcessLimits::unlimited() });
+
+    let started = Instant::now();
+    let result = tokio::time::timeout(Duration::from_secs(10), backend.compile(request())).await
+        .expect("The compile waited for the background process");
+    assert!(result.is_ok_and(|result| result.log.contains("run 1: luatex")));
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_cpu_limit_is_reported() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = create_mtxrun(temp_dir.path(), "echo 'spinning'\nwhile :; do :; done");
+    let backend = LocalBackend::new(Some(mtxrun))
+        .expect("Failed to create local backend")
+        .with_limits(ProcessLimits {
+            timeout: Some(Duration::from_secs(20)),
+            max_cpu_seconds: Some(1),
+            ..ProcessLimits::unlimited()
+        });
+
+    match backend.compile(request()).await {
+        Err(BackendError::ResourceExceeded { resource, log }) => {
+            assert_eq!(resource, ResourceLimit::Cpu);
+            assert!(log.contains("spinning"));
+        }
+        other => panic!("Expected CPU limit, got {:?}", other.map(|r| r.success)),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_other_kills_are_not_blamed_on_the_cpu_limit() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = create_mtxrun(temp_dir.path(), "echo 'killed'\nkill -KILL $$");
+    let backend = LocalBackend::new(Some(mtxrun))
+        .expect("Failed to create local backend")
+        .with_limits(ProcessLimits {
+            timeout: Some(Duration::from_secs(20)),
+            max_cpu_seconds: Some(60),
+            ..ProcessLimits::unlimited()
+        });
+
+    match backend.compile(request()).await {
+        Ok(result) => assert!(!result.success),
+        other => panic!("Expected a failed compile, got {:?}", other.map(|r| r.success)),
+    }
+}
@@ -0,0 +1,66 @@
+use context_runtime::backend_traits::{CompilationBackend, CompilationRequest, LocalBackend};
+use context_runtime::progress::{CompileEvent, CompileProgress, LogStream, ProgressTracker};
+
+const LOG: &str = "\
+mtx-context     | run 1: luatex --fmt=cont-en --jobname=report report.tex
+open source     > level 1, order 1, name 'report.tex'
+open source     > level 2, order 2, name 'chapter.tex'
+pages           > flushing realpage 1, userpage 1, subpage 1
+close source    > level 2, order 2, name 'chapter.tex'
+pages           > flushing realpage 2, userpage 2, subpage 2
+system          | total runtime 0.412 seconds
+mtx-context     | run 2: luatex --fmt=cont-en --jobname=report report.tex
+";
+
+#[test]
+fn test_tracker_follows_passes_pages_and_files() {
+    let mut tracker = ProgressTracker::new();
+    let updates: Vec<CompileProgress> = LOG.lines().filter_map(|line| tracker.feed_line(line)).collect();
+
+    assert_eq!(updates.len(), 7);
+    assert_eq!(updates[2], CompileProgress { pass: 1, pages: 0, current_file: Some("chapter.tex".into()) });
+    assert_eq!(updates[4], CompileProgress { pass: 1, pages: 1, current_file: Some("report.tex".into()) });
+    assert_eq!(updates[5].pages, 2);
+    assert_eq!(tracker.progress(), &CompileProgress { pass: 2, pages: 0, current_file: None });
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_local_backend_streams_events() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    std::fs::write(&mtxrun, format!("#!/bin/sh\ncat <<'EOF'\n{}EOF\necho 'engine warning' >&2\n", LOG))
+        .expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let backend = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let request = CompilationRequest {
+        content: r"\starttext \stoptext".to_string(),
+        job_id: "report.tex".to_string(),
+        events: Some(sender),
+        ..Default::default()
+    };
+
+    let result = backend.compile(request).await.expect("Compilation failed");
+    assert!(result.log.contains("total runtime"));
+
+    let mut events = Vec::new();
+    while let Some(event) = receiver.recv().await {
+        events.push(event);
+    }
+
+    let last_progress = events.iter().rev().find_map(|e| match e {
+        CompileEvent::Progress(progress) => Some(progress.clone()),
+        _ => None,
+    });
+    assert_eq!(last_progress.map(|p| p.pass), Some(2));
+
+    let stdout_lines = events.iter()
+        .filter(|e| matches!(e, CompileEvent::Log { stream: LogStream::Stdout, .. }))
+        .count();
+    assert_eq!(stdout_lines, LOG.lines().count());
+    assert!(events.contains(&CompileEvent::Log { stream: LogStream::Stderr, chunk: "engine warning\n".into() }));
+}
@@ -0,0 +1,95 @@
+use context_runtime::backend_traits::{BackendError, CompileOptions};
+use context_runtime::protocol::{CompileRequest, ErrorCode, PROTOCOL_VERSION, VERSION_HEADER};
+use context_runtime::remote_client::RemoteClient;
+use context_runtime::retry::RetryPolicy;
+use tokio_util::sync::CancellationToken;
+
+fn compile_request(options: CompileOptions) -> CompileRequest {
+    CompileRequest {
+        uri: "protocol.tex".to_string(),
+        content: r"\starttext Protocol \stoptext".to_string(),
+        files: Vec::new(),
+        options,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_legacy_server_without_capabilities() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities").with_status(404).create_async().await;
+    let compile = server.mock("POST", "/compile")
+        .match_header(VERSION_HEADER, PROTOCOL_VERSION.to_string().as_str())
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({ "success": true, "log": "ok", "output_url": null }).to_string())
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None);
+    let cancel = CancellationToken::new();
+
+    let compiled = client.compile(&compile_request(CompileOptions::default()), &cancel, None).await
+        .expect("Legacy servers should accept default options");
+    assert!(compiled.response.success);
+    assert!(compiled.response.diagnostics.is_empty());
+
+    // Options would be silently ignored by a server that predates them.
+    let draft = CompileOptions { draft: true, ..Default::default() };
+    let result = client.compile(&compile_request(draft), &cancel, None).await;
+    assert!(matches!(result, Err(BackendError::InvalidOptions(_))), "got {:?}", result.map(|c| c.response));
+
+    compile.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_incompatible_server_version_is_fatal() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "protocol_version": PROTOCOL_VERSION + 2,
+            "min_protocol_version": PROTOCOL_VERSION + 1,
+        }).to_string())
+        .create_async()
+        .await;
+    let compile = server.mock("POST", "/compile").expect(0).create_async().await;
+
+    let client = RemoteClient::new(server.url(), None);
+    let result = client.compile(&compile_request(CompileOptions::default()), &CancellationToken::new(), None).await;
+
+    assert!(matches!(result, Err(BackendError::Setup(_))), "got {:?}", result.map(|c| c.response));
+    compile.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_error_bodies_map_to_backend_errors() {
+    let cases = [
+        (409, ErrorCode::Superseded),
+        (400, ErrorCode::InvalidOptions),
+        (401, ErrorCode::Unauthorized),
+        (503, ErrorCode::Unavailable),
+    ];
+
+    for (status, code) in cases {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/capabilities").with_status(404).create_async().await;
+        server.mock("POST", "/compile")
+            .with_status(status)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({ "error": "nope", "code": code }).to_string())
+            .create_async()
+            .await;
+
+        let client = RemoteClient::new(server.url(), Some("token".to_string())).with_retry(RetryPolicy::none());
+        let error = client.compile(&compile_request(CompileOptions::default()), &CancellationToken::new(), None).await
+            .expect_err("Error statuses should fail the compile");
+
+        match code {
+            ErrorCode::Superseded => assert!(matches!(error, BackendError::Cancelled)),
+            ErrorCode::InvalidOptions => assert!(matches!(error, BackendError::InvalidOptions(ref m) if m == "nope")),
+            ErrorCode::Unauthorized => assert!(matches!(error, BackendError::Unauthorized(ref m) if m.contains("401"))),
+            _ => assert!(matches!(error, BackendError::Unavailable(_))),
+        }
+    }
+}
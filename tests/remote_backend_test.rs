@@ -0,0 +1,333 @@
+use context_runtime::artifact::content_hash;
+use context_runtime::backend_traits::{CompilationBackend, CompilationRequest, RemoteBackend};
+use mockito::Matcher;
+use tempfile::TempDir;
+
+const PDF: &[u8] = b"%PDF-1.4\n3 0 obj << /Type /Page >> endobj\n%%EOF\n";
+
+fn request(job_id: &str) -> CompilationRequest {
+    CompilationRequest {
+        content: r"\starttext Remote \stoptext".to_string(),
+        job_id: job_id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_remote_backend_downloads_artifact_with_token() {
+    let mut server = mockito::Server::new_async().await;
+    let cache_dir = TempDir::new().expect("Failed to create temp dir");
+    let hash = content_hash(PDF);
+
+    let compile = server.mock("POST", "/compile")
+        .match_header("authorization", "Bearer secret")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "success": true,
+            "log": "ok",
+            "output_url": "/artifacts/report.pdf",
+            "output_hash": hash,
+            "diagnostics": [],
+        }).to_string())
+        .expect(2)
+        .create_async()
+        .await;
+
+    // The second compile reports the same hash, so only one download happens.
+    let download = server.mock("GET", "/artifacts/report.pdf")
+        .match_header("authorization", "Bearer secret")
+        .with_body(PDF)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let backend = RemoteBackend::new(server.url(), Some("secret".to_string()))
+        .with_cache_dir(Some(cache_dir.path().to_path_buf()));
+
+    for _ in 0..2 {
+        let result = backend.compile(request("report.tex")).await.expect("Compilation failed");
+        let artifact = result.artifact.expect("Missing artifact");
+        let path = artifact.path().expect("Artifact should be a cached file");
+
+        assert!(path.starts_with(cache_dir.path()));
+        assert_eq!(std::fs::read(path).unwrap(), PDF);
+        assert_eq!(artifact.content_hash.as_deref(), Some(hash.as_str()));
+        assert_eq!(artifact.page_count, Some(1));
+    }
+
+    compile.assert_async().await;
+    download.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_remote_backend_keeps_token_from_other_hosts() {
+    let mut server = mockito::Server::new_async().await;
+    let mut storage = mockito::Server::new_async().await;
+    let cache_dir = TempDir::new().expect("Failed to create temp dir");
+
+    server.mock("POST", "/compile")
+        .match_header("authorization", "Bearer secret")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "success": true,
+            "log": "ok",
+            "output_url": format!("{}/bucket/report.pdf?signature=abc", storage.url()),
+            "output_hash": content_hash(PDF),
+            "diagnostics": [],
+        }).to_string())
+        .create_async()
+        .await;
+
+    // A presigned URL on another host gets no credentials.
+    let download = storage.mock("GET", "/bucket/report.pdf")
+        .match_query(Matcher::Any)
+        .match_header("authorization", Matcher::Missing)
+        .with_body(PDF)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let backend = RemoteBackend::new(server.url(), Some("secret".to_string()))
+        .with_cache_dir(Some(cache_dir.path().to_path_buf()));
+    let result = backend.compile(request("report.tex")).await.expect("Compilation failed");
+    assert!(result.artifact.is_some());
+    download.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_remote_backend_rejects_hash_mismatch() {
+    let mut server = mockito::Server::new_async().await;
+    let cache_dir = TempDir::new().expect("Failed to create temp dir");
+
+    server.mock("POST", "/compile")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "success": true,
+            "log": "ok",
+            "output_url": format!("{}/out.pdf", server.url()),
+            "output_hash": "0000000000000000000000000000000000000000000000000000000000000000",
+            "diagnostics": [],
+        }).to_string())
+        .create_async()
+        .await;
+
+    server.mock("GET", "/out.pdf")
+        .match_header("authorization", Matcher::Missing)
+        .with_body(PDF)
+        .create_async()
+        .await;
+
+    let backend = RemoteBackend::new(server.url(), None)
+        .with_cache_dir(Some(cache_dir.path().to_path_buf()));
+
+    let result = backend.compile(request("mismatch.tex")).await;
+    assert!(result.is_err(), "Hash mismatch should fail the compile");
+}
+
+#[tokio::test]
+async fn test_remote_backend_sends_compile_options() {
+    use context_runtime::backend_traits::{CompileOptions, ContextEngineChoice};
+
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "protocol_version": 1,
+            "min_protocol_version": 1,
+            "features": ["options"],
+        }).to_string())
+        .create_async()
+        .await;
+    let compile = server.mock("POST", "/compile")
+        .match_body(Matcher::PartialJson(serde_json::json!({
+            "options": { "engine": "lmtx", "draft": true, "modes": ["print"] },
+        })))
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({ "success": true, "log": "ok", "diagnostics": [] }).to_string())
+        .create_async()
+        .await;
+
+    let backend = RemoteBackend::new(server.url(), None);
+    let request = CompilationRequest {
+        options: CompileOptions {
+            engine: Some(ContextEngineChoice::Lmtx),
+            draft: true,
+            modes: vec!["print".to_string()],
+            ..Default::default()
+        },
+        ..request("options.tex")
+    };
+    backend.compile(request).await.expect("Compilation failed");
+
+    compile.assert_async().await;
+}
+
+#[test]
+fn test_handle_reuses_its_connection_between_compiles() {
+    use context_runtime::ffi::ContextRuntimeHandle;
+    use context_runtime::ffi_bridge::RuntimeConfigFfi;
+
+    let mut server = mockito::Server::new();
+    let capabilities = server.mock("GET", "/capabilities").with_status(404).expect(1).create();
+    let compile = server.mock("POST", "/compile")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({ "success": true, "log": "ok", "diagnostics": [] }).to_string())
+        .expect(2)
+        .create();
+
+    let cache_dir = TempDir::new().expect("Failed to create temp dir");
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(server.url()),
+        cache_dir: Some(cache_dir.path().to_string_lossy().into_owned()),
+        ..Default::default()
+    });
+    let uri = "reused.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Reused \stoptext".to_string()).expect("Failed to open");
+
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    let first = executor.block_on(handle.compile_async(uri.clone())).expect("Compilation failed");
+    assert!(first.success, "{}", first.log);
+    // Changes the content so the second compile isn't served from the cache.
+    handle.update(uri.clone(), 17, 17, "!".to_string()).expect("Failed to update");
+    let second = executor.block_on(handle.compile_async(uri.clone())).expect("Compilation failed");
+    assert!(second.success, "{}", second.log);
+    capabilities.assert();
+    compile.assert();
+}
+
+#[test]
+fn test_handle_does_not_recompile_unchanged_content_on_the_server() {
+    use context_runtime::ffi::ContextRuntimeHandle;
+    use context_runtime::ffi_bridge::RuntimeConfigFfi;
+
+    let mut server = mockito::Server::new();
+    server.mock("GET", "/capabilities").with_status(404).create();
+    let compile = server.mock("POST", "/compile")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({ "success": true, "log": "ok", "diagnostics": [] }).to_string())
+        .expect(1)
+        .create();
+
+    let cache_dir = TempDir::new().expect("Failed to create temp dir");
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(server.url()),
+        cache_dir: Some(cache_dir.path().to_string_lossy().into_owned()),
+        ..Default::default()
+    });
+    let uri = "cached.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Cached \stoptext".to_string()).expect("Failed to open");
+
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    for _ in 0..2 {
+        let result = executor.block_on(handle.compile_async(uri.clone())).expect("Compilation failed");
+        assert!(result.success, "{}", result.log);
+    }
+    compile.assert();
+}
+
+// A stand-in server on a raw socket, for answers mockito can't give: late ones and
+// none at all. `handle` gets the start of each request and the connection.
+async fn raw_server<F, Fut>(handle: F) -> String
+where
+    F: Fn(String, tokio::net::TcpStream) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    use tokio::io::AsyncReadExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::sync::Arc::new(handle);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let handle = std::sync::Arc::clone(&handle);
+            tokio::spawn(async move {
+                let mut request = vec![0; 8192];
+                let read = socket.read(&mut request).await.unwrap_or(0);
+                handle(String::from_utf8_lossy(&request[..read]).into_owned(), socket).await;
+            });
+        }
+    });
+    url
+}
+
+const NO_CAPABILITIES: &[u8] = b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+#[tokio::test]
+async fn test_remote_timeouts_are_reported_as_timeouts() {
+    use context_runtime::backend_traits::BackendError;
+    use context_runtime::retry::RetryPolicy;
+    use std::time::Duration;
+
+    // Never answers.
+    let url = raw_server(|_, socket| async move {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        drop(socket);
+    })
+    .await;
+
+    let backend = RemoteBackend::new(url, None)
+        .with_timeout(Some(Duration::from_millis(200)))
+        .with_retry(RetryPolicy { max_attempts: 1, ..Default::default() });
+    let result = backend.compile(request("slow.tex")).await;
+    assert!(matches!(result, Err(BackendError::Timeout { .. })), "{:?}", result);
+}
+
+#[tokio::test]
+async fn test_compiles_outlast_the_request_timeout() {
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    // Has no `/capabilities` and answers `/compile` after longer than the timeout.
+    let url = raw_server(|request, mut socket| async move {
+        if request.starts_with("GET /capabilities") {
+            let _ = socket.write_all(NO_CAPABILITIES).await;
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        let body = serde_json::json!({ "success": true, "log": "slow but done", "diagnostics": [] }).to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = socket.write_all(response.as_bytes()).await;
+    })
+    .await;
+
+    let backend = RemoteBackend::new(url, None).with_timeout(Some(Duration::from_millis(200)));
+    let result = backend.compile(request("slow.tex")).await.expect("Compilation failed");
+    assert!(result.log.contains("slow but done"));
+}
+
+#[tokio::test]
+async fn test_compiles_are_not_resent_once_they_reached_the_server() {
+    use context_runtime::retry::RetryPolicy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
+
+    // Takes each compile and drops the connection without an answer.
+    let compiles = std::sync::Arc::new(AtomicUsize::new(0));
+    let counted = std::sync::Arc::clone(&compiles);
+    let url = raw_server(move |request, mut socket| {
+        let counted = std::sync::Arc::clone(&counted);
+        async move {
+            if request.starts_with("GET /capabilities") {
+                let _ = socket.write_all(NO_CAPABILITIES).await;
+            } else if request.starts_with("POST /compile") {
+                counted.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    })
+    .await;
+
+    let backend = RemoteBackend::new(url, None).with_retry(RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::ZERO,
+        ..Default::default()
+    });
+    assert!(backend.compile(request("dropped.tex")).await.is_err());
+    assert_eq!(compiles.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,206 @@
+use std::time::Duration;
+use context_runtime::backend_traits::{BackendError, CompileOptions};
+use context_runtime::progress::CompileEvent;
+use context_runtime::protocol::CompileRequest;
+use context_runtime::remote_client::RemoteClient;
+use mockito::{Matcher, Server, ServerGuard};
+use tokio_util::sync::CancellationToken;
+
+fn compile_request() -> CompileRequest {
+    CompileRequest {
+        uri: "book.tex".to_string(),
+        content: r"\starttext Long \stoptext".to_string(),
+        files: Vec::new(),
+        options: CompileOptions::default(),
+        ..Default::default()
+    }
+}
+
+fn status(state: &str, extra: serde_json::Value) -> serde_json::Value {
+    let mut status = serde_json::json!({ "job_id": "job-1", "state": state });
+    status.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+    status
+}
+
+fn finished() -> serde_json::Value {
+    status("finished", serde_json::json!({
+        "result": { "success": true, "log": "done", "output_url": null, "diagnostics": [] },
+    }))
+}
+
+async fn job_server() -> ServerGuard {
+    let mut server = Server::new_async().await;
+    server.mock("GET", "/capabilities")
+        .with_header("content-type", "application/json")
+        .with_body(serde_json::json!({
+            "protocol_version": 1,
+            "min_protocol_version": 1,
+            "features": ["options", "project_files", "jobs"],
+        }).to_string())
+        .create_async()
+        .await;
+    server.mock("POST", "/jobs")
+        .match_body(Matcher::PartialJson(serde_json::json!({ "uri": "book.tex" })))
+        .with_status(202)
+        .with_header("content-type", "application/json")
+        .with_body(status("queued", serde_json::json!({})).to_string())
+        .create_async()
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn test_job_progress_streams_over_events() {
+    let mut server = job_server().await;
+    let running = status("running", serde_json::json!({ "progress": { "pass": 2, "pages": 40, "current_file": "ch1.tex" } }));
+    let stream = format!(
+        ": keep-alive\n\nevent: status\ndata: {}\n\nevent: status\r\ndata: {}\r\n\r\n",
+        running, finished()
+    );
+    server.mock("GET", "/jobs/job-1/events")
+        .with_header("content-type", "text/event-stream")
+        .with_body(stream)
+        .create_async()
+        .await;
+    let poll = server.mock("GET", "/jobs/job-1").expect(0).create_async().await;
+
+    let (events, mut progress) = tokio::sync::mpsc::unbounded_channel();
+    let client = RemoteClient::new(server.url(), None);
+    let compiled = client.compile(&compile_request(), &CancellationToken::new(), Some(&events)).await
+        .expect("Job should finish");
+
+    assert!(compiled.response.success);
+    assert_eq!(compiled.response.log, "done");
+    match progress.try_recv() {
+        Ok(CompileEvent::Progress(p)) => assert_eq!((p.pass, p.pages, p.current_file.as_deref()), (2, 40, Some("ch1.tex"))),
+        other => panic!("Expected progress, got {:?}", other),
+    }
+    poll.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_job_is_polled_without_events() {
+    let mut server = job_server().await;
+    server.mock("GET", "/jobs/job-1/events").with_status(404).create_async().await;
+    let poll = server.mock("GET", "/jobs/job-1")
+        .with_header("content-type", "application/json")
+        .with_body(finished().to_string())
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None).with_poll_interval(Duration::from_millis(10));
+    let compiled = client.compile(&compile_request(), &CancellationToken::new(), None).await
+        .expect("Job should finish");
+
+    assert!(compiled.response.success);
+    poll.assert_async().await;
+}
+
+// A job server whose event stream sends one keep-alive and then goes silent, which
+// mockito can't do without holding up its other responses.
+async fn silent_events_server() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut request = vec![0; 8192];
+                let read = socket.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]).into_owned();
+                let (status, content_type, body) = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                    ["GET", "/capabilities"] => ("200 OK", "application/json", serde_json::json!({
+                        "protocol_version": 1, "min_protocol_version": 1, "features": ["jobs"],
+                    })),
+                    ["POST", "/jobs"] => ("202 Accepted", "application/json", status("queued", serde_json::json!({}))),
+                    ["GET", "/jobs/job-1"] => ("200 OK", "application/json", finished()),
+                    ["GET", "/jobs/job-1/events"] => {
+                        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n: keep-alive\n\n";
+                        let _ = socket.write_all(head.as_bytes()).await;
+                        tokio::time::sleep(Duration::from_secs(30)).await;
+                        return;
+                    }
+                    _ => ("404 Not Found", "application/json", serde_json::json!({})),
+                };
+                let body = body.to_string();
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status, content_type, body.len(), body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    });
+    url
+}
+
+#[tokio::test]
+async fn test_silent_event_stream_falls_back_to_polling() {
+    let client = RemoteClient::new(silent_events_server().await, None)
+        .with_poll_interval(Duration::from_millis(10))
+        .with_events_idle_timeout(Duration::from_millis(200));
+
+    let compiled = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.compile(&compile_request(), &CancellationToken::new(), None),
+    )
+    .await
+    .expect("Waited for the silent stream")
+    .expect("Job should finish");
+    assert!(compiled.response.success);
+}
+
+#[tokio::test]
+async fn test_failed_job_is_unavailable() {
+    let mut server = job_server().await;
+    server.mock("GET", "/jobs/job-1/events")
+        .with_header("content-type", "text/event-stream")
+        .with_body(format!(
+            "data: {}\n\n",
+            status("failed", serde_json::json!({ "error": { "error": "disk full", "code": "internal" } }))
+        ))
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None);
+    let result = client.compile(&compile_request(), &CancellationToken::new(), None).await;
+    assert!(matches!(&result, Err(BackendError::Unavailable(msg)) if msg.contains("disk full")), "{:?}", result.map(|c| c.response));
+}
+
+#[tokio::test]
+async fn test_cancelling_deletes_the_job() {
+    let mut server = job_server().await;
+    server.mock("GET", "/jobs/job-1/events").with_status(404).create_async().await;
+    server.mock("GET", "/jobs/job-1")
+        .with_header("content-type", "application/json")
+        .with_body(status("running", serde_json::json!({})).to_string())
+        .create_async()
+        .await;
+    let delete = server.mock("DELETE", "/jobs/job-1")
+        .with_header("content-type", "application/json")
+        .with_body(status("running", serde_json::json!({})).to_string())
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None).with_poll_interval(Duration::from_millis(10));
+    let cancel = CancellationToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        canceller.cancel();
+    });
+
+    let result = client.compile(&compile_request(), &cancel, None).await;
+    assert!(matches!(result, Err(BackendError::Cancelled)));
+
+    // The delete is sent in the background.
+    for _ in 0..100 {
+        if delete.matched_async().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("The job was not deleted");
+}
@@ -0,0 +1,297 @@
+use std::time::Duration;
+
+use context_runtime::backend_traits::{BackendError, CompileOptions};
+use context_runtime::offline_queue::OfflineQueue;
+use context_runtime::progress::CompileEvent;
+use context_runtime::protocol::CompileRequest;
+use context_runtime::remote_client::RemoteClient;
+use context_runtime::retry::RetryPolicy;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+fn compile_request() -> CompileRequest {
+    CompileRequest {
+        uri: "retry.tex".to_string(),
+        content: r"\starttext Retry \stoptext".to_string(),
+        files: Vec::new(),
+        options: CompileOptions::default(),
+        ..Default::default()
+    }
+}
+
+fn fast_retry(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(20),
+        jitter_percent: 0,
+    }
+}
+
+#[test]
+fn test_backoff_doubles_up_to_the_cap() {
+    let policy = RetryPolicy {
+        max_attempts: 6,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+        jitter_percent: 0,
+    };
+    let delays: Vec<_> = (1..=5).map(|attempt| policy.backoff(attempt).as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 500, 500]);
+    assert!(policy.allows_retry(5));
+    assert!(!policy.allows_retry(6));
+    assert!(!RetryPolicy::none().allows_retry(1));
+
+    let jittered = RetryPolicy { jitter_percent: 50, ..policy };
+    for _ in 0..50 {
+        let delay = jittered.backoff(2);
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200), "{:?}", delay);
+    }
+}
+
+#[tokio::test]
+async fn test_transient_failures_are_retried_with_events() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities").with_status(404).create_async().await;
+    let compile = server.mock("POST", "/compile")
+        .with_status(503)
+        .expect(3)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None).with_retry(fast_retry(3));
+    let (events, mut received) = mpsc::unbounded_channel();
+    let result = client.compile(&compile_request(), &CancellationToken::new(), Some(&events)).await;
+
+    assert!(matches!(result, Err(BackendError::Unavailable(_))), "got {:?}", result.map(|c| c.response));
+    compile.assert_async().await;
+
+    drop(events);
+    let mut attempts = Vec::new();
+    while let Some(event) = received.recv().await {
+        if let CompileEvent::Retrying { attempt, .. } = event {
+            attempts.push(attempt);
+        }
+    }
+    assert_eq!(attempts, [1, 2]);
+}
+
+#[tokio::test]
+async fn test_client_errors_are_not_retried() {
+    let mut server = mockito::Server::new_async().await;
+    server.mock("GET", "/capabilities").with_status(404).create_async().await;
+    let compile = server.mock("POST", "/compile")
+        .with_status(401)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let client = RemoteClient::new(server.url(), None).with_retry(fast_retry(3));
+    let result = client.compile(&compile_request(), &CancellationToken::new(), None).await;
+
+    let error = result.map(|c| c.response).expect_err("401 should fail the compile");
+    assert!(!error.is_transient(), "got {:?}", error);
+    compile.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_offline_queue_keeps_each_document_once() {
+    let queue = OfflineQueue::new();
+    queue.push("b.tex", queue.start());
+    queue.push("a.tex", queue.start());
+    queue.push("b.tex", queue.start());
+    assert_eq!(queue.uris(), ["a.tex", "b.tex"]);
+
+    tokio::time::timeout(Duration::from_secs(1), queue.wait_for_documents()).await
+        .expect("Queued documents should wake the waiter");
+
+    assert!(queue.remove("a.tex"));
+    assert!(!queue.remove("a.tex"));
+    assert_eq!(queue.drain(), ["b.tex"]);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn test_offline_queue_skips_documents_compiled_since() {
+    let queue = OfflineQueue::new();
+    let (older, newer) = (queue.start(), queue.start());
+
+    // The newer attempt succeeded before the older one reported its failure.
+    queue.compiled("a.tex", newer);
+    queue.push("a.tex", older);
+    assert!(queue.is_empty());
+
+    // A success doesn't settle a failure that came after it.
+    let latest = queue.start();
+    queue.push("a.tex", latest);
+    queue.compiled("a.tex", newer);
+    assert_eq!(queue.uris(), ["a.tex"]);
+
+    queue.compiled("a.tex", latest);
+    assert!(queue.is_empty());
+}
+
+// Records what the handle delivers to the app.
+#[derive(Default, Clone)]
+struct RecordingCallback {
+    completed: std::sync::Arc<std::sync::Mutex<Vec<bool>>>,
+    errors: std::sync::Arc<std::sync::Mutex<Vec<context_runtime::ffi_bridge::RuntimeErrorFfi>>>,
+}
+
+impl context_runtime::ffi::LiveUpdateCallback for RecordingCallback {
+    fn on_highlights_updated(&self, _: String, _: Vec<context_runtime::ffi_bridge::HighlightFfi>) {}
+    fn on_diagnostics_updated(&self, _: String, _: Vec<context_runtime::ffi_bridge::DiagnosticFfi>) {}
+    fn on_compilation_completed(&self, _: String, result: context_runtime::ffi_bridge::CompileResultFfi) {
+        self.completed.lock().unwrap().push(result.success);
+    }
+    fn on_error(&self, error: context_runtime::ffi_bridge::RuntimeErrorFfi) {
+        self.errors.lock().unwrap().push(error);
+    }
+    fn on_compilation_progress(&self, _: String, _: context_runtime::ffi_bridge::CompileProgressFfi) {}
+    fn on_log_chunk(&self, _: String, _: String) {}
+}
+
+// A handle whose server refuses connections, compiling locally with `local_executable`.
+fn unreachable_server_handle(local_executable: String) -> std::sync::Arc<context_runtime::ffi::ContextRuntimeHandle> {
+    use context_runtime::ffi_bridge::{RetryPolicyFfi, RuntimeConfigFfi};
+
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let server_url = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    context_runtime::ffi::ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(server_url),
+        local_executable: Some(local_executable),
+        remote_retry: Some(RetryPolicyFfi { max_attempts: 1, initial_backoff_ms: 0, max_backoff_ms: 0, jitter_percent: 0 }),
+        compile_cache_entries: 0,
+        ..Default::default()
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn test_unreachable_server_falls_back_without_queueing() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    let script = "#!/bin/sh\nfor last; do :; done\nprintf '%%PDF-1.4\\n/Type /Page\\n%%%%EOF\\n' > \"${last%.tex}.pdf\"\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let handle = unreachable_server_handle(mtxrun.display().to_string());
+    let callback = RecordingCallback::default();
+    handle.set_live_callback(Some(Box::new(callback.clone())));
+    let uri = "fallback.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Fallback \stoptext".to_string()).expect("Failed to open");
+
+    handle.compile(uri).expect("Failed to queue");
+    for _ in 0..250 {
+        if !callback.completed.lock().unwrap().is_empty() && handle.get_active_jobs().is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    // Long enough for the first reconnect probe, which would resubmit a queued document.
+    std::thread::sleep(Duration::from_millis(2500));
+
+    assert_eq!(*callback.completed.lock().unwrap(), [true]);
+    assert!(callback.errors.lock().unwrap().is_empty(), "{:?}", callback.errors.lock().unwrap());
+    assert!(handle.get_offline_documents().is_empty());
+}
+
+#[test]
+fn test_unreachable_server_without_local_context_queues_the_document() {
+    use context_runtime::ffi_bridge::{ContextErrorFfi, RuntimeErrorFfi};
+
+    let handle = unreachable_server_handle("/nonexistent/mtxrun".to_string());
+    let callback = RecordingCallback::default();
+    handle.set_live_callback(Some(Box::new(callback.clone())));
+    let uri = "queued.tex".to_string();
+    handle.open(uri.clone(), r"\starttext Queued \stoptext".to_string()).expect("Failed to open");
+
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    let result = executor.block_on(handle.compile_async(uri.clone()));
+    assert!(matches!(result, Err(ContextErrorFfi::Offline { .. })), "{:?}", result);
+    assert_eq!(handle.get_offline_documents(), [uri]);
+    assert!(callback.completed.lock().unwrap().is_empty());
+    assert!(matches!(callback.errors.lock().unwrap().as_slice(), [RuntimeErrorFfi::Offline { .. }]));
+}
+
+#[test]
+fn test_dropping_the_handle_while_documents_are_resubmitted() {
+    use context_runtime::ffi_bridge::{RetryPolicyFfi, RuntimeConfigFfi};
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // Dropping the runtime from one of its own workers panics there, out of the test's sight.
+    let worker_panics = Arc::new(AtomicUsize::new(0));
+    let previous_hook = std::panic::take_hook();
+    let panics = Arc::clone(&worker_panics);
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() == Some("tokio-runtime-worker") {
+            panics.fetch_add(1, Ordering::SeqCst);
+        }
+        previous_hook(info);
+    }));
+
+    // Unavailable until `reachable`, then healthy with compiles that never finish.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let server_url = format!("http://{}", listener.local_addr().unwrap());
+    let reachable = Arc::new(AtomicBool::new(false));
+    let compiles = Arc::new(AtomicUsize::new(0));
+    let (server_reachable, server_compiles) = (Arc::clone(&reachable), Arc::clone(&compiles));
+    std::thread::spawn(move || {
+        let mut running = Vec::new();
+        for mut socket in listener.incoming().flatten() {
+            let mut request = vec![0; 8192];
+            let read = socket.read(&mut request).unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response: &[u8] = if request.starts_with("GET /capabilities") {
+                b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            } else if !server_reachable.load(Ordering::SeqCst) {
+                b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            } else if request.starts_with("GET /health") {
+                b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            } else {
+                server_compiles.fetch_add(1, Ordering::SeqCst);
+                running.push(socket);
+                continue;
+            };
+            let _ = socket.write_all(response);
+        }
+    });
+
+    let handle = context_runtime::ffi::ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: true,
+        server_url: Some(server_url),
+        local_executable: Some("/nonexistent/mtxrun".to_string()),
+        remote_retry: Some(RetryPolicyFfi { max_attempts: 1, initial_backoff_ms: 0, max_backoff_ms: 0, jitter_percent: 0 }),
+        compile_cache_entries: 0,
+        ..Default::default()
+    });
+    let executor = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Failed to build executor");
+    for index in 0..10 {
+        let uri = format!("queued-{}.tex", index);
+        handle.open(uri.clone(), format!(r"\starttext Queued {} \stoptext", index)).expect("Failed to open");
+        assert!(executor.block_on(handle.compile_async(uri)).is_err());
+    }
+    assert_eq!(handle.get_offline_documents().len(), 10);
+
+    reachable.store(true, Ordering::SeqCst);
+    handle.notify_network_available();
+    for _ in 0..250 {
+        if compiles.load(Ordering::SeqCst) > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert!(compiles.load(Ordering::SeqCst) > 0, "No document was resubmitted");
+
+    // The resubmitted compiles are still running; the handle goes away with them.
+    let weak = std::sync::Arc::downgrade(&handle);
+    drop(handle);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(worker_panics.load(Ordering::SeqCst), 0);
+}
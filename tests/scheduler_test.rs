@@ -0,0 +1,119 @@
+use context_runtime::scheduler::CompileScheduler;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+#[tokio::test]
+async fn test_rapid_edits_coalesce_into_one_compile() {
+    let scheduler = CompileScheduler::new(Handle::current(), Duration::from_millis(50));
+    let compiled = Arc::new(Mutex::new(Vec::new()));
+
+    for version in 1..=3 {
+        let compiled = Arc::clone(&compiled);
+        scheduler.schedule("doc.tex", move || async move {
+            compiled.lock().unwrap().push(version);
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    assert!(scheduler.is_scheduled("doc.tex"));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+
+    assert_eq!(*compiled.lock().unwrap(), vec![3]);
+    assert!(!scheduler.is_scheduled("doc.tex"));
+}
+
+#[tokio::test]
+async fn test_newer_edit_supersedes_in_flight_compile() {
+    let scheduler = CompileScheduler::new(Handle::current(), Duration::ZERO);
+    let delivered = Arc::new(Mutex::new(Vec::new()));
+
+    let first = Arc::clone(&delivered);
+    scheduler.schedule("doc.tex", move || async move {
+        // Simulates a long-running compile that is still busy when the next edit arrives.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        first.lock().unwrap().push("stale");
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let second = Arc::clone(&delivered);
+    scheduler.schedule("doc.tex", move || async move {
+        second.lock().unwrap().push("newest");
+    });
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(*delivered.lock().unwrap(), vec!["newest"]);
+}
+
+#[tokio::test]
+async fn test_documents_are_scheduled_independently() {
+    let scheduler = CompileScheduler::new(Handle::current(), Duration::from_millis(20));
+    let compiled = Arc::new(Mutex::new(Vec::new()));
+
+    for uri in ["a.tex", "b.tex", "c.tex"] {
+        let compiled = Arc::clone(&compiled);
+        scheduler.schedule(uri, move || async move {
+            compiled.lock().unwrap().push(uri);
+        });
+    }
+    assert!(scheduler.cancel("c.tex"));
+    assert!(!scheduler.cancel("missing.tex"));
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut compiled = compiled.lock().unwrap().clone();
+    compiled.sort();
+    assert_eq!(compiled, vec!["a.tex", "b.tex"]);
+}
+
+// Records the compilation results delivered to the app.
+#[derive(Default)]
+struct CompletedCallback {
+    completed: Arc<Mutex<Vec<String>>>,
+}
+
+impl context_runtime::ffi::LiveUpdateCallback for CompletedCallback {
+    fn on_highlights_updated(&self, _: String, _: Vec<context_runtime::ffi_bridge::HighlightFfi>) {}
+    fn on_diagnostics_updated(&self, _: String, _: Vec<context_runtime::ffi_bridge::DiagnosticFfi>) {}
+    fn on_compilation_completed(&self, uri: String, _: context_runtime::ffi_bridge::CompileResultFfi) {
+        self.completed.lock().unwrap().push(uri);
+    }
+    fn on_error(&self, _: context_runtime::ffi_bridge::RuntimeErrorFfi) {}
+    fn on_compilation_progress(&self, _: String, _: context_runtime::ffi_bridge::CompileProgressFfi) {}
+    fn on_log_chunk(&self, _: String, _: String) {}
+}
+
+#[cfg(unix)]
+#[test]
+fn test_turning_auto_compile_off_stops_running_jobs() {
+    use context_runtime::ffi::ContextRuntimeHandle;
+    use context_runtime::ffi_bridge::RuntimeConfigFfi;
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    std::fs::write(&mtxrun, "#!/bin/sh\nsleep 1\n").expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let handle = ContextRuntimeHandle::new_with_config(RuntimeConfigFfi {
+        remote: false,
+        local_executable: Some(mtxrun.display().to_string()),
+        compile_cache_entries: 0,
+        ..Default::default()
+    });
+    let callback = CompletedCallback::default();
+    let completed = Arc::clone(&callback.completed);
+    handle.set_live_callback(Some(Box::new(callback)));
+
+    handle.set_auto_compile(true, 0);
+    handle.open("live.tex".to_string(), r"\starttext Live \stoptext".to_string()).expect("Failed to open");
+    let started = (0..100).any(|_| {
+        std::thread::sleep(Duration::from_millis(10));
+        !handle.get_active_jobs().is_empty()
+    });
+    assert!(started, "The live-preview compile never started");
+
+    handle.set_auto_compile(false, 0);
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(handle.get_active_jobs().is_empty(), "{:?}", handle.get_active_jobs());
+    assert!(completed.lock().unwrap().is_empty(), "A result arrived after auto-compile was turned off");
+}
@@ -0,0 +1,358 @@
+#![cfg(all(unix, feature = "server"))]
+
+use context_runtime::artifact::ArtifactData;
+use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationRequest, LocalBackend, RemoteBackend};
+use context_runtime::progress::CompileEvent;
+use context_runtime::server::{CompileServer, ServerConfig};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tempfile::TempDir;
+
+const PDF: &[u8] = b"%PDF-1.4\n/Type /Page\n%%EOF\n";
+
+// Writes a one-page PDF next to the source file, which is passed as the last argument.
+fn create_dummy_mtxrun(dir: &Path) -> std::path::PathBuf {
+    let path = dir.join("mtxrun");
+    let script = "#!/bin/sh\necho 'dummy mtxrun output'\necho 'mtx-context     | run 1: luametatex'\nfor last; do :; done\nprintf '%%PDF-1.4\\n/Type /Page\\n%%%%EOF\\n' > \"${last%.tex}.pdf\"\n";
+    std::fs::write(&path, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+async fn start_server(temp_dir: &TempDir, token: &str) -> String {
+    start_server_with(temp_dir, token, create_dummy_mtxrun(temp_dir.path())).await
+}
+
+async fn start_server_with(temp_dir: &TempDir, token: &str, mtxrun: std::path::PathBuf) -> String {
+    let local = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+    let server = CompileServer::new(Box::new(local), ServerConfig {
+        auth_token: Some(token.to_string()),
+        artifact_dir: temp_dir.path().join("artifacts"),
+        ..Default::default()
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { server.serve(listener).await });
+    url
+}
+
+fn request(job_id: &str) -> CompilationRequest {
+    CompilationRequest {
+        content: r"\starttext Served \stoptext".to_string(),
+        job_id: job_id.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_remote_backend_compiles_through_server() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+
+    let client = RemoteBackend::new(url, Some("secret".to_string()))
+        .with_cache_dir(Some(temp_dir.path().join("client-cache")));
+    client.health_check().await.expect("Server should be healthy");
+
+    // The server advertises jobs, so this is submitted as one and followed over SSE.
+    let (events, mut progress) = tokio::sync::mpsc::unbounded_channel();
+    let request = CompilationRequest { events: Some(events), ..request("file:///docs/report.tex") };
+    let result = client.compile(request).await.expect("Compilation failed");
+    assert!(result.success);
+    assert!(result.log.contains("dummy mtxrun output"));
+    assert!(matches!(progress.try_recv(), Ok(CompileEvent::Progress(p)) if p.pass == 1));
+
+    let artifact = result.artifact.expect("Missing artifact");
+    let ArtifactData::File(path) = &artifact.data else { panic!("Expected a downloaded file") };
+    assert_eq!(std::fs::read(path).unwrap(), PDF);
+    assert_eq!(artifact.page_count, Some(1));
+}
+
+#[tokio::test]
+async fn test_server_rejects_wrong_token() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+
+    let client = RemoteBackend::new(url.clone(), Some("wrong".to_string()));
+    let result = client.compile(request("report.tex")).await;
+    assert!(matches!(&result, Err(BackendError::Unauthorized(msg)) if msg.contains("401")), "{:?}", result.map(|r| r.success));
+
+    let response = reqwest::get(format!("{}/artifacts/{}", url, "0".repeat(64))).await.unwrap();
+    assert_eq!(response.status(), 401);
+}
+
+#[tokio::test]
+async fn test_server_negotiates_protocol_version() {
+    use context_runtime::protocol::{Capabilities, ErrorBody, ErrorCode, Feature, PROTOCOL_VERSION, VERSION_HEADER};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+    let http = reqwest::Client::new();
+
+    let response = http.get(format!("{}/capabilities", url)).send().await.unwrap();
+    assert_eq!(response.headers()[VERSION_HEADER], PROTOCOL_VERSION.to_string().as_str());
+    let capabilities: Capabilities = response.json().await.unwrap();
+    assert_eq!(capabilities.negotiate(), Some(PROTOCOL_VERSION));
+    assert!(capabilities.supports(Feature::Options));
+
+    let response = http.post(format!("{}/compile", url))
+        .bearer_auth("secret")
+        .header(VERSION_HEADER, (PROTOCOL_VERSION + 1).to_string())
+        .json(&serde_json::json!({ "uri": "future.tex", "content": "" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, ErrorCode::UnsupportedVersion);
+}
+
+#[tokio::test]
+async fn test_server_cancels_deleted_job() {
+    use context_runtime::protocol::{JobState, JobStatus};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    std::fs::write(&mtxrun, "#!/bin/sh\nsleep 30\n").unwrap();
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let url = start_server_with(&temp_dir, "secret", mtxrun).await;
+    let http = reqwest::Client::new();
+
+    let submitted: JobStatus = http.post(format!("{}/jobs", url))
+        .bearer_auth("secret")
+        .json(&serde_json::json!({ "uri": "slow.tex", "content": "" }))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    let job_url = format!("{}/jobs/{}", url, submitted.job_id);
+
+    let response = http.delete(&job_url).bearer_auth("secret").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    loop {
+        let status: JobStatus = http.get(&job_url).bearer_auth("secret").send().await.unwrap().json().await.unwrap();
+        if status.state == JobState::Cancelled {
+            break;
+        }
+        assert!(std::time::Instant::now() < deadline, "Job still {:?}", status.state);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_server_rebuilds_documents_from_deltas() {
+    use context_runtime::protocol::{CompileRequest, ErrorBody, ErrorCode};
+    use context_runtime::remote_client::RemoteClient;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+    let client = RemoteClient::new(url.clone(), Some("secret".to_string()))
+        .with_cache_dir(Some(temp_dir.path().join("client-cache")));
+    let cancel = tokio_util::sync::CancellationToken::new();
+
+    // The second compile is sent as a delta, which the server has to rebuild and hash-check.
+    for content in [r"\starttext Draft \stoptext", r"\starttext Final draft \stoptext"] {
+        let request = CompileRequest { uri: "delta.tex".to_string(), content: content.to_string(), ..Default::default() };
+        let compiled = client.compile(&request, &cancel, None).await.expect("Compilation failed");
+        assert!(compiled.response.success, "{}", compiled.response.log);
+    }
+
+    let response = reqwest::Client::new().post(format!("{}/compile", url))
+        .bearer_auth("secret")
+        .json(&serde_json::json!({
+            "uri": "delta.tex",
+            "version": { "version": 9, "hash": "0" },
+            "base": { "version": 8, "hash": "0" },
+            "delta": [{ "start": 0, "end": 0, "text": "%" }],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+    let body: ErrorBody = response.json().await.unwrap();
+    assert_eq!(body.code, ErrorCode::BaseMismatch);
+}
+
+#[tokio::test]
+async fn test_server_keeps_delta_bases_per_client() {
+    use context_runtime::delta::document_hash;
+    use context_runtime::protocol::CLIENT_HEADER;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+    let http = reqwest::Client::new();
+    let post = |client: &str, body: serde_json::Value| http.post(format!("{}/compile", url))
+        .bearer_auth("secret")
+        .header(CLIENT_HEADER, client)
+        .json(&body)
+        .send();
+
+    for (client, content) in [("a", "Alpha"), ("b", "Beta")] {
+        let version = serde_json::json!({ "version": 1, "hash": document_hash(content, &[]) });
+        let response = post(client, serde_json::json!({ "uri": "main.tex", "content": content, "version": version }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    // Client a's base is still its own, not the one client b sent last.
+    let response = post("a", serde_json::json!({
+        "uri": "main.tex",
+        "version": { "version": 2, "hash": document_hash("Alphabet", &[]) },
+        "base": { "version": 1, "hash": document_hash("Alpha", &[]) },
+        "delta": [{ "start": 5, "end": 5, "text": "bet" }],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_server_forgets_least_recently_compiled_documents() {
+    use context_runtime::delta::document_hash;
+    use context_runtime::protocol::{ErrorBody, ErrorCode};
+
+    // Working files are named after the job, so the evicted document's are gone.
+    #[derive(Debug)]
+    struct Releases {
+        local: LocalBackend,
+        released: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl CompilationBackend for Releases {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        async fn compile(&self, request: CompilationRequest) -> Result<context_runtime::backend_traits::CompilationResult, BackendError> {
+            self.local.compile(request).await
+        }
+
+        async fn release(&self, job_id: &str) {
+            self.released.lock().unwrap().push(job_id.to_string());
+            self.local.release(job_id).await
+        }
+    }
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let released = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let local = LocalBackend::new(Some(create_dummy_mtxrun(temp_dir.path()))).expect("Failed to create local backend");
+    let server = CompileServer::new(Box::new(Releases { local, released: released.clone() }), ServerConfig {
+        artifact_dir: temp_dir.path().join("artifacts"),
+        max_documents: 1,
+        ..Default::default()
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { server.serve(listener).await });
+
+    let http = reqwest::Client::new();
+    let post = |body: serde_json::Value| http.post(format!("{}/compile", url)).json(&body).send();
+    for uri in ["first.tex", "second.tex"] {
+        let version = serde_json::json!({ "version": 1, "hash": document_hash("Text", &[]) });
+        let response = post(serde_json::json!({ "uri": uri, "content": "Text", "version": version })).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+
+    let response = post(serde_json::json!({
+        "uri": "first.tex",
+        "version": { "version": 2, "hash": document_hash("Texts", &[]) },
+        "base": { "version": 1, "hash": document_hash("Text", &[]) },
+        "delta": [{ "start": 4, "end": 4, "text": "s" }],
+    }))
+    .await
+    .unwrap();
+    assert_eq!(response.status(), 412);
+    assert_eq!(response.json::<ErrorBody>().await.unwrap().code, ErrorCode::BaseMismatch);
+    // Compiling first.tex again in turn evicted second.tex.
+    assert_eq!(released.lock().unwrap().first().map(String::as_str), Some("\nfirst.tex"));
+}
+
+#[tokio::test]
+async fn test_clients_compiling_the_same_uri_get_their_own_output() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    // Copies the source into the PDF after a pause, so both jobs overlap.
+    let script = "#!/bin/sh\nfor last; do :; done\nsleep 0.2\n{ printf '%%PDF-1.4\\n'; cat \"$last\"; } > \"${last%.tex}.pdf\"\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let url = start_server_with(&temp_dir, "secret", mtxrun).await;
+
+    let client = |name: &str| RemoteBackend::new(url.clone(), Some("secret".to_string()))
+        .with_cache_dir(Some(temp_dir.path().join(name)));
+    let (first, second) = (client("first"), client("second"));
+    let main = |content: &str| CompilationRequest { content: content.to_string(), ..request("file:///book/main.tex") };
+    let (first, second) = tokio::join!(first.compile(main("first")), second.compile(main("second")));
+
+    for (result, content) in [(first, "first"), (second, "second")] {
+        let artifact = result.expect("Compilation failed").artifact.expect("No PDF");
+        let pdf = std::fs::read(artifact.path().expect("Downloaded PDF")).unwrap();
+        assert!(String::from_utf8_lossy(&pdf).ends_with(content), "Got another client's PDF");
+    }
+}
+
+#[tokio::test]
+async fn test_server_rejects_environments_outside_the_job() {
+    use context_runtime::protocol::{ErrorBody, ErrorCode};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let url = start_server(&temp_dir, "secret").await;
+    let http = reqwest::Client::new();
+
+    for environment in ["/etc/passwd", "../secrets/env.tex", "styles/../../env.tex"] {
+        let response = http.post(format!("{}/compile", url))
+            .bearer_auth("secret")
+            .json(&serde_json::json!({ "uri": "env.tex", "content": "", "options": { "environments": [environment] } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400, "{} was accepted", environment);
+        assert_eq!(response.json::<ErrorBody>().await.unwrap().code, ErrorCode::InvalidOptions);
+    }
+
+    let response = http.post(format!("{}/compile", url))
+        .bearer_auth("secret")
+        .json(&serde_json::json!({ "uri": "env.tex", "content": "", "options": { "environments": ["styles/env.tex"] } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_evicting_a_document_waits_for_its_running_compile() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let mtxrun = temp_dir.path().join("mtxrun");
+    // Reads the source only after a pause, so the eviction happens mid-run.
+    let script = "#!/bin/sh\nfor last; do :; done\nsleep 0.5\n{ printf '%%PDF-1.4\\n'; cat \"$last\"; } > \"${last%.tex}.pdf\"\n";
+    std::fs::write(&mtxrun, script).expect("Failed to write dummy mtxrun");
+    std::fs::set_permissions(&mtxrun, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let local = LocalBackend::new(Some(mtxrun)).expect("Failed to create local backend");
+    let server = CompileServer::new(Box::new(local), ServerConfig {
+        artifact_dir: temp_dir.path().join("artifacts"),
+        max_documents: 1,
+        ..Default::default()
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { server.serve(listener).await });
+
+    let http = reqwest::Client::new();
+    let post = |uri: &str| http.post(format!("{}/compile", url))
+        .json(&serde_json::json!({ "uri": uri, "content": uri }))
+        .send();
+    let running = post("running.tex");
+    let evicting = async {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        post("newer.tex").await
+    };
+    let (running, evicting) = tokio::join!(running, evicting);
+    assert_eq!(evicting.unwrap().status(), 200);
+
+    let response: context_runtime::protocol::CompileResponse = running.unwrap().json().await.unwrap();
+    assert!(response.success, "{}", response.log);
+    let pdf = http.get(format!("{}{}", url, response.output_url.expect("No PDF"))).send().await.unwrap();
+    assert!(String::from_utf8_lossy(&pdf.bytes().await.unwrap()).ends_with("running.tex"));
+}
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use context_runtime::backend_traits::{BackendError, CompilationBackend, CompilationRequest, CompilationResult};
+use context_runtime::runtime::{ContextRuntime, RuntimeConfig};
+use context_runtime::synctex::SyncTex;
+use std::any::Any;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+
+// One page, two lines of text from the main file and one from an included file.
+// Unit 1 means coordinates are in scaled points (65536 sp = 1pt).
+const SAMPLE: &str = "SyncTeX Version:1
+Input:1:./main.tex
+Input:2:/tmp/chapter.tex
+Output:pdf
+Magnification:1000
+Unit:1
+X Offset:0
+Y Offset:0
+Content:
+!120
+{1
+[1,3:4736286,6553600:30785863,39333603,0
+(1,5:4736286,7013245:30785863,655360,196608
+k1,5:5000000,7013245:65536
+)
+(1,7:4736286,9000000:30785863,655360,196608
+)
+(2,2:4736286,12000000:30785863,655360,196608
+)
+]
+}1
+Input:3:/tmp/unused.tex
+Postamble:
+Count:6
+";
+
+fn bp(sp: f64) -> f64 {
+    sp * 72.0 / (72.27 * 65536.0)
+}
+
+#[test]
+fn test_parse_reads_inputs_and_records() {
+    let synctex = SyncTex::parse(SAMPLE).expect("Failed to parse synctex");
+
+    assert_eq!(synctex.records().len(), 5);
+    assert_eq!(synctex.page_count(), 1);
+    assert_eq!(synctex.input_path(2), Some(Path::new("/tmp/chapter.tex")));
+    assert_eq!(synctex.input_path(3), Some(Path::new("/tmp/unused.tex")));
+}
+
+#[test]
+fn test_parse_rejects_missing_content() {
+    assert!(SyncTex::parse("SyncTeX Version:1\nInput:1:main.tex\n").is_err());
+}
+
+#[test]
+fn test_forward_search_finds_line_box() {
+    let synctex = SyncTex::parse(SAMPLE).unwrap()
+        .with_main_input(Path::new("/work/main.tex"));
+    assert_eq!(synctex.main_input(), Some(1));
+
+    let rect = synctex.forward_search(1, 5, 0).expect("No match for line 5");
+    assert_eq!(rect.page, 1);
+    assert!((rect.x - bp(4736286.0)).abs() < 1e-6);
+    assert!((rect.y - bp(7013245.0 - 655360.0)).abs() < 1e-6);
+    assert!((rect.width - bp(30785863.0)).abs() < 1e-6);
+    assert!((rect.height - bp(655360.0 + 196608.0)).abs() < 1e-6);
+}
+
+#[test]
+fn test_forward_search_falls_back_to_nearest_line() {
+    let synctex = SyncTex::parse(SAMPLE).unwrap();
+
+    let rect = synctex.forward_search(1, 8, 0).expect("No fallback match");
+    assert!((rect.y - bp(9000000.0 - 655360.0)).abs() < 1e-6);
+    assert!(synctex.forward_search(9, 1, 0).is_none());
+}
+
+#[test]
+fn test_inverse_search_prefers_innermost_box() {
+    let synctex = SyncTex::parse(SAMPLE).unwrap();
+
+    // Inside the line 7 hbox, which is also inside the line 3 vbox.
+    let x = bp(4736286.0) + 10.0;
+    let y = bp(9000000.0) - 2.0;
+    let position = synctex.inverse_search(1, x, y).expect("No inverse match");
+    assert_eq!(position.file, Path::new("./main.tex"));
+    assert_eq!(position.line, 7);
+
+    let position = synctex.inverse_search(1, x, bp(12000000.0) - 2.0).unwrap();
+    assert_eq!(position.file, Path::new("/tmp/chapter.tex"));
+    assert_eq!(position.line, 2);
+
+    assert!(synctex.inverse_search(2, x, y).is_none());
+}
+
+#[test]
+fn test_load_gzipped_file() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("main.synctex.gz");
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(SAMPLE.as_bytes()).unwrap();
+    std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let synctex = SyncTex::load(&path).expect("Failed to load gzipped synctex");
+    assert_eq!(synctex, SyncTex::parse(SAMPLE).unwrap());
+}
+
+// Every document compiles to the same PDF, so their SyncTeX data is the same too.
+#[derive(Debug)]
+struct SampleBackend;
+
+#[async_trait]
+impl CompilationBackend for SampleBackend {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn compile(&self, _request: CompilationRequest) -> Result<CompilationResult, BackendError> {
+        Ok(CompilationResult {
+            success: true,
+            artifact: None,
+            log: String::new(),
+            errors: vec![],
+            warnings: vec![],
+            synctex: SyncTex::parse(SAMPLE).ok().map(|synctex| synctex.with_main_input(Path::new("main.tex"))),
+            backend: "sample".to_string(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "sample"
+    }
+
+    async fn health_check(&self) -> Result<(), BackendError> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_inverse_search_uses_the_given_document() {
+    let runtime = ContextRuntime::new_with_backend(RuntimeConfig::default(), Box::new(SampleBackend));
+    for uri in ["a.tex", "b.tex"] {
+        runtime.open_document(uri.to_string(), r"\starttext \stoptext".to_string()).unwrap();
+        runtime.compile_document_streaming(uri, Default::default(), None).await.expect("Compilation failed");
+    }
+
+    let (x, y) = (bp(4736286.0) + 10.0, bp(9000000.0) - 2.0);
+    for uri in ["a.tex", "b.tex"] {
+        let location = runtime.inverse_search(uri, 1, x, y).expect("No inverse match");
+        assert_eq!((location.uri.as_str(), location.line), (uri, 7));
+    }
+
+    runtime.close_document("a.tex");
+    assert!(runtime.inverse_search("a.tex", 1, x, y).is_none());
+    assert!(runtime.forward_search("a.tex", 7, 0).is_none());
+}